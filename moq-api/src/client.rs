@@ -31,6 +31,54 @@ impl Client {
 		let url = self.url.join("origin/")?.join(id)?;
 
 		let resp = self.client.post(url).json(origin).send().await?;
+		if resp.status() == reqwest::StatusCode::CONFLICT {
+			return Err(ApiError::Duplicate);
+		}
+
+		resp.error_for_status()?;
+
+		Ok(())
+	}
+
+	/// Add the origin's URL as an alternate for the existing origin, returning false if there is none.
+	pub async fn add_alternate(&mut self, id: &str, origin: &Origin) -> Result<bool, ApiError> {
+		let url = self.url.join("origin/")?.join(&format!("{}/alternate", id))?;
+
+		let resp = self.client.post(url).json(origin).send().await?;
+		if resp.status() == reqwest::StatusCode::NOT_FOUND {
+			return Ok(false);
+		}
+
+		resp.error_for_status()?;
+
+		Ok(true)
+	}
+
+	/// Remove the origin's URL from the alternates of the existing origin, if any.
+	pub async fn remove_alternate(&mut self, id: &str, origin: &Origin) -> Result<(), ApiError> {
+		let url = self.url.join("origin/")?.join(&format!("{}/alternate", id))?;
+
+		let resp = self.client.delete(url).json(origin).send().await?;
+		if resp.status() == reqwest::StatusCode::NOT_FOUND {
+			return Ok(());
+		}
+
+		resp.error_for_status()?;
+
+		Ok(())
+	}
+
+	/// Set the origin, replacing any existing origin with the same owner in a single operation.
+	///
+	/// Returns [ApiError::Duplicate] if there's an existing origin, unless the server allows takeover and the owner matches.
+	pub async fn replace_origin(&mut self, id: &str, origin: &Origin) -> Result<(), ApiError> {
+		let url = self.url.join("origin/")?.join(id)?;
+
+		let resp = self.client.put(url).json(origin).send().await?;
+		if resp.status() == reqwest::StatusCode::CONFLICT {
+			return Err(ApiError::Duplicate);
+		}
+
 		resp.error_for_status()?;
 
 		Ok(())
@@ -45,10 +93,29 @@ impl Client {
 		Ok(())
 	}
 
+	/// Delete the origin, unless it was replaced by another one.
+	pub async fn delete_origin_if(&mut self, id: &str, origin: &Origin) -> Result<(), ApiError> {
+		let url = self.url.join("origin/")?.join(id)?;
+
+		let resp = self.client.delete(url).json(origin).send().await?;
+		if resp.status() == reqwest::StatusCode::CONFLICT {
+			return Ok(());
+		}
+
+		resp.error_for_status()?;
+
+		Ok(())
+	}
+
+	/// Refresh the origin's expiration, returning [ApiError::Duplicate] if another origin has replaced it.
 	pub async fn patch_origin(&mut self, id: &str, origin: &Origin) -> Result<(), ApiError> {
 		let url = self.url.join("origin/")?.join(id)?;
 
 		let resp = self.client.patch(url).json(origin).send().await?;
+		if resp.status() == reqwest::StatusCode::CONFLICT {
+			return Err(ApiError::Duplicate);
+		}
+
 		resp.error_for_status()?;
 
 		Ok(())
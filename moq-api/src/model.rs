@@ -2,7 +2,17 @@ use serde::{Deserialize, Serialize};
 
 use url::Url;
 
-#[derive(Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 pub struct Origin {
 	pub url: Url,
+
+	/// Other nodes serving the same broadcast, tried in order if `url` is unreachable.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub alternates: Vec<Url>,
+
+	/// Identifies the publisher, so only it can replace the origin when taking over the broadcast.
+	///
+	/// This is a hash of a token known only to the publisher, since anybody can read the origin.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub owner: Option<String>,
 }
@@ -1,10 +1,10 @@
 use std::net;
 
 use axum::{
-	extract::{Path, State},
+	extract::{FromRef, Path, State},
 	http::StatusCode,
 	response::{IntoResponse, Response},
-	routing::get,
+	routing::{get, post},
 	Json, Router,
 };
 
@@ -25,6 +25,13 @@ pub struct ServerConfig {
 	/// Connect to the given redis instance
 	#[arg(long)]
 	pub redis: url::Url,
+
+	/// Let PUT replace an existing origin with the same owner, so its publisher can take over the broadcast from another relay.
+	///
+	/// The owner is a hash of a token sent by the publisher, and an origin without one is never replaced.
+	/// Otherwise PUT only sets a missing origin.
+	#[arg(long)]
+	pub takeover: bool,
 }
 
 pub struct Server {
@@ -50,10 +57,15 @@ impl Server {
 				"/origin/:id",
 				get(get_origin)
 					.post(set_origin)
+					.put(replace_origin)
 					.delete(delete_origin)
 					.patch(patch_origin),
 			)
-			.with_state(redis);
+			.route("/origin/:id/alternate", post(add_alternate).delete(remove_alternate))
+			.with_state(AppState {
+				redis,
+				takeover: self.config.takeover,
+			});
 
 		log::info!("serving requests: bind={}", self.config.listen);
 
@@ -65,6 +77,20 @@ impl Server {
 	}
 }
 
+#[derive(Clone)]
+struct AppState {
+	redis: ConnectionManager,
+
+	// Whether PUT can replace an existing origin with the same owner.
+	takeover: bool,
+}
+
+impl FromRef<AppState> for ConnectionManager {
+	fn from_ref(state: &AppState) -> Self {
+		state.redis.clone()
+	}
+}
+
 async fn get_origin(
 	Path(id): Path<String>,
 	State(mut redis): State<ConnectionManager>,
@@ -106,10 +132,59 @@ async fn set_origin(
 	Ok(())
 }
 
-async fn delete_origin(Path(id): Path<String>, State(mut redis): State<ConnectionManager>) -> Result<(), AppError> {
+// Set the origin even if one already exists with the same owner, so its publisher can take over the broadcast.
+// Without takeover, this is the same as set_origin.
+async fn replace_origin(
+	State(state): State<AppState>,
+	Path(id): Path<String>,
+	Json(origin): Json<Origin>,
+) -> Result<(), AppError> {
+	if !state.takeover {
+		return set_origin(State(state.redis), Path(id), Json(origin)).await;
+	}
+
+	let mut redis = state.redis;
+
+	let key = origin_key(&id);
+	let payload = serde_json::to_string(&origin)?;
+
+	// A script so the origin is never missing in between, and another owner can't set it between the check and the set.
+	let res: i32 = redis::Script::new(REPLACE_ORIGIN)
+		.key(key)
+		.arg(payload)
+		.arg(origin.owner.as_deref().unwrap_or_default())
+		.invoke_async(&mut redis)
+		.await?;
+
+	match res {
+		-1 => Err(AppError::Duplicate),
+		_ => Ok(()),
+	}
+}
+
+// Delete the origin, or only if it matches the body so we don't delete another publisher's origin.
+async fn delete_origin(
+	Path(id): Path<String>,
+	State(mut redis): State<ConnectionManager>,
+	origin: Option<Json<Origin>>,
+) -> Result<(), AppError> {
 	let key = origin_key(&id);
-	match redis.del(key).await? {
+
+	let res: i32 = match origin {
+		// A script so another publisher can't take over between the check and the delete.
+		Some(Json(origin)) => {
+			redis::Script::new(DELETE_ORIGIN)
+				.key(key)
+				.arg(origin.url.as_str())
+				.invoke_async(&mut redis)
+				.await?
+		}
+		None => redis.del(key).await?,
+	};
+
+	match res {
 		0 => Err(AppError::NotFound),
+		-1 => Err(AppError::Duplicate),
 		_ => Ok(()),
 	}
 }
@@ -122,18 +197,150 @@ async fn patch_origin(
 ) -> Result<(), AppError> {
 	let key = origin_key(&id);
 
-	// Make sure the contents haven't changed
-	// TODO make a LUA script to do this all in one operation.
-	let payload: Option<String> = redis.get(&key).await?;
-	let payload = payload.ok_or(AppError::NotFound)?;
-	let expected: Origin = serde_json::from_str(&payload)?;
+	// Make sure the origin hasn't changed, although the alternates may have been updated by someone else.
+	let res: i32 = redis::Script::new(PATCH_ORIGIN)
+		.key(key)
+		.arg(origin.url.as_str())
+		.invoke_async(&mut redis)
+		.await?;
 
-	if expected != origin {
-		return Err(AppError::Duplicate);
+	match res {
+		0 => Err(AppError::NotFound),
+		-1 => Err(AppError::Duplicate),
+		_ => Ok(()),
 	}
+}
+
+// Set the origin with a 10 minute timeout, unless there's an existing origin with a different or no owner, returning -1 if so.
+const REPLACE_ORIGIN: &str = r#"
+local payload = redis.call("GET", KEYS[1])
+if payload then
+	local owner = cjson.decode(payload).owner
+	if ARGV[2] == "" or owner ~= ARGV[2] then
+		return -1
+	end
+end
+
+redis.call("SET", KEYS[1], ARGV[1], "EX", 600)
+return 1
+"#;
+
+// Delete the origin only if it has the given URL, returning -1 if it doesn't.
+const DELETE_ORIGIN: &str = r#"
+local payload = redis.call("GET", KEYS[1])
+if not payload then
+	return 0
+end
+
+if cjson.decode(payload).url ~= ARGV[1] then
+	return -1
+end
+
+return redis.call("DEL", KEYS[1])
+"#;
+
+// Reset the timeout to 10 minutes only if the origin has the given URL, returning -1 if it doesn't.
+const PATCH_ORIGIN: &str = r#"
+local payload = redis.call("GET", KEYS[1])
+if not payload then
+	return 0
+end
+
+if cjson.decode(payload).url ~= ARGV[1] then
+	return -1
+end
+
+return redis.call("EXPIRE", KEYS[1], 600)
+"#;
+
+// Append the URL to the alternates of the existing origin, keeping its expiration.
+const ADD_ALTERNATE: &str = r#"
+local payload = redis.call("GET", KEYS[1])
+if not payload then
+	return 0
+end
+
+local origin = cjson.decode(payload)
+if origin.url == ARGV[1] then
+	return 1
+end
+
+local alternates = origin.alternates or {}
+for _, url in ipairs(alternates) do
+	if url == ARGV[1] then
+		return 1
+	end
+end
+
+table.insert(alternates, ARGV[1])
+origin.alternates = alternates
+
+redis.call("SET", KEYS[1], cjson.encode(origin), "KEEPTTL")
+return 1
+"#;
+
+// Remove the URL from the alternates of the existing origin, keeping its expiration.
+const REMOVE_ALTERNATE: &str = r#"
+local payload = redis.call("GET", KEYS[1])
+if not payload then
+	return 0
+end
+
+local origin = cjson.decode(payload)
+
+local alternates = {}
+for _, url in ipairs(origin.alternates or {}) do
+	if url ~= ARGV[1] then
+		table.insert(alternates, url)
+	end
+end
+
+-- An empty table would be encoded as an object, so omit the field instead.
+if #alternates == 0 then
+	origin.alternates = nil
+else
+	origin.alternates = alternates
+end
+
+redis.call("SET", KEYS[1], cjson.encode(origin), "KEEPTTL")
+return 1
+"#;
+
+// Register another node serving the same broadcast, so subscribers can fall back to it.
+async fn add_alternate(
+	State(mut redis): State<ConnectionManager>,
+	Path(id): Path<String>,
+	Json(origin): Json<Origin>,
+) -> Result<(), AppError> {
+	let key = origin_key(&id);
+
+	// A script so a concurrent update can't lose the alternate.
+	let res: i32 = redis::Script::new(ADD_ALTERNATE)
+		.key(key)
+		.arg(origin.url.as_str())
+		.invoke_async(&mut redis)
+		.await?;
 
-	// Reset the timeout to 10 minutes.
-	match redis.expire(key, 600).await? {
+	match res {
+		0 => Err(AppError::NotFound),
+		_ => Ok(()),
+	}
+}
+
+async fn remove_alternate(
+	State(mut redis): State<ConnectionManager>,
+	Path(id): Path<String>,
+	Json(origin): Json<Origin>,
+) -> Result<(), AppError> {
+	let key = origin_key(&id);
+
+	let res: i32 = redis::Script::new(REMOVE_ALTERNATE)
+		.key(key)
+		.arg(origin.url.as_str())
+		.invoke_async(&mut redis)
+		.await?;
+
+	match res {
 		0 => Err(AppError::NotFound),
 		_ => Ok(()),
 	}
@@ -169,3 +376,234 @@ impl IntoResponse for AppError {
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+	use mlua::{Lua, LuaSerdeExt, Value, Variadic};
+
+	use super::*;
+
+	// A value in the fake redis, with the TTL if any.
+	#[derive(Clone, Debug, PartialEq)]
+	struct Entry {
+		value: String,
+		ttl: Option<i64>,
+	}
+
+	// Runs the scripts with Lua 5.1 like redis, emulating the few commands and the cjson library they use.
+	struct Redis {
+		lua: Lua,
+		store: Rc<RefCell<HashMap<String, Entry>>>,
+	}
+
+	impl Redis {
+		fn new() -> Self {
+			let lua = Lua::new();
+			let store: Rc<RefCell<HashMap<String, Entry>>> = Default::default();
+
+			let db = store.clone();
+			let call = lua
+				.create_function(move |lua, args: Variadic<String>| {
+					let mut db = db.borrow_mut();
+					let key = args[1].clone();
+
+					let res = match args[0].as_str() {
+						// A missing key is returned as false, not nil.
+						"GET" => match db.get(&key) {
+							Some(entry) => Value::String(lua.create_string(&entry.value)?),
+							None => Value::Boolean(false),
+						},
+						"DEL" => Value::Integer(db.remove(&key).is_some() as i64),
+						"EXPIRE" => match db.get_mut(&key) {
+							Some(entry) => {
+								entry.ttl = Some(args[2].parse().unwrap());
+								Value::Integer(1)
+							}
+							None => Value::Integer(0),
+						},
+						"SET" => {
+							let ttl = match args.get(3).map(String::as_str) {
+								Some("EX") => Some(args[4].parse().unwrap()),
+								Some("KEEPTTL") => db.get(&key).and_then(|entry| entry.ttl),
+								None => None,
+								Some(arg) => panic!("unsupported SET argument: {}", arg),
+							};
+
+							let value = args[2].clone();
+							db.insert(key, Entry { value, ttl });
+							Value::String(lua.create_string("OK")?)
+						}
+						cmd => panic!("unsupported command: {}", cmd),
+					};
+
+					Ok(res)
+				})
+				.unwrap();
+
+			let redis = lua.create_table().unwrap();
+			redis.set("call", call).unwrap();
+			lua.globals().set("redis", redis).unwrap();
+
+			let decode = lua
+				.create_function(|lua, payload: String| {
+					let json: serde_json::Value = serde_json::from_str(&payload).map_err(mlua::Error::external)?;
+					lua.to_value(&json)
+				})
+				.unwrap();
+
+			let encode = lua
+				.create_function(|lua, value: Value| {
+					let json: serde_json::Value = lua.from_value(value)?;
+					Ok(json.to_string())
+				})
+				.unwrap();
+
+			let cjson = lua.create_table().unwrap();
+			cjson.set("decode", decode).unwrap();
+			cjson.set("encode", encode).unwrap();
+			lua.globals().set("cjson", cjson).unwrap();
+
+			Self { lua, store }
+		}
+
+		fn eval(&self, script: &str, key: &str, args: &[&str]) -> i64 {
+			let globals = self.lua.globals();
+			globals.set("KEYS", vec![key]).unwrap();
+			globals.set("ARGV", args.to_vec()).unwrap();
+
+			self.lua.load(script).eval().unwrap()
+		}
+
+		fn set(&self, key: &str, origin: &Origin, ttl: Option<i64>) {
+			let value = serde_json::to_string(origin).unwrap();
+			self.store.borrow_mut().insert(key.to_string(), Entry { value, ttl });
+		}
+
+		fn get(&self, key: &str) -> Option<(Origin, Option<i64>)> {
+			let entry = self.store.borrow().get(key).cloned()?;
+			Some((serde_json::from_str(&entry.value).unwrap(), entry.ttl))
+		}
+	}
+
+	fn origin(url: &str, alternates: &[&str]) -> Origin {
+		Origin {
+			url: url.parse().unwrap(),
+			alternates: alternates.iter().map(|url| url.parse().unwrap()).collect(),
+			owner: None,
+		}
+	}
+
+	fn owned(url: &str, owner: &str) -> Origin {
+		Origin {
+			owner: Some(owner.to_string()),
+			..origin(url, &[])
+		}
+	}
+
+	const KEY: &str = "origin.test";
+	const NODE: &str = "https://node.example.com/test";
+	const OTHER: &str = "https://other.example.com/test";
+
+	#[test]
+	fn delete_origin() {
+		let redis = Redis::new();
+		assert_eq!(redis.eval(DELETE_ORIGIN, KEY, &[NODE]), 0);
+
+		redis.set(KEY, &origin(OTHER, &[]), Some(600));
+		assert_eq!(redis.eval(DELETE_ORIGIN, KEY, &[NODE]), -1);
+		assert!(redis.get(KEY).is_some());
+
+		assert_eq!(redis.eval(DELETE_ORIGIN, KEY, &[OTHER]), 1);
+		assert!(redis.get(KEY).is_none());
+	}
+
+	#[test]
+	fn patch_origin() {
+		let redis = Redis::new();
+		assert_eq!(redis.eval(PATCH_ORIGIN, KEY, &[NODE]), 0);
+
+		redis.set(KEY, &origin(NODE, &[OTHER]), Some(10));
+		assert_eq!(redis.eval(PATCH_ORIGIN, KEY, &[OTHER]), -1);
+		assert_eq!(redis.get(KEY).unwrap().1, Some(10));
+
+		// The alternates don't matter, only the origin's URL.
+		assert_eq!(redis.eval(PATCH_ORIGIN, KEY, &[NODE]), 1);
+		assert_eq!(redis.get(KEY), Some((origin(NODE, &[OTHER]), Some(600))));
+	}
+
+	#[test]
+	fn add_alternate() {
+		let redis = Redis::new();
+		assert_eq!(redis.eval(ADD_ALTERNATE, KEY, &[OTHER]), 0);
+		assert!(redis.get(KEY).is_none());
+
+		redis.set(KEY, &origin(NODE, &[]), Some(10));
+
+		// The origin isn't its own alternate.
+		assert_eq!(redis.eval(ADD_ALTERNATE, KEY, &[NODE]), 1);
+		assert_eq!(redis.get(KEY), Some((origin(NODE, &[]), Some(10))));
+
+		// Each alternate is only added once, keeping the expiration.
+		assert_eq!(redis.eval(ADD_ALTERNATE, KEY, &[OTHER]), 1);
+		assert_eq!(redis.eval(ADD_ALTERNATE, KEY, &[OTHER]), 1);
+		assert_eq!(redis.get(KEY), Some((origin(NODE, &[OTHER]), Some(10))));
+
+		let third = "https://third.example.com/test";
+		assert_eq!(redis.eval(ADD_ALTERNATE, KEY, &[third]), 1);
+		assert_eq!(redis.get(KEY), Some((origin(NODE, &[OTHER, third]), Some(10))));
+	}
+
+	#[test]
+	fn remove_alternate() {
+		let redis = Redis::new();
+		assert_eq!(redis.eval(REMOVE_ALTERNATE, KEY, &[OTHER]), 0);
+
+		let third = "https://third.example.com/test";
+		redis.set(KEY, &origin(NODE, &[OTHER, third]), Some(10));
+
+		assert_eq!(redis.eval(REMOVE_ALTERNATE, KEY, &[OTHER]), 1);
+		assert_eq!(redis.get(KEY), Some((origin(NODE, &[third]), Some(10))));
+
+		// The field is omitted once it's empty, rather than encoded as an empty object.
+		assert_eq!(redis.eval(REMOVE_ALTERNATE, KEY, &[third]), 1);
+		assert_eq!(redis.get(KEY), Some((origin(NODE, &[]), Some(10))));
+		assert!(!redis.store.borrow()[KEY].value.contains("alternates"));
+
+		// Removing an unknown alternate leaves the origin as is.
+		assert_eq!(redis.eval(REMOVE_ALTERNATE, KEY, &[OTHER]), 1);
+		assert_eq!(redis.get(KEY), Some((origin(NODE, &[]), Some(10))));
+	}
+
+	#[test]
+	fn replace_origin() {
+		let redis = Redis::new();
+
+		let first = serde_json::to_string(&owned(NODE, "owner")).unwrap();
+		assert_eq!(redis.eval(REPLACE_ORIGIN, KEY, &[&first, "owner"]), 1);
+		assert_eq!(redis.get(KEY), Some((owned(NODE, "owner"), Some(600))));
+
+		// Only the same owner can replace it.
+		let second = serde_json::to_string(&owned(OTHER, "other")).unwrap();
+		assert_eq!(redis.eval(REPLACE_ORIGIN, KEY, &[&second, "other"]), -1);
+		assert_eq!(redis.eval(REPLACE_ORIGIN, KEY, &[&second, ""]), -1);
+		assert_eq!(redis.get(KEY).unwrap().0, owned(NODE, "owner"));
+
+		let second = serde_json::to_string(&owned(OTHER, "owner")).unwrap();
+		assert_eq!(redis.eval(REPLACE_ORIGIN, KEY, &[&second, "owner"]), 1);
+		assert_eq!(redis.get(KEY).unwrap().0, owned(OTHER, "owner"));
+	}
+
+	#[test]
+	fn replace_unowned() {
+		let redis = Redis::new();
+		redis.set(KEY, &origin(NODE, &[]), Some(600));
+
+		// An origin without an owner is never replaced, even without an owner.
+		let payload = serde_json::to_string(&origin(OTHER, &[])).unwrap();
+		assert_eq!(redis.eval(REPLACE_ORIGIN, KEY, &[&payload, ""]), -1);
+		assert_eq!(redis.eval(REPLACE_ORIGIN, KEY, &[&payload, "owner"]), -1);
+		assert_eq!(redis.get(KEY).unwrap().0, origin(NODE, &[]));
+	}
+}
@@ -25,6 +25,13 @@ pub struct Config {
 	#[arg(long)]
 	pub tls_disable_verify: bool,
 
+	/// Only accept a server certificate with this SHA-256 fingerprint, encoded as hex.
+	///
+	/// This value can be provided multiple times to accept multiple certificates.
+	/// Use this instead of --tls-disable-verify for the self-signed certificate served by `moq-relay --dev` at /fingerprint.
+	#[arg(long)]
+	pub tls_fingerprint: Vec<String>,
+
 	/// Publish the current time to the relay, otherwise only subscribe.
 	#[arg(long)]
 	pub publish: bool,
@@ -32,6 +39,11 @@ pub struct Config {
 	/// The name of the clock track.
 	#[arg(long, default_value = "now")]
 	pub track: String,
+
+	/// Log output format, either text or json.
+	/// Use RUST_LOG to filter per module, ex. RUST_LOG=info,moq_transport=debug
+	#[arg(long, default_value = "text")]
+	pub log_format: moq_transport::logging::Format,
 }
 
 fn moq_url(s: &str) -> Result<Url, String> {
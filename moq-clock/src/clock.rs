@@ -3,6 +3,7 @@ use std::time;
 use anyhow::Context;
 use moq_transport::{
 	cache::{fragment, segment, track},
+	message::ObjectExtensions,
 	VarInt,
 };
 
@@ -25,13 +26,10 @@ impl Publisher {
 		let mut sequence = start.minute();
 
 		loop {
+			// The priority and expiration come from the track metadata.
 			let segment = self
 				.track
-				.create_segment(segment::Info {
-					sequence: VarInt::from_u32(sequence),
-					priority: 0,
-					expires: Some(time::Duration::from_secs(60)),
-				})
+				.create_default_segment(VarInt::from_u32(sequence))
 				.context("failed to create minute segment")?;
 
 			sequence += 1;
@@ -65,8 +63,14 @@ impl Publisher {
 			let delta = now.format("%S").to_string();
 			let sequence = VarInt::from_u32(now.second() + 1);
 
+			// Include the timestamp so subscribers can measure latency.
+			let extensions = ObjectExtensions {
+				timestamp: Some(now.into()),
+				..Default::default()
+			};
+
 			segment
-				.fragment(sequence, delta.len())?
+				.fragment_with(sequence, delta.len(), extensions)?
 				.chunk(delta.clone().into())
 				.context("failed to write delta")?;
 
@@ -129,6 +133,12 @@ impl Subscriber {
 
 		while let Some(fragment) = segment.fragment().await? {
 			log::debug!("next fragment: {:?}", fragment);
+
+			if let Some(timestamp) = fragment.extensions.timestamp {
+				let latency = time::SystemTime::now().duration_since(timestamp).unwrap_or_default();
+				log::debug!("latency: {:?}", latency);
+			}
+
 			let value = Self::recv_fragment(fragment, base.clone()).await?;
 			let str = String::from_utf8(value).context("invalid UTF-8")?;
 
@@ -6,21 +6,14 @@ use clap::Parser;
 mod cli;
 mod clock;
 
-use moq_transport::cache::broadcast;
+use moq_transport::{cache::broadcast, message};
 
 // TODO: clap complete
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-	env_logger::init();
-
-	// Disable tracing so we don't get a bunch of Quinn spam.
-	let tracer = tracing_subscriber::FmtSubscriber::builder()
-		.with_max_level(tracing::Level::WARN)
-		.finish();
-	tracing::subscriber::set_global_default(tracer).unwrap();
-
 	let config = cli::Config::parse();
+	moq_transport::logging::init(config.log_format);
 
 	// Create a list of acceptable root certificates.
 	let mut roots = rustls::RootCertStore::empty();
@@ -55,6 +48,16 @@ async fn main() -> anyhow::Result<()> {
 	if config.tls_disable_verify {
 		let noop = NoCertificateVerification {};
 		tls_config.dangerous().set_certificate_verifier(Arc::new(noop));
+	} else if !config.tls_fingerprint.is_empty() {
+		// Otherwise pin the certificate, ignoring the roots.
+		let fingerprints = config
+			.tls_fingerprint
+			.iter()
+			.map(|fingerprint| hex::decode(fingerprint).context("invalid --tls-fingerprint"))
+			.collect::<anyhow::Result<_>>()?;
+
+		let pinned = FingerprintVerification { fingerprints };
+		tls_config.dangerous().set_certificate_verifier(Arc::new(pinned));
 	}
 
 	tls_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec()]; // this one is important
@@ -78,9 +81,18 @@ async fn main() -> anyhow::Result<()> {
 			.await
 			.context("failed to create MoQ Transport session")?;
 
+		let metadata = message::Metadata {
+			kind: Some(message::MediaKind::Data),
+			created: Some(time::SystemTime::now()),
+			priority: Some(0),
+			expires: Some(time::Duration::from_secs(60)),
+			..Default::default()
+		};
+
 		let publisher = publisher
-			.create_track(&config.track)
+			.create_track_with(&config.track, metadata)
 			.context("failed to create clock track")?;
+
 		let clock = clock::Publisher::new(publisher);
 
 		tokio::select! {
@@ -121,3 +133,29 @@ impl rustls::client::ServerCertVerifier for NoCertificateVerification {
 		Ok(rustls::client::ServerCertVerified::assertion())
 	}
 }
+
+// Accept a certificate only if it matches one of the SHA-256 fingerprints, like WebTransport's serverCertificateHashes.
+pub struct FingerprintVerification {
+	fingerprints: Vec<Vec<u8>>,
+}
+
+impl rustls::client::ServerCertVerifier for FingerprintVerification {
+	fn verify_server_cert(
+		&self,
+		end_entity: &rustls::Certificate,
+		_intermediates: &[rustls::Certificate],
+		_server_name: &rustls::ServerName,
+		_scts: &mut dyn Iterator<Item = &[u8]>,
+		_ocsp_response: &[u8],
+		_now: time::SystemTime,
+	) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+		let fingerprint = ring::digest::digest(&ring::digest::SHA256, end_entity.as_ref());
+
+		match self.fingerprints.iter().any(|pinned| pinned == fingerprint.as_ref()) {
+			true => Ok(rustls::client::ServerCertVerified::assertion()),
+			false => Err(rustls::Error::InvalidCertificate(
+				rustls::CertificateError::ApplicationVerificationFailure,
+			)),
+		}
+	}
+}
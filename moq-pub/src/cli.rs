@@ -34,6 +34,23 @@ pub struct Config {
 	/// Fine for local development, but should be used in caution in production.
 	#[arg(long)]
 	pub tls_disable_verify: bool,
+
+	/// Only accept a server certificate with this SHA-256 fingerprint, encoded as hex.
+	///
+	/// This value can be provided multiple times to accept multiple certificates.
+	/// Use this instead of --tls-disable-verify for the self-signed certificate served by `moq-relay --dev` at /fingerprint.
+	#[arg(long)]
+	pub tls_fingerprint: Vec<String>,
+
+	/// Send this token to the relay, so only a publisher with the same token can resume or take over the broadcast.
+	#[arg(long)]
+	pub token: Option<String>,
+
+	/// Log output format, either text or json.
+	/// Use RUST_LOG to filter per module, ex. RUST_LOG=info,moq_transport=debug
+	// NOTE: This is a string because cli.rs is also included by build.rs, which doesn't depend on moq-transport.
+	#[arg(long, default_value = "text", value_parser = ["text", "json"])]
+	pub log_format: String,
 }
 
 fn moq_url(s: &str) -> Result<Url, String> {
@@ -15,18 +15,11 @@ use moq_transport::cache::broadcast;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-	env_logger::init();
-
-	// Disable tracing so we don't get a bunch of Quinn spam.
-	let tracer = tracing_subscriber::FmtSubscriber::builder()
-		.with_max_level(tracing::Level::WARN)
-		.finish();
-	tracing::subscriber::set_global_default(tracer).unwrap();
-
 	let config = Config::parse();
+	moq_transport::logging::init(config.log_format.parse().map_err(anyhow::Error::msg)?);
 
-	let (publisher, subscriber) = broadcast::new("");
-	let mut media = Media::new(&config, publisher).await?;
+	let (mut publisher, subscriber) = broadcast::new("");
+	let mut media = Media::new(&config, publisher.clone()).await?;
 
 	// Create a list of acceptable root certificates.
 	let mut roots = rustls::RootCertStore::empty();
@@ -61,6 +54,16 @@ async fn main() -> anyhow::Result<()> {
 	if config.tls_disable_verify {
 		let noop = NoCertificateVerification {};
 		tls_config.dangerous().set_certificate_verifier(Arc::new(noop));
+	} else if !config.tls_fingerprint.is_empty() {
+		// Otherwise pin the certificate, ignoring the roots.
+		let fingerprints = config
+			.tls_fingerprint
+			.iter()
+			.map(|fingerprint| hex::decode(fingerprint).context("invalid --tls-fingerprint"))
+			.collect::<anyhow::Result<_>>()?;
+
+		let pinned = FingerprintVerification { fingerprints };
+		tls_config.dangerous().set_certificate_verifier(Arc::new(pinned));
 	}
 
 	tls_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec()]; // this one is important
@@ -77,14 +80,19 @@ async fn main() -> anyhow::Result<()> {
 		.await
 		.context("failed to create WebTransport session")?;
 
-	let session = moq_transport::session::Client::publisher(session, subscriber)
-		.await
-		.context("failed to create MoQ Transport session")?;
+	let session = match &config.token {
+		Some(token) => moq_transport::session::Client::publisher_with_token(session, subscriber, token).await,
+		None => moq_transport::session::Client::publisher(session, subscriber).await,
+	}
+	.context("failed to create MoQ Transport session")?;
+
+	// Return a 404 for all unknown subscriptions.
+	let unknown = publisher.serve(broadcast::NotFound, time::Duration::from_secs(10));
 
-	// TODO run a task that returns a 404 for all unknown subscriptions.
 	tokio::select! {
 		res = session.run() => res.context("session error")?,
 		res = media.run() => res.context("media error")?,
+		res = unknown => res.context("broadcast error")?,
 	}
 
 	Ok(())
@@ -105,3 +113,29 @@ impl rustls::client::ServerCertVerifier for NoCertificateVerification {
 		Ok(rustls::client::ServerCertVerified::assertion())
 	}
 }
+
+// Accept a certificate only if it matches one of the SHA-256 fingerprints, like WebTransport's serverCertificateHashes.
+pub struct FingerprintVerification {
+	fingerprints: Vec<Vec<u8>>,
+}
+
+impl rustls::client::ServerCertVerifier for FingerprintVerification {
+	fn verify_server_cert(
+		&self,
+		end_entity: &rustls::Certificate,
+		_intermediates: &[rustls::Certificate],
+		_server_name: &rustls::ServerName,
+		_scts: &mut dyn Iterator<Item = &[u8]>,
+		_ocsp_response: &[u8],
+		_now: time::SystemTime,
+	) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+		let fingerprint = ring::digest::digest(&ring::digest::SHA256, end_entity.as_ref());
+
+		match self.fingerprints.iter().any(|pinned| pinned == fingerprint.as_ref()) {
+			true => Ok(rustls::client::ServerCertVerified::assertion()),
+			false => Err(rustls::Error::InvalidCertificate(
+				rustls::CertificateError::ApplicationVerificationFailure,
+			)),
+		}
+	}
+}
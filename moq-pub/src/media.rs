@@ -1,6 +1,7 @@
 use crate::cli::Config;
 use anyhow::{self, Context};
 use moq_transport::cache::{broadcast, fragment, segment, track};
+use moq_transport::message::{MediaKind, Metadata, ObjectExtensions};
 use moq_transport::VarInt;
 use mp4::{self, ReadBox};
 use serde_json::json;
@@ -46,6 +47,7 @@ impl Media {
 			sequence: VarInt::ZERO,
 			priority: 0,
 			expires: None,
+			extensions: Default::default(),
 		})?;
 
 		// Create a single fragment, optionally setting the size
@@ -53,6 +55,16 @@ impl Media {
 
 		init_fragment.chunk(init.into())?;
 
+		broadcast.set_metadata(Metadata {
+			created: Some(time::SystemTime::now()),
+			..Default::default()
+		});
+
+		let mut catalog = broadcast.create_track(".catalog")?;
+
+		// Create the catalog track
+		let metadata = Self::serve_catalog(&mut catalog, &init_track.name, &moov)?;
+
 		let mut tracks = HashMap::new();
 
 		for trak in &moov.traks {
@@ -61,17 +73,15 @@ impl Media {
 
 			let timescale = track_timescale(&moov, id);
 
+			// Also advertise the kind and codec of each track in SUBSCRIBE_OK.
+			let metadata = metadata.get(&id).cloned().unwrap_or_default();
+
 			// Store the track publisher in a map so we can update it later.
-			let track = broadcast.create_track(&name)?;
+			let track = broadcast.create_track_with(&name, metadata)?;
 			let track = Track::new(track, timescale);
 			tracks.insert(id, track);
 		}
 
-		let mut catalog = broadcast.create_track(".catalog")?;
-
-		// Create the catalog track
-		Self::serve_catalog(&mut catalog, &init_track.name, &moov)?;
-
 		Ok(Media {
 			_broadcast: broadcast,
 			_catalog: catalog,
@@ -128,14 +138,16 @@ impl Media {
 		track: &mut track::Publisher,
 		init_track_name: &str,
 		moov: &mp4::MoovBox,
-	) -> Result<(), anyhow::Error> {
+	) -> Result<HashMap<u32, Metadata>, anyhow::Error> {
 		let segment = track.create_segment(segment::Info {
 			sequence: VarInt::ZERO,
 			priority: 0,
 			expires: None,
+			extensions: Default::default(),
 		})?;
 
 		let mut tracks = Vec::new();
+		let mut metadata = HashMap::new();
 
 		for trak in &moov.traks {
 			let mut track = json!({
@@ -204,6 +216,22 @@ impl Media {
 				anyhow::bail!("unknown codec for track: {}", trak.tkhd.track_id);
 			}
 
+			let kind = match track["kind"].as_str() {
+				Some("video") => Some(MediaKind::Video),
+				Some("audio") => Some(MediaKind::Audio),
+				_ => None,
+			};
+
+			metadata.insert(
+				trak.tkhd.track_id,
+				Metadata {
+					kind,
+					codec: track["codec"].as_str().map(str::to_string),
+					created: Some(time::SystemTime::now()),
+					..Default::default()
+				},
+			);
+
 			tracks.push(track);
 		}
 
@@ -220,7 +248,7 @@ impl Media {
 		// Add the segment and add the fragment.
 		fragment.chunk(catalog_str.into())?;
 
-		Ok(())
+		Ok(metadata)
 	}
 }
 
@@ -273,6 +301,10 @@ struct Track {
 
 	// The number of segments produced.
 	sequence: u64,
+
+	// The timestamp of the first keyframe and the wall clock time when it was received.
+	// Used to convert sample timestamps into capture times.
+	epoch: Option<(time::Duration, time::SystemTime)>,
 }
 
 impl Track {
@@ -282,6 +314,7 @@ impl Track {
 			sequence: 0,
 			current: None,
 			timescale,
+			epoch: None,
 		}
 	}
 
@@ -295,24 +328,27 @@ impl Track {
 		}
 
 		// Otherwise make a new segment
+		let timestamp = fragment.timestamp(self.timescale);
 
-		// Compute the timestamp in milliseconds.
-		// Overflows after 583 million years, so we're fine.
-		let timestamp: u32 = fragment
-			.timestamp(self.timescale)
-			.as_millis()
-			.try_into()
-			.context("timestamp too large")?;
+		// We don't know the actual capture time, so assume the first keyframe was captured when we received it.
+		let (start, started) = *self.epoch.get_or_insert((timestamp, time::SystemTime::now()));
+		let captured = started + timestamp.saturating_sub(start);
 
 		// Create a new segment.
 		let segment = self.track.create_segment(segment::Info {
 			sequence: VarInt::try_from(self.sequence).context("sequence too large")?,
 
 			// Newer segments are higher priority
-			priority: u32::MAX.checked_sub(timestamp).context("priority too large")?,
+			priority: u32::MAX - u32::try_from(self.sequence).context("priority too large")?,
 
 			// Delete segments after 10s.
 			expires: Some(time::Duration::from_secs(10)),
+
+			extensions: ObjectExtensions {
+				timestamp: Some(captured),
+				media_time: Some(timestamp),
+				..Default::default()
+			},
 		})?;
 
 		// Create a single fragment for the segment that we will keep appending.
@@ -392,8 +428,10 @@ fn sample_keyframe(moof: &mp4::MoofBox) -> bool {
 				None => default_flags,
 			};
 
-			if i == 0 && trun.first_sample_flags.is_some() {
-				flags = trun.first_sample_flags.unwrap();
+			if i == 0 {
+				if let Some(first) = trun.first_sample_flags {
+					flags = first;
+				}
 			}
 
 			// https://chromium.googlesource.com/chromium/src/media/+/master/formats/mp4/track_run_iterator.cc#177
@@ -0,0 +1,200 @@
+use std::net;
+
+use anyhow::Context;
+
+use axum::{
+	extract::{Path, State},
+	http::{header, Request, StatusCode},
+	middleware::{self, Next},
+	response::{IntoResponse, Response},
+	routing::{delete, get, post},
+	Json, Router,
+};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::{FetchInfo, Metrics, Origin, Registry, SessionInfo, Shutdown, Tls};
+
+// Run a HTTP server for operators, bound separately from the media server.
+// NOTE: Requests are only authenticated if a token is configured, so this should still be kept on a trusted network.
+pub struct Admin {
+	app: Router,
+	listen: net::SocketAddr,
+}
+
+#[derive(Clone)]
+struct AdminState {
+	origin: Origin,
+	registry: Registry,
+	metrics: Metrics,
+	tls: Tls,
+	shutdown: Shutdown,
+
+	// Required as a bearer token on every request, if set, and updated on reload.
+	token: watch::Receiver<Option<String>>,
+}
+
+impl Admin {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		listen: net::SocketAddr,
+		token: watch::Receiver<Option<String>>,
+		origin: Origin,
+		registry: Registry,
+		metrics: Metrics,
+		tls: Tls,
+		shutdown: Shutdown,
+	) -> Self {
+		if token.borrow().is_none() && !listen.ip().is_loopback() {
+			log::warn!(
+				"admin API has no token, so anybody who can reach it can kick sessions: bind={}",
+				listen
+			);
+		}
+
+		let state = AdminState {
+			origin,
+			registry,
+			metrics,
+			tls,
+			shutdown,
+			token,
+		};
+
+		let app = Router::new()
+			.route("/broadcasts", get(get_broadcasts))
+			.route("/broadcasts/:id", delete(delete_broadcast))
+			.route("/sessions", get(get_sessions))
+			.route("/sessions/:id", delete(delete_session))
+			.route("/fetches", get(get_fetches))
+			.route("/tls/reload", post(reload_tls))
+			.route("/shutdown", post(start_shutdown))
+			.route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+			// Added after the layer so Prometheus can scrape the metrics without the admin token.
+			.route("/metrics", get(get_metrics))
+			.with_state(state);
+
+		Self { app, listen }
+	}
+
+	pub async fn serve(self) -> anyhow::Result<()> {
+		log::info!("serving admin API: bind={}", self.listen);
+
+		axum::Server::try_bind(&self.listen)
+			.with_context(|| format!("failed to bind admin API: {}", self.listen))?
+			.serve(self.app.into_make_service())
+			.await?;
+
+		Ok(())
+	}
+}
+
+// Reject any request without the bearer token, if one is configured.
+async fn authorize<B>(State(state): State<AdminState>, request: Request<B>, next: Next<B>) -> Response {
+	// Clone the token so the watch isn't locked while the request runs.
+	let token = state.token.borrow().clone();
+	let token = match token {
+		Some(token) => token,
+		None => return next.run(request).await,
+	};
+
+	let provided = request
+		.headers()
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "));
+
+	// Compare in constant time so the token can't be guessed one byte at a time.
+	match provided.map(|provided| ring::constant_time::verify_slices_are_equal(provided.as_bytes(), token.as_bytes())) {
+		Some(Ok(())) => next.run(request).await,
+		_ => StatusCode::UNAUTHORIZED.into_response(),
+	}
+}
+
+/// A broadcast as returned by the admin API.
+#[derive(Serialize)]
+struct BroadcastInfo {
+	id: String,
+
+	// The number of subscriber sessions for this broadcast.
+	subscribers: usize,
+
+	// True if the broadcast is being fetched from another origin.
+	remote: bool,
+}
+
+async fn get_broadcasts(State(state): State<AdminState>) -> Json<Vec<BroadcastInfo>> {
+	let fetches = state.origin.fetches();
+
+	let broadcasts = state
+		.origin
+		.broadcasts()
+		.into_iter()
+		.map(|id| BroadcastInfo {
+			subscribers: state.registry.subscribers(&id),
+			remote: fetches.iter().any(|fetch| fetch.id == id),
+			id,
+		})
+		.collect();
+
+	Json(broadcasts)
+}
+
+// Close a broadcast by kicking the publisher, or cancelling the fetch from another origin.
+async fn delete_broadcast(Path(id): Path<String>, State(state): State<AdminState>) -> StatusCode {
+	let kicked = state.registry.kick_publishers(&id) > 0;
+	let aborted = state.origin.abort_fetch(&id);
+
+	// Close the broadcast now, rather than waiting for a publisher to reconnect.
+	let unpublished = match state.origin.unpublish(&id).await {
+		Ok(unpublished) => unpublished,
+		Err(err) => {
+			log::warn!("failed to delete origin: id={} err={}", id, err);
+			true
+		}
+	};
+
+	match kicked || aborted || unpublished {
+		true => StatusCode::NO_CONTENT,
+		false => StatusCode::NOT_FOUND,
+	}
+}
+
+async fn get_sessions(State(state): State<AdminState>) -> Json<Vec<SessionInfo>> {
+	Json(state.registry.sessions())
+}
+
+async fn delete_session(Path(id): Path<usize>, State(state): State<AdminState>) -> StatusCode {
+	match state.registry.kick(id) {
+		true => StatusCode::NO_CONTENT,
+		false => StatusCode::NOT_FOUND,
+	}
+}
+
+async fn get_fetches(State(state): State<AdminState>) -> Json<Vec<FetchInfo>> {
+	Json(state.origin.fetches())
+}
+
+async fn get_metrics(State(state): State<AdminState>) -> impl IntoResponse {
+	let body = state.metrics.render(&state.origin, &state.registry);
+	([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+// Reload the certificate and key files, keeping the previous certificates on error.
+async fn reload_tls(State(state): State<AdminState>) -> impl IntoResponse {
+	match state.tls.reload_certs() {
+		Ok(()) => {
+			log::info!("reloaded certificates: fingerprints={:?}", state.tls.fingerprints());
+			StatusCode::NO_CONTENT.into_response()
+		}
+		Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", err)).into_response(),
+	}
+}
+
+// Start a graceful shutdown, the same as SIGTERM.
+async fn start_shutdown(State(state): State<AdminState>) -> StatusCode {
+	match state.shutdown.start() {
+		true => StatusCode::ACCEPTED,
+		false => StatusCode::CONFLICT,
+	}
+}
@@ -1,7 +1,9 @@
-use std::{net, path};
+use std::{fs, net, path, time};
 use url::Url;
 
-use clap::Parser;
+use anyhow::Context;
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser};
+use serde::Deserialize;
 
 /// Search for a pattern in a file and display the lines that contain it.
 #[derive(Parser, Clone)]
@@ -10,6 +12,16 @@ pub struct Config {
 	#[arg(long, default_value = "[::]:4443")]
 	pub listen: net::SocketAddr,
 
+	/// Listen for admin HTTP requests on this address, listing and closing broadcasts and sessions.
+	///
+	/// This is only reachable locally by default, and it should not be reachable from the public internet.
+	#[arg(long, default_value = "127.0.0.1:4543")]
+	pub admin_listen: net::SocketAddr,
+
+	/// Require this bearer token in the Authorization header of every admin request.
+	#[arg(long)]
+	pub admin_token: Option<String>,
+
 	/// Use the certificates at this path, encoded as PEM.
 	///
 	/// You can use this option multiple times for multiple certificates.
@@ -37,6 +49,18 @@ pub struct Config {
 	#[arg(long)]
 	pub tls_disable_verify: bool,
 
+	/// Generate a self-signed certificate for these hostnames when --dev is set without --tls-cert.
+	///
+	/// The certificate is only kept in memory and is valid for 10 days, as required by WebTransport's serverCertificateHashes.
+	/// It's renewed a day before it expires, checked hourly even if --tls-poll is 0, so clients should fetch /fingerprint again before connecting.
+	#[arg(long, default_values = ["localhost", "127.0.0.1", "::1"])]
+	pub tls_generate: Vec<String>,
+
+	/// Check the certificate and key files for changes every this many seconds, reloading them if modified.
+	/// Set to 0 to only reload on SIGHUP or via the admin API.
+	#[arg(long, default_value = "10")]
+	pub tls_poll: u64,
+
 	/// Optional: Use the moq-api via HTTP to store origin information.
 	#[arg(long)]
 	pub api: Option<Url>,
@@ -49,7 +73,271 @@ pub struct Config {
 	pub api_node: Option<Url>,
 
 	/// Enable development mode.
-	/// Currently, this only listens on HTTPS and serves /fingerprint, for self-signed certificates
+	/// This listens on HTTPS and serves /fingerprint for self-signed certificates, generating one if --tls-cert is not provided.
 	#[arg(long, action)]
 	pub dev: bool,
+
+	/// Log output format, either text or json.
+	/// Use RUST_LOG to filter per module, ex. RUST_LOG=info,moq_transport=debug
+	#[arg(long, default_value = "text")]
+	pub log_format: moq_transport::logging::Format,
+
+	/// Load settings from this TOML file, although flags on the command line take precedence.
+	///
+	/// The file is reloaded on SIGHUP, applying the TLS certificates, limits and QUIC settings to new sessions, and the admin token.
+	#[arg(long)]
+	pub config: Option<path::PathBuf>,
+
+	/// Close a QUIC connection after this many seconds without any packets.
+	#[arg(long, default_value = "10")]
+	pub quic_idle_timeout: u64,
+
+	/// Send a QUIC keep-alive after this many seconds without any packets.
+	#[arg(long, default_value = "4")]
+	pub quic_keep_alive: u64,
+
+	/// Refresh each origin in moq-api after this many seconds.
+	/// This must be less than the 10 minute expiration used by moq-api.
+	#[arg(long, default_value = "300")]
+	pub api_refresh: u64,
+
+	/// On SIGTERM, wait up to this many seconds for sessions to leave after sending them a GOAWAY.
+	#[arg(long, default_value = "30")]
+	pub shutdown_timeout: u64,
+
+	/// Keep a broadcast open for this many seconds after its publisher leaves, so a reconnecting publisher can take over.
+	#[arg(long, default_value = "5")]
+	pub publish_grace: u64,
+
+	/// Let a publisher take over a broadcast that's still being published, kicking the current publisher.
+	///
+	/// Only a publisher sending the same token as the one that created the broadcast can take over; otherwise it's rejected as a duplicate.
+	/// With moq-api, the server needs to run with --takeover as well to replace an origin on another node.
+	#[arg(long)]
+	pub publish_takeover: bool,
+
+	/// Retry fetching a broadcast from another origin this many times, looking up the origin again each time.
+	#[arg(long, default_value = "5")]
+	pub api_retries: u32,
+
+	/// Fetch each broadcast from its origin and every alternate at the same time, instead of falling back one at a time.
+	///
+	/// Each track is merged from all of them, so subscribers aren't interrupted when one origin fails.
+	/// A broadcast published here while another node is its origin is registered as an alternate, rather than taking over.
+	#[arg(long)]
+	pub api_redundant: bool,
+
+	/// Push each broadcast published to this relay to these upstream relays, appending the broadcast ID to the path.
+	///
+	/// The upstream relays don't register pushed broadcasts in moq-api, since this relay already has, as long as they share the --push-token.
+	#[arg(long)]
+	pub push: Vec<Url>,
+
+	/// Only push broadcasts with an ID starting with this prefix.
+	#[arg(long, default_value = "")]
+	pub push_prefix: String,
+
+	/// A secret shared with other relays, sent to each --push upstream and required from any relay pushing to us.
+	///
+	/// A relay pushing without it is treated like any other publisher, so it's registered in moq-api and can be rejected as a duplicate.
+	#[arg(long)]
+	pub push_token: Option<String>,
+
+	/// The maximum number of concurrent sessions, otherwise unlimited.
+	#[arg(long)]
+	pub max_sessions: Option<usize>,
+
+	/// The maximum number of broadcasts published to this relay, otherwise unlimited.
+	#[arg(long)]
+	pub max_broadcasts: Option<usize>,
+}
+
+impl Config {
+	/// Parse the command line, filling in any flags that weren't provided from the --config file.
+	pub fn load() -> anyhow::Result<Self> {
+		let matches = Self::command().get_matches();
+		let mut config = Self::from_arg_matches(&matches)?;
+
+		if let Some(path) = &config.config {
+			let file =
+				fs::read_to_string(path).with_context(|| format!("failed to read config: {}", path.display()))?;
+			let file: File =
+				toml::from_str(&file).with_context(|| format!("failed to parse config: {}", path.display()))?;
+			config.merge(file, &matches)?;
+		}
+
+		Ok(config)
+	}
+
+	// Use the value from the file unless the flag was provided on the command line.
+	fn merge(&mut self, file: File, matches: &ArgMatches) -> anyhow::Result<()> {
+		let set = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+		fn apply<T>(field: &mut T, value: Option<T>, set: bool) {
+			if let (true, Some(value)) = (set, value) {
+				*field = value;
+			}
+		}
+
+		apply(&mut self.listen, file.listen, set("listen"));
+		apply(&mut self.admin_listen, file.admin_listen, set("admin_listen"));
+		apply(&mut self.admin_token, file.admin_token.map(Some), set("admin_token"));
+		apply(&mut self.dev, file.dev, set("dev"));
+
+		if let (true, Some(format)) = (set("log_format"), file.log_format) {
+			self.log_format = format.parse().map_err(anyhow::Error::msg)?;
+		}
+
+		apply(&mut self.tls_cert, file.tls.cert, set("tls_cert"));
+		apply(&mut self.tls_key, file.tls.key, set("tls_key"));
+		apply(&mut self.tls_root, file.tls.root, set("tls_root"));
+		apply(
+			&mut self.tls_disable_verify,
+			file.tls.disable_verify,
+			set("tls_disable_verify"),
+		);
+		apply(&mut self.tls_poll, file.tls.poll, set("tls_poll"));
+		apply(&mut self.tls_generate, file.tls.generate, set("tls_generate"));
+
+		apply(&mut self.api, file.api.url.map(Some), set("api"));
+		apply(&mut self.api_node, file.api.node.map(Some), set("api_node"));
+		apply(&mut self.api_refresh, file.api.refresh, set("api_refresh"));
+		apply(&mut self.api_retries, file.api.retries, set("api_retries"));
+		apply(&mut self.api_redundant, file.api.redundant, set("api_redundant"));
+		apply(&mut self.publish_grace, file.publish_grace, set("publish_grace"));
+		apply(
+			&mut self.publish_takeover,
+			file.publish_takeover,
+			set("publish_takeover"),
+		);
+		apply(
+			&mut self.shutdown_timeout,
+			file.shutdown_timeout,
+			set("shutdown_timeout"),
+		);
+
+		apply(&mut self.push, file.push.url, set("push"));
+		apply(&mut self.push_prefix, file.push.prefix, set("push_prefix"));
+		apply(&mut self.push_token, file.push.token.map(Some), set("push_token"));
+
+		apply(
+			&mut self.max_sessions,
+			file.limits.sessions.map(Some),
+			set("max_sessions"),
+		);
+		apply(
+			&mut self.max_broadcasts,
+			file.limits.broadcasts.map(Some),
+			set("max_broadcasts"),
+		);
+
+		apply(
+			&mut self.quic_idle_timeout,
+			file.quic.idle_timeout,
+			set("quic_idle_timeout"),
+		);
+		apply(&mut self.quic_keep_alive, file.quic.keep_alive, set("quic_keep_alive"));
+
+		Ok(())
+	}
+
+	/// The limits that can be changed without a restart.
+	pub fn limits(&self) -> Limits {
+		Limits {
+			sessions: self.max_sessions,
+			broadcasts: self.max_broadcasts,
+		}
+	}
+
+	pub fn api_refresh(&self) -> time::Duration {
+		time::Duration::from_secs(self.api_refresh)
+	}
+
+	pub fn publish_grace(&self) -> time::Duration {
+		time::Duration::from_secs(self.publish_grace)
+	}
+
+	pub fn shutdown_timeout(&self) -> time::Duration {
+		time::Duration::from_secs(self.shutdown_timeout)
+	}
+
+	/// How often to check the certificate files for changes, if at all.
+	pub fn tls_poll(&self) -> Option<time::Duration> {
+		match self.tls_poll {
+			0 => None,
+			secs => Some(time::Duration::from_secs(secs)),
+		}
+	}
+}
+
+/// Limits enforced for new sessions and broadcasts, which are updated on reload.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+	/// The maximum number of concurrent sessions.
+	pub sessions: Option<usize>,
+
+	/// The maximum number of broadcasts published to this relay.
+	pub broadcasts: Option<usize>,
+}
+
+// The contents of the --config file, using the same names as the flags.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+	listen: Option<net::SocketAddr>,
+	admin_listen: Option<net::SocketAddr>,
+	admin_token: Option<String>,
+	dev: Option<bool>,
+	log_format: Option<String>,
+	publish_grace: Option<u64>,
+	publish_takeover: Option<bool>,
+	shutdown_timeout: Option<u64>,
+	tls: FileTls,
+	api: FileApi,
+	push: FilePush,
+	limits: FileLimits,
+	quic: FileQuic,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileTls {
+	cert: Option<Vec<path::PathBuf>>,
+	key: Option<Vec<path::PathBuf>>,
+	root: Option<Vec<path::PathBuf>>,
+	disable_verify: Option<bool>,
+	poll: Option<u64>,
+	generate: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileApi {
+	url: Option<Url>,
+	node: Option<Url>,
+	refresh: Option<u64>,
+	retries: Option<u32>,
+	redundant: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FilePush {
+	url: Option<Vec<Url>>,
+	prefix: Option<String>,
+	token: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileLimits {
+	sessions: Option<usize>,
+	broadcasts: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileQuic {
+	idle_timeout: Option<u64>,
+	keep_alive: Option<u64>,
 }
@@ -22,6 +22,9 @@ pub enum RelayError {
 
 	#[error("missing node")]
 	MissingNode,
+
+	#[error("limit exceeded")]
+	LimitExceeded,
 }
 
 impl moq_transport::MoqError for RelayError {
@@ -32,6 +35,7 @@ impl moq_transport::MoqError for RelayError {
 			Self::MoqApi(_err) => 504,
 			Self::Url(_) => 500,
 			Self::MissingNode => 500,
+			Self::LimitExceeded => moq_transport::code::LIMIT_EXCEEDED,
 			Self::WebTransportClient(_) => 504,
 			Self::WebTransportServer(_) => 500,
 		}
@@ -44,6 +48,7 @@ impl moq_transport::MoqError for RelayError {
 			Self::MoqApi(err) => format!("api error: {}", err),
 			Self::Url(err) => format!("url error: {}", err),
 			Self::MissingNode => "missing node".to_owned(),
+			Self::LimitExceeded => "limit exceeded".to_owned(),
 			Self::WebTransportServer(err) => format!("upstream server error: {}", err),
 			Self::WebTransportClient(err) => format!("upstream client error: {}", err),
 		}
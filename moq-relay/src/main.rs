@@ -1,40 +1,74 @@
 use anyhow::Context;
-use clap::Parser;
+use tokio::sync::watch;
 
+mod admin;
 mod config;
 mod error;
+mod metrics;
 mod origin;
+mod pool;
+mod push;
 mod quic;
+mod registry;
+mod reload;
 mod session;
+mod shutdown;
 mod tls;
 mod web;
 
+pub use admin::*;
 pub use config::*;
 pub use error::*;
+pub use metrics::*;
 pub use origin::*;
+pub use pool::*;
+pub use push::*;
 pub use quic::*;
+pub use registry::*;
+pub use reload::*;
 pub use session::*;
+pub use shutdown::*;
 pub use tls::*;
 pub use web::*;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-	env_logger::init();
+	let config = Config::load()?;
+	moq_transport::logging::init(config.log_format);
 
-	// Disable tracing so we don't get a bunch of Quinn spam.
-	let tracer = tracing_subscriber::FmtSubscriber::builder()
-		.with_max_level(tracing::Level::WARN)
-		.finish();
-	tracing::subscriber::set_global_default(tracer).unwrap();
-
-	let config = Config::parse();
 	let tls = Tls::load(&config)?;
+	let (limits, limits_recv) = watch::channel(config.limits());
+	let (admin_token, admin_token_recv) = watch::channel(config.admin_token.clone());
+
+	// Drain the sessions on SIGTERM, returning once they've left.
+	let shutdown = Shutdown::new();
 
 	// Create a QUIC server for media.
-	let quic = Quic::new(config.clone(), tls.clone())
+	let quic = Quic::new(config.clone(), tls.clone(), limits_recv, shutdown.clone())
 		.await
 		.context("failed to create server")?;
 
+	// Apply a new config on SIGHUP.
+	let reloader = Reloader::new(
+		tls.clone(),
+		quic.endpoint(),
+		limits,
+		admin_token,
+		config.tls_poll(),
+		shutdown.clone(),
+	);
+
+	// Serve the admin API on a separate address.
+	let admin = Admin::new(
+		config.admin_listen,
+		admin_token_recv,
+		quic.origin(),
+		quic.registry(),
+		quic.metrics(),
+		tls.clone(),
+		shutdown.clone(),
+	);
+
 	// Create the web server if the --dev flag was set.
 	// This is currently only useful in local development so it's not enabled by default.
 	if config.dev {
@@ -44,8 +78,16 @@ async fn main() -> anyhow::Result<()> {
 		tokio::select! {
 			res = quic.serve() => res.context("failed to run quic server"),
 			res = web.serve() => res.context("failed to run web server"),
+			res = admin.serve() => res.context("failed to run admin server"),
+			res = reloader.run() => res.context("failed to reload config"),
+			res = shutdown.run() => res.context("failed to shutdown"),
 		}
 	} else {
-		quic.serve().await.context("failed to run quic server")
+		tokio::select! {
+			res = quic.serve() => res.context("failed to run quic server"),
+			res = admin.serve() => res.context("failed to run admin server"),
+			res = reloader.run() => res.context("failed to reload config"),
+			res = shutdown.run() => res.context("failed to shutdown"),
+		}
 	}
 }
@@ -0,0 +1,324 @@
+use std::{
+	fmt::Write,
+	future::Future,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time,
+};
+
+use moq_api::ApiError;
+use moq_transport::{cache, setup::Role};
+
+use crate::{Origin, Registry};
+
+/// Counters for the relay, served in the Prometheus text format at `/metrics` on the admin API.
+#[derive(Clone, Default)]
+pub struct Metrics {
+	inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+	connections: AtomicU64,
+	connections_total: AtomicU64,
+
+	publishers: AtomicU64,
+	subscribers: AtomicU64,
+
+	broadcasts_published: AtomicU64,
+	broadcasts_relayed: AtomicU64,
+	fetch_errors: AtomicU64,
+
+	api_errors: AtomicU64,
+	api_duration: Histogram,
+
+	// Shared by every broadcast in the cache.
+	cache: cache::metrics::Metrics,
+}
+
+impl Metrics {
+	/// Count an active QUIC connection until the returned handle is dropped.
+	pub fn connection(&self) -> Active {
+		self.inner.connections_total.fetch_add(1, Ordering::Relaxed);
+		Active::new(self.clone(), |inner| &inner.connections)
+	}
+
+	/// Count an active session with the given role until the returned handle is dropped.
+	pub fn session(&self, role: Role) -> Option<Active> {
+		match role {
+			Role::Publisher => Some(Active::new(self.clone(), |inner| &inner.publishers)),
+			Role::Subscriber => Some(Active::new(self.clone(), |inner| &inner.subscribers)),
+			Role::Both => None,
+		}
+	}
+
+	/// Returns the handle used to count segments in the cache, see [cache::broadcast::Publisher::set_metrics].
+	pub fn cache(&self) -> cache::metrics::Metrics {
+		self.inner.cache.clone()
+	}
+
+	/// A broadcast was published to this relay.
+	pub fn published(&self) {
+		self.inner.broadcasts_published.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// A broadcast was fetched from another relay, counted once connected to its origin.
+	pub fn relayed(&self) {
+		self.inner.broadcasts_relayed.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// A broadcast could not be fetched from another relay.
+	pub fn fetch_error(&self) {
+		self.inner.fetch_errors.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Record the latency and result of a moq-api request.
+	pub async fn api<T, F: Future<Output = Result<T, ApiError>>>(&self, request: F) -> Result<T, ApiError> {
+		let start = time::Instant::now();
+		let res = request.await;
+
+		self.inner.api_duration.observe(start.elapsed());
+		if res.is_err() {
+			self.inner.api_errors.fetch_add(1, Ordering::Relaxed);
+		}
+
+		res
+	}
+
+	/// Encode every metric in the Prometheus text format.
+	pub fn render(&self, origin: &Origin, registry: &Registry) -> String {
+		let inner = &self.inner;
+		let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+		let cache = inner.cache.snapshot();
+
+		// Include the sessions used to fetch from other relays, which aren't in the registry.
+		let (sent, received) = registry.bytes();
+		let (upstream_sent, upstream_received) = origin.upstream_bytes();
+		let (sent, received) = (sent + upstream_sent, received + upstream_received);
+
+		let mut out = String::new();
+
+		gauge(
+			&mut out,
+			"moq_relay_connections",
+			"Active QUIC connections.",
+			load(&inner.connections),
+		);
+		counter(
+			&mut out,
+			"moq_relay_connections_total",
+			"Accepted QUIC connections.",
+			load(&inner.connections_total),
+		);
+
+		writeln!(out, "# HELP moq_relay_sessions Active MoQ sessions by role.").ok();
+		writeln!(out, "# TYPE moq_relay_sessions gauge").ok();
+		writeln!(
+			out,
+			"moq_relay_sessions{{role=\"publisher\"}} {}",
+			load(&inner.publishers)
+		)
+		.ok();
+		writeln!(
+			out,
+			"moq_relay_sessions{{role=\"subscriber\"}} {}",
+			load(&inner.subscribers)
+		)
+		.ok();
+
+		gauge(
+			&mut out,
+			"moq_relay_broadcasts",
+			"Active broadcasts, either published or relayed.",
+			origin.broadcasts().len() as u64,
+		);
+		counter(
+			&mut out,
+			"moq_relay_broadcasts_published_total",
+			"Broadcasts published to this relay.",
+			load(&inner.broadcasts_published),
+		);
+		counter(
+			&mut out,
+			"moq_relay_broadcasts_relayed_total",
+			"Broadcasts fetched from another relay.",
+			load(&inner.broadcasts_relayed),
+		);
+		counter(
+			&mut out,
+			"moq_relay_fetch_errors_total",
+			"Broadcasts that could not be fetched from another relay.",
+			load(&inner.fetch_errors),
+		);
+
+		counter(
+			&mut out,
+			"moq_relay_bytes_sent_total",
+			"UDP bytes sent by sessions.",
+			sent,
+		);
+		counter(
+			&mut out,
+			"moq_relay_bytes_received_total",
+			"UDP bytes received by sessions.",
+			received,
+		);
+
+		counter(
+			&mut out,
+			"moq_relay_segments_cached_total",
+			"Segments inserted into the cache.",
+			cache.segments_cached,
+		);
+		counter(
+			&mut out,
+			"moq_relay_segments_evicted_total",
+			"Segments evicted from the cache after expiring or being replaced.",
+			cache.segments_evicted,
+		);
+
+		counter(
+			&mut out,
+			"moq_relay_api_errors_total",
+			"Failed requests to moq-api.",
+			load(&inner.api_errors),
+		);
+		inner.api_duration.render(
+			&mut out,
+			"moq_relay_api_duration_seconds",
+			"Latency of requests to moq-api.",
+		);
+
+		out
+	}
+}
+
+/// A handle that decrements a gauge when dropped.
+pub struct Active {
+	metrics: Metrics,
+	gauge: fn(&Inner) -> &AtomicU64,
+}
+
+impl Active {
+	fn new(metrics: Metrics, gauge: fn(&Inner) -> &AtomicU64) -> Self {
+		gauge(&metrics.inner).fetch_add(1, Ordering::Relaxed);
+		Self { metrics, gauge }
+	}
+}
+
+impl Drop for Active {
+	fn drop(&mut self) {
+		(self.gauge)(&self.metrics.inner).fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+// A histogram with fixed buckets, in seconds.
+#[derive(Default)]
+struct Histogram {
+	buckets: [AtomicU64; Histogram::BOUNDS.len()],
+	count: AtomicU64,
+	sum_micros: AtomicU64,
+}
+
+impl Histogram {
+	const BOUNDS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+	fn observe(&self, elapsed: time::Duration) {
+		let secs = elapsed.as_secs_f64();
+
+		// Buckets are cumulative, so increment every bucket that's large enough.
+		for (bound, bucket) in Self::BOUNDS.iter().zip(&self.buckets) {
+			if secs <= *bound {
+				bucket.fetch_add(1, Ordering::Relaxed);
+			}
+		}
+
+		self.count.fetch_add(1, Ordering::Relaxed);
+		self.sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+	}
+
+	fn render(&self, out: &mut String, name: &str, help: &str) {
+		writeln!(out, "# HELP {} {}", name, help).ok();
+		writeln!(out, "# TYPE {} histogram", name).ok();
+
+		for (bound, bucket) in Self::BOUNDS.iter().zip(&self.buckets) {
+			writeln!(
+				out,
+				"{}_bucket{{le=\"{}\"}} {}",
+				name,
+				bound,
+				bucket.load(Ordering::Relaxed)
+			)
+			.ok();
+		}
+
+		let count = self.count.load(Ordering::Relaxed);
+		let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+
+		writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count).ok();
+		writeln!(out, "{}_sum {}", name, sum).ok();
+		writeln!(out, "{}_count {}", name, count).ok();
+	}
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+	writeln!(out, "# HELP {} {}", name, help).ok();
+	writeln!(out, "# TYPE {} counter", name).ok();
+	writeln!(out, "{} {}", name, value).ok();
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: u64) {
+	writeln!(out, "# HELP {} {}", name, help).ok();
+	writeln!(out, "# TYPE {} gauge", name).ok();
+	writeln!(out, "{} {}", name, value).ok();
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn histogram() {
+		let histogram = Histogram::default();
+		histogram.observe(time::Duration::from_millis(3));
+		histogram.observe(time::Duration::from_millis(30));
+		histogram.observe(time::Duration::from_millis(300));
+		histogram.observe(time::Duration::from_secs(10));
+
+		let mut out = String::new();
+		histogram.render(&mut out, "test_seconds", "A test.");
+
+		let expected = [
+			"# HELP test_seconds A test.",
+			"# TYPE test_seconds histogram",
+			"test_seconds_bucket{le=\"0.005\"} 1",
+			"test_seconds_bucket{le=\"0.01\"} 1",
+			"test_seconds_bucket{le=\"0.025\"} 1",
+			"test_seconds_bucket{le=\"0.05\"} 2",
+			"test_seconds_bucket{le=\"0.1\"} 2",
+			"test_seconds_bucket{le=\"0.25\"} 2",
+			"test_seconds_bucket{le=\"0.5\"} 3",
+			"test_seconds_bucket{le=\"1\"} 3",
+			"test_seconds_bucket{le=\"2.5\"} 3",
+			"test_seconds_bucket{le=\"5\"} 3",
+			"test_seconds_bucket{le=\"+Inf\"} 4",
+			"test_seconds_sum 10.333",
+			"test_seconds_count 4",
+		];
+
+		assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+	}
+
+	#[test]
+	fn empty_histogram() {
+		let mut out = String::new();
+		Histogram::default().render(&mut out, "test_seconds", "A test.");
+
+		assert!(out.contains("test_seconds_bucket{le=\"+Inf\"} 0\n"));
+		assert!(out.contains("test_seconds_sum 0\n"));
+		assert!(out.contains("test_seconds_count 0\n"));
+	}
+}
@@ -1,16 +1,24 @@
 use std::ops::{Deref, DerefMut};
 use std::{
 	collections::HashMap,
-	sync::{Arc, Mutex, Weak},
+	iter,
+	sync::{atomic, Arc, Mutex, Weak},
 };
 
 use moq_api::ApiError;
 use moq_transport::cache::{broadcast, CacheError};
+use moq_transport::session::{Resolved, Resolver};
+use moq_transport::MoqError;
+use serde::Serialize;
 use url::Url;
 
-use tokio::time;
+use tokio::{
+	sync::{oneshot, watch},
+	task::{AbortHandle, JoinSet},
+	time,
+};
 
-use crate::RelayError;
+use crate::{unix_secs, Config, Limits, Metrics, Pool, Push, RelayError};
 
 #[derive(Clone)]
 pub struct Origin {
@@ -27,68 +35,344 @@ pub struct Origin {
 	// A map of active broadcasts by ID.
 	cache: Arc<Mutex<HashMap<String, Weak<Subscriber>>>>,
 
-	// A QUIC endpoint we'll use to fetch from other origins.
-	quic: quinn::Endpoint,
+	// The broadcasts being fetched from other origins, by ID.
+	fetches: Arc<Mutex<HashMap<String, Fetch>>>,
+
+	// The broadcasts published to this relay, by ID, kept open between publishers so one can take over.
+	handoffs: Arc<Mutex<HashMap<String, Handoff>>>,
+
+	// Used to tell apart fetches and publishers for the same ID.
+	next_seq: Arc<atomic::AtomicU64>,
+
+	// The sessions we'll use to fetch from other origins, shared by every broadcast from the same node.
+	pool: Pool,
+
+	// Pushes the broadcasts published to this relay to upstream relays, if any.
+	push: Push,
+
+	// Counters for published and relayed broadcasts, and moq-api requests.
+	metrics: Metrics,
+
+	// The maximum number of published broadcasts, updated on reload.
+	limits: watch::Receiver<Limits>,
+
+	// The number of broadcasts currently published to this relay.
+	published: Arc<atomic::AtomicUsize>,
+
+	// How often each publisher refreshes its origin in moq-api.
+	refresh: time::Duration,
+
+	// The number of times to retry a failed fetch before giving up.
+	retries: u32,
+
+	// Fetch from the origin and every alternate at the same time, instead of one at a time.
+	redundant: bool,
+
+	// How long to keep a broadcast open after its publisher leaves, in case it reconnects.
+	grace: time::Duration,
+
+	// Let a publisher take over a broadcast that's still being published, instead of returning Duplicate.
+	takeover: bool,
 }
 
 impl Origin {
-	pub fn new(api: Option<moq_api::Client>, node: Option<Url>, quic: quinn::Endpoint) -> Self {
+	// The delay before the first retry, doubled after each failed attempt up to the maximum.
+	const RETRY_DELAY: time::Duration = time::Duration::from_millis(250);
+	const RETRY_DELAY_MAX: time::Duration = time::Duration::from_secs(10);
+
+	pub fn new(config: &Config, quic: quinn::Endpoint, metrics: Metrics, limits: watch::Receiver<Limits>) -> Self {
+		let api = config.api.clone().map(|url| {
+			log::info!("using moq-api: url={}", url);
+			moq_api::Client::new(url)
+		});
+
+		if let Some(ref node) = config.api_node {
+			log::info!("advertising origin: url={}", node);
+		}
+
 		Self {
 			api,
-			node,
+			node: config.api_node.clone(),
 			cache: Default::default(),
-			quic,
+			fetches: Default::default(),
+			handoffs: Default::default(),
+			next_seq: Default::default(),
+			pool: Pool::new(quic.clone()),
+			push: Push::new(
+				quic,
+				config.push.clone(),
+				config.push_prefix.clone(),
+				config.push_token.clone(),
+			),
+			metrics,
+			limits,
+			published: Default::default(),
+			refresh: config.api_refresh(),
+			retries: config.api_retries,
+			redundant: config.api_redundant,
+			grace: config.publish_grace(),
+			takeover: config.publish_takeover,
 		}
 	}
 
-	/// Create a new broadcast with the given ID.
+	/// Create a new broadcast with the given ID, or resume the existing broadcast if its publisher has left.
 	///
+	/// Existing subscribers continue on the new publisher's tracks.
+	/// A broadcast that's still being published returns Duplicate, unless takeover is enabled and the previous publisher is told to stop.
+	/// A broadcast created with a token can only be resumed or taken over with the same token, and one without a token can't be taken over.
+	/// A broadcast pushed by another relay isn't registered in moq-api, since that relay is already its origin.
 	/// Publisher::run needs to be called to periodically refresh the origin cache.
-	pub async fn publish(&mut self, id: &str) -> Result<Publisher, RelayError> {
-		let (publisher, subscriber) = broadcast::new(id);
+	pub async fn publish(&mut self, id: &str, pushed: bool, token: Option<&str>) -> Result<Publisher, RelayError> {
+		let seq = self.next_seq.fetch_add(1, atomic::Ordering::Relaxed);
+		let (replace, replaced) = oneshot::channel();
+		let owner = token.map(owner);
 
-		let subscriber = {
-			let mut cache = self.cache.lock().unwrap();
+		// Only a publisher with a token can take over a broadcast, since it's the only way to tell it's the same publisher.
+		let takeover = self.takeover && owner.is_some();
 
-			// Check if the broadcast already exists.
-			// TODO This is racey, because a new publisher could be created while existing subscribers are still active.
-			if cache.contains_key(id) {
+		// Check the owner before taking over a broadcast fetched from another node, so a rejected publisher can't close it.
+		// NOTE: This is racey, but moq-api checks the owner again when replacing the origin.
+		let remote = takeover && !pushed && !self.redundant && self.fetches.lock().unwrap().contains_key(id);
+		if let Some(api) = self.api.as_mut().filter(|_| remote) {
+			let existing = self.metrics.api(api.get_origin(id)).await?;
+			if existing.is_some_and(|existing| existing.owner != owner) {
 				return Err(CacheError::Duplicate.into());
 			}
+		}
 
-			// Create subscriber that will remove from the cache when dropped.
-			let subscriber = Arc::new(Subscriber {
-				broadcast: subscriber,
-				origin: self.clone(),
-			});
+		// A cached broadcast that's being replaced, only dropped once the locks are released.
+		// Dropping the last reference removes it from the cache, which would otherwise deadlock.
+		let mut stale = None;
+
+		let (broadcast, subscriber, created, registration) = {
+			let mut cache = self.cache.lock().unwrap();
+			let mut handoffs = self.handoffs.lock().unwrap();
+
+			match handoffs.get_mut(id) {
+				Some(handoff) => {
+					// Without a token, anybody can resume the broadcast once its publisher has left, but nobody can take it over.
+					let authorized = match &handoff.owner {
+						Some(existing) => owner.as_ref() == Some(existing),
+						None => !handoff.active,
+					};
+
+					if !authorized || (handoff.active && !self.takeover) {
+						return Err(CacheError::Duplicate.into());
+					}
+
+					log::info!("taking over broadcast: id={} active={}", id, handoff.active);
+
+					// Dropping the previous sender tells the previous publisher to stop.
+					handoff.seq = seq;
+					handoff.replace = replace;
+					handoff.active = true;
+
+					// Keep the existing origin in moq-api, which is ours either way.
+					let registration = handoff.registration.clone();
+
+					(
+						handoff.broadcast.clone(),
+						handoff.subscriber.clone(),
+						false,
+						registration,
+					)
+				}
+				None => {
+					let max = self.limits.borrow().broadcasts;
+					if max.is_some_and(|max| self.published.load(atomic::Ordering::Relaxed) >= max) {
+						return Err(RelayError::LimitExceeded);
+					}
+
+					let mut fetches = self.fetches.lock().unwrap();
+
+					// The broadcast is being published on another node, which only pushes, redundancy or takeover with a token allow.
+					if fetches.contains_key(id) && !pushed && !self.redundant && !takeover {
+						return Err(CacheError::Duplicate.into());
+					}
+
+					let existing = cache.get(id).and_then(Weak::upgrade);
+					let fetch = fetches.remove(id);
+					drop(fetches);
+
+					let (broadcast, subscriber) = match (existing, fetch) {
+						// Take over a broadcast being fetched from another origin, resuming its tracks.
+						(Some(subscriber), Some(fetch)) => {
+							log::info!("taking over remote broadcast: id={}", id);
+							fetch.abort.abort();
+
+							(fetch.broadcast, subscriber)
+						}
+						(existing, fetch) => {
+							stale = existing;
+
+							// Nobody is subscribed to the fetch anymore, so it's about to end anyway.
+							if let Some(fetch) = fetch {
+								fetch.abort.abort();
+							}
 
-			cache.insert(id.to_string(), Arc::downgrade(&subscriber));
+							let (mut publisher, subscriber) = broadcast::new(id);
+							publisher.set_metrics(self.metrics.cache());
 
-			subscriber
+							let subscriber = Arc::new(Subscriber {
+								broadcast: subscriber,
+								origin: self.clone(),
+							});
+
+							cache.insert(id.to_string(), Arc::downgrade(&subscriber));
+
+							(publisher, subscriber)
+						}
+					};
+
+					let handoff = Handoff {
+						seq,
+						broadcast: broadcast.clone(),
+						subscriber: subscriber.clone(),
+						replace,
+						active: true,
+						registration: None,
+						owner: owner.clone(),
+					};
+
+					handoffs.insert(id.to_string(), handoff);
+
+					(broadcast, subscriber, true, None)
+				}
+			}
 		};
 
+		drop(stale);
+
+		// Decremented when the Publisher is dropped.
+		self.published.fetch_add(1, atomic::Ordering::Relaxed);
+
 		// Create a publisher that constantly updates itself as the origin in moq-api.
 		// It holds a reference to the subscriber to prevent dropping early.
 		let mut publisher = Publisher {
-			broadcast: publisher,
+			broadcast,
 			subscriber,
 			api: None,
+			metrics: self.metrics.clone(),
+			published: self.published.clone(),
+			refresh: self.refresh,
+			seq,
+			replaced,
 		};
 
-		// Insert the publisher into the database.
-		if let Some(api) = self.api.as_mut() {
+		// Insert the publisher into the database, unless it's resuming a broadcast that's already registered.
+		if let Some(mut api) = self.api.clone().filter(|_| !pushed) {
 			// Make a URL for the broadcast.
 			let url = self.node.as_ref().ok_or(RelayError::MissingNode)?.clone().join(id)?;
-			let origin = moq_api::Origin { url };
-			api.set_origin(id, &origin).await?;
+			let origin = moq_api::Origin {
+				url,
+				alternates: Vec::new(),
+				owner,
+			};
+
+			// With redundancy, another node publishing the same broadcast stays the origin and we become an alternate.
+			// Otherwise it's a duplicate, unless takeover is enabled and we replace it.
+			let res = match registration {
+				Some(registration) => Ok(registration),
+				None if self.redundant => self.metrics.api(register(&mut api, id, origin)).await,
+				None if takeover => self
+					.metrics
+					.api(api.replace_origin(id, &origin))
+					.await
+					.map(|_| Registration::Origin(origin)),
+				None => self
+					.metrics
+					.api(claim(&mut api, id, &origin))
+					.await
+					.map(|_| Registration::Origin(origin)),
+			};
 
-			// Refresh every 5 minutes
-			publisher.api = Some((api.clone(), origin));
+			let registration = match res {
+				Ok(registration) => registration,
+				Err(err) => {
+					// Don't keep the broadcast open for a publisher that never started.
+					self.end(id, Some(seq), CacheError::Closed).await.ok();
+
+					return Err(match err {
+						ApiError::Duplicate => CacheError::Duplicate.into(),
+						err => err.into(),
+					});
+				}
+			};
+
+			self.registered(id, seq, &registration);
+
+			// Refresh periodically, see Publisher::run
+			publisher.api = Some((api, registration));
+		}
+
+		// A takeover continues the existing pushes, since it's the same broadcast.
+		if created {
+			self.push.push(publisher.subscriber.broadcast.clone());
 		}
 
+		self.metrics.published();
+
 		Ok(publisher)
 	}
 
+	/// Returns true if the token identifies another relay pushing a broadcast, see Push::trusted.
+	pub fn is_pusher(&self, token: Option<&str>) -> bool {
+		self.push.trusted(token)
+	}
+
+	/// Close a broadcast published to this relay and delete its origin, returning false if there was none.
+	pub async fn unpublish(&self, id: &str) -> Result<bool, ApiError> {
+		self.end(id, None, CacheError::Stop).await
+	}
+
+	// Close the published broadcast and delete its origin, unless a newer publisher has taken over.
+	async fn end(&self, id: &str, seq: Option<u64>, err: CacheError) -> Result<bool, ApiError> {
+		let handoff = {
+			let mut handoffs = self.handoffs.lock().unwrap();
+			match handoffs.get(id) {
+				Some(handoff) if seq.is_none_or(|seq| seq == handoff.seq) => handoffs.remove(id),
+				_ => None,
+			}
+		};
+
+		let handoff = match handoff {
+			Some(handoff) => handoff,
+			None => return Ok(false),
+		};
+
+		log::debug!("ending broadcast: id={} err={}", id, err);
+		handoff.broadcast.close(err).ok();
+
+		// Another node may have taken over the origin, in which case it's not deleted.
+		match (self.api.clone(), handoff.registration) {
+			(Some(mut api), Some(Registration::Origin(origin))) => {
+				self.metrics.api(api.delete_origin_if(id, &origin)).await?
+			}
+			(Some(mut api), Some(Registration::Alternate(origin))) => {
+				self.metrics.api(api.remove_alternate(id, &origin)).await?
+			}
+			_ => {}
+		}
+
+		Ok(true)
+	}
+
+	// Let another publisher resume the broadcast, unless a newer one has already taken over.
+	fn left(&self, id: &str, seq: u64) {
+		let mut handoffs = self.handoffs.lock().unwrap();
+		if let Some(handoff) = handoffs.get_mut(id).filter(|handoff| handoff.seq == seq) {
+			handoff.active = false;
+		}
+	}
+
+	// Remember how the current publisher is registered in moq-api, so it's removed when the broadcast ends.
+	fn registered(&self, id: &str, seq: u64, registration: &Registration) {
+		let mut handoffs = self.handoffs.lock().unwrap();
+		if let Some(handoff) = handoffs.get_mut(id).filter(|handoff| handoff.seq == seq) {
+			handoff.registration = Some(registration.clone());
+		}
+	}
+
 	pub fn subscribe(&self, id: &str) -> Arc<Subscriber> {
 		let mut cache = self.cache.lock().unwrap();
 
@@ -98,7 +382,9 @@ impl Origin {
 			}
 		}
 
-		let (publisher, subscriber) = broadcast::new(id);
+		let (mut publisher, subscriber) = broadcast::new(id);
+		publisher.set_metrics(self.metrics.cache());
+
 		let subscriber = Arc::new(Subscriber {
 			broadcast: subscriber,
 			origin: self.clone(),
@@ -108,45 +394,394 @@ impl Origin {
 
 		let mut this = self.clone();
 		let id = id.to_string();
+		let cached = Arc::downgrade(&subscriber);
+
+		// Keep a handle so a publisher on this relay can take over the broadcast, see publish().
+		let takeover = publisher.clone();
+		let seq = self.next_seq.fetch_add(1, atomic::Ordering::Relaxed);
+
+		// Hold the lock while spawning so the task can't remove the entry before it's inserted.
+		let mut fetches = self.fetches.lock().unwrap();
 
 		// Rather than fetching from the API and connecting via QUIC inline, we'll spawn a task to do it.
 		// This way we could stop polling this session and it won't impact other session.
 		// It also means we'll only connect the API and QUIC once if N subscribers suddenly show up.
 		// However, the downside is that we don't return an error immediately.
 		// If that's important, it can be done but it gets a bit racey.
-		tokio::spawn(async move {
-			if let Err(err) = this.serve(&id, publisher).await {
+		let handle = tokio::spawn(async move {
+			if let Err(err) = this.serve(&id, &publisher).await {
 				log::warn!("failed to serve remote broadcast: id={} err={}", id, err);
+				this.metrics.fetch_error();
+
+				// Tell any subscribers why, rather than leaving them on an empty broadcast.
+				publisher.close(cache_error(&err)).ok();
+
+				// Forget the broadcast so the next subscriber tries again.
+				let mut cache = this.cache.lock().unwrap();
+				if cache.get(&id).is_some_and(|entry| entry.ptr_eq(&cached)) {
+					cache.remove(&id);
+				}
+			}
+
+			// Remove the entry, unless it was replaced by a newer fetch.
+			let mut fetches = this.fetches.lock().unwrap();
+			if fetches.get(&id).is_some_and(|fetch| fetch.seq == seq) {
+				fetches.remove(&id);
 			}
 		});
 
+		let fetch = Fetch {
+			seq,
+			broadcast: takeover,
+			url: None,
+			started: std::time::SystemTime::now(),
+			abort: handle.abort_handle(),
+			relayed: false,
+		};
+
+		fetches.insert(subscriber.broadcast.id.clone(), fetch);
+
 		subscriber
 	}
 
-	async fn serve(&mut self, id: &str, publisher: broadcast::Publisher) -> Result<(), RelayError> {
+	/// Returns the ID of each active broadcast, sorted.
+	pub fn broadcasts(&self) -> Vec<String> {
+		let mut ids: Vec<String> = self.cache.lock().unwrap().keys().cloned().collect();
+		ids.sort();
+		ids
+	}
+
+	/// Returns each broadcast being fetched from another origin, sorted by ID.
+	pub fn fetches(&self) -> Vec<FetchInfo> {
+		let mut fetches: Vec<FetchInfo> = self
+			.fetches
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(id, fetch)| FetchInfo {
+				id: id.clone(),
+				url: fetch.url.clone(),
+				started: unix_secs(fetch.started),
+				elapsed_ms: fetch.started.elapsed().unwrap_or_default().as_millis() as u64,
+			})
+			.collect();
+
+		fetches.sort_by(|a, b| a.id.cmp(&b.id));
+		fetches
+	}
+
+	/// Cancel the fetch for the given broadcast, returning false if there was none.
+	pub fn abort_fetch(&self, id: &str) -> bool {
+		match self.fetches.lock().unwrap().remove(id) {
+			Some(fetch) => {
+				fetch.abort.abort();
+				true
+			}
+			None => false,
+		}
+	}
+
+	// Fetch the broadcast until it's closed, retrying with backoff if the origin fails.
+	async fn serve(&mut self, id: &str, publisher: &broadcast::Publisher) -> Result<(), RelayError> {
+		let mut delay = Self::RETRY_DELAY;
+		let mut retries = self.retries;
+
+		loop {
+			let started = time::Instant::now();
+
+			let err = match self.fetch(id, publisher).await {
+				Ok(()) => return Ok(()),
+
+				// The broadcast doesn't exist, so there's nothing to retry.
+				Err(RelayError::Cache(CacheError::NotFound)) => return Err(CacheError::NotFound.into()),
+				Err(err) => err,
+			};
+
+			// The fetch was working for a while, so start over rather than counting it against the last failure.
+			if started.elapsed() > Self::RETRY_DELAY_MAX {
+				delay = Self::RETRY_DELAY;
+				retries = self.retries;
+			}
+
+			if retries == 0 {
+				return Err(err);
+			}
+
+			retries -= 1;
+			log::info!("retrying remote broadcast: id={} delay={:?} err={}", id, delay, err);
+
+			// Stop waiting if every subscriber has left.
+			tokio::select! {
+				_ = time::sleep(delay) => {},
+				_ = publisher.closed() => return Ok(()),
+			}
+
+			delay = (delay * 2).min(Self::RETRY_DELAY_MAX);
+		}
+	}
+
+	// Look up the origin and fetch the broadcast from it, falling back to each alternate in order.
+	async fn fetch(&mut self, id: &str, publisher: &broadcast::Publisher) -> Result<(), RelayError> {
 		log::debug!("finding origin: id={}", id);
 
-		// Fetch the origin from the API.
+		// Fetch the origin from the API each time, in case the broadcast moved.
+		let api = self.api.as_mut().ok_or(CacheError::NotFound)?;
 		let origin = self
-			.api
-			.as_mut()
-			.ok_or(CacheError::NotFound)?
-			.get_origin(id)
+			.metrics
+			.api(api.get_origin(id))
 			.await?
 			.ok_or(CacheError::NotFound)?;
 
-		log::debug!("fetching from origin: id={} url={}", id, origin.url);
+		let mut urls: Vec<Url> = iter::once(origin.url).chain(origin.alternates).collect();
+		urls.dedup();
 
-		// Establish the webtransport session.
-		let session = webtransport_quinn::connect(&self.quic, &origin.url).await?;
-		let session = moq_transport::session::Client::subscriber(session, publisher).await?;
+		if let Some(fetch) = self.fetches.lock().unwrap().get_mut(id) {
+			fetch.url = urls.first().cloned();
+		}
 
-		session.run().await?;
+		if self.redundant && urls.len() > 1 {
+			return self.fetch_redundant(id, publisher, urls).await;
+		}
 
-		Ok(())
+		let mut res = Ok(());
+
+		for url in urls {
+			log::debug!("fetching from origin: id={} url={}", id, url);
+
+			if let Some(fetch) = self.fetches.lock().unwrap().get_mut(id) {
+				fetch.url = Some(url.clone());
+			}
+
+			res = self.fetch_from(id, &url, publisher.clone()).await;
+			match &res {
+				Ok(()) => break,
+				Err(err) => log::warn!("failed to fetch from origin: id={} url={} err={}", id, url, err),
+			}
+		}
+
+		res
+	}
+
+	// Fetch the broadcast from the origin URL, counting it as relayed the first time we connect.
+	async fn fetch_from(&self, id: &str, url: &Url, broadcast: broadcast::Publisher) -> Result<(), RelayError> {
+		let upstream = self.pool.connect(url).await?;
+
+		if let Some(fetch) = self.fetches.lock().unwrap().get_mut(id) {
+			if !fetch.relayed {
+				fetch.relayed = true;
+				self.metrics.relayed();
+			}
+		}
+
+		upstream.fetch(broadcast).await
+	}
+
+	/// Returns the UDP bytes sent and received by the sessions used to fetch from other origins.
+	pub fn upstream_bytes(&self) -> (u64, u64) {
+		self.pool.bytes()
+	}
+
+	// Fetch the broadcast from every URL at the same time, so subscribers aren't interrupted if one origin fails.
+	//
+	// Each requested track is fed by every origin, see track::Publisher::add_source.
+	// A failed origin is retried on its own, and this returns once every origin has given up or the broadcast is closed.
+	async fn fetch_redundant(
+		&self,
+		id: &str,
+		publisher: &broadcast::Publisher,
+		urls: Vec<Url>,
+	) -> Result<(), RelayError> {
+		let mut sources = Vec::new();
+		let mut fetches = JoinSet::new();
+
+		for url in urls {
+			log::debug!("fetching from redundant origin: id={} url={}", id, url);
+
+			// Each origin gets its own copy of the broadcast, kept open until the task exits.
+			let (source, subscriber) = broadcast::new(id);
+			sources.push(source.clone());
+
+			let this = self.clone();
+			fetches.spawn(async move {
+				let res = this.fetch_source(&url, &source).await;
+				if let Err(err) = &res {
+					log::warn!("failed to fetch from origin: id={} url={} err={}", source.id, url, err);
+
+					// Close any tracks still waiting for this origin, so only the other sources remain.
+					source.close(cache_error(err)).ok();
+				}
+
+				drop(subscriber);
+				res
+			});
+		}
+
+		let mut publisher = publisher.clone();
+
+		tokio::select! {
+			// NOTE: This returns Closed when the broadcast is closed.
+			_ = publisher.serve_sources(&mut sources) => Ok(()),
+			res = async {
+				let mut res = Ok(());
+				while let Some(joined) = fetches.join_next().await {
+					if let Ok(Err(err)) = joined {
+						res = Err(err);
+					}
+				}
+				res
+			} => res,
+		}
+	}
+
+	// Fetch the broadcast from a single origin, retrying with backoff if it fails.
+	async fn fetch_source(&self, url: &Url, source: &broadcast::Publisher) -> Result<(), RelayError> {
+		let mut delay = Self::RETRY_DELAY;
+		let mut retries = self.retries;
+
+		loop {
+			let started = time::Instant::now();
+
+			let err = match self.fetch_from(&source.id, url, source.clone()).await {
+				Ok(()) => return Ok(()),
+				Err(err) => err,
+			};
+
+			if started.elapsed() > Self::RETRY_DELAY_MAX {
+				delay = Self::RETRY_DELAY;
+				retries = self.retries;
+			}
+
+			if retries == 0 {
+				return Err(err);
+			}
+
+			retries -= 1;
+			log::info!(
+				"retrying redundant origin: id={} url={} delay={:?} err={}",
+				source.id,
+				url,
+				delay,
+				err
+			);
+
+			time::sleep(delay).await;
+			delay = (delay * 2).min(Self::RETRY_DELAY_MAX);
+		}
+	}
+}
+
+// Tell subscribers why a fetch failed, using the relay error code if it wasn't a cache error.
+fn cache_error(err: &RelayError) -> CacheError {
+	match err {
+		RelayError::Cache(err) => err.clone(),
+		err => CacheError::Reset(err.code()),
+	}
+}
+
+// Serve any broadcast to other relays over a single session, using the namespace as the ID.
+impl Resolver for Origin {
+	fn resolve(&self, namespace: &str) -> Result<Resolved, CacheError> {
+		let subscriber = self.subscribe(namespace);
+
+		Ok(Resolved {
+			broadcast: subscriber.broadcast.clone(),
+
+			// Keep the broadcast cached while it's being served.
+			hold: Some(subscriber),
+		})
+	}
+}
+
+// A broadcast being fetched from another origin.
+struct Fetch {
+	seq: u64,
+
+	// Used to hand the broadcast over to a local publisher.
+	broadcast: broadcast::Publisher,
+
+	url: Option<Url>,
+	started: std::time::SystemTime,
+	abort: AbortHandle,
+
+	// Set once connected to an origin, so the broadcast is only counted as relayed once.
+	relayed: bool,
+}
+
+// A broadcast published to this relay, which stays open while publishers come and go.
+struct Handoff {
+	// The current publisher, so a replaced publisher doesn't end the broadcast.
+	seq: u64,
+
+	// Keep the broadcast open while there's no publisher.
+	broadcast: broadcast::Publisher,
+	subscriber: Arc<Subscriber>,
+
+	// Dropped to tell the current publisher it's been replaced.
+	replace: oneshot::Sender<()>,
+
+	// False once the current publisher has left, so another can resume the broadcast during the grace period.
+	active: bool,
+
+	// The origin or alternate registered in moq-api, removed when the broadcast ends.
+	registration: Option<Registration>,
+
+	// A hash of the token sent by the publisher that created the broadcast, required to resume or take it over.
+	owner: Option<String>,
+}
+
+// How a publisher is registered in moq-api.
+#[derive(Clone)]
+enum Registration {
+	// We're the origin, refreshing it until another publisher takes over.
+	Origin(moq_api::Origin),
+
+	// Another node is the origin and we're one of its alternates, becoming the origin if it goes away.
+	Alternate(moq_api::Origin),
+}
+
+// Hash the token sent by a publisher, so it's not revealed to anybody reading the origin in moq-api.
+fn owner(token: &str) -> String {
+	hex::encode(ring::digest::digest(&ring::digest::SHA256, token.as_bytes()))
+}
+
+// Set ourselves as the origin, reusing the record left behind if this node restarted without deleting it.
+async fn claim(api: &mut moq_api::Client, id: &str, origin: &moq_api::Origin) -> Result<(), ApiError> {
+	match api.set_origin(id, origin).await {
+		Err(ApiError::Duplicate) => api.patch_origin(id, origin).await,
+		res => res,
+	}
+}
+
+// Register as the origin, or as an alternate if another node already is.
+async fn register(api: &mut moq_api::Client, id: &str, origin: moq_api::Origin) -> Result<Registration, ApiError> {
+	loop {
+		match claim(api, id, &origin).await {
+			Ok(()) => return Ok(Registration::Origin(origin)),
+			Err(ApiError::Duplicate) => {}
+			Err(err) => return Err(err),
+		}
+
+		if api.add_alternate(id, &origin).await? {
+			return Ok(Registration::Alternate(origin));
+		}
+
+		// The origin was deleted in between, so try again.
 	}
 }
 
+/// A broadcast being fetched from another origin, as returned by the admin API.
+#[derive(Serialize)]
+pub struct FetchInfo {
+	pub id: String,
+
+	/// The origin URL, or None while it's being looked up via the API.
+	pub url: Option<Url>,
+
+	/// The time the fetch started, in seconds since the UNIX epoch.
+	pub started: u64,
+	pub elapsed_ms: u64,
+}
+
 pub struct Subscriber {
 	pub broadcast: broadcast::Subscriber,
 
@@ -155,7 +790,15 @@ pub struct Subscriber {
 
 impl Drop for Subscriber {
 	fn drop(&mut self) {
-		self.origin.cache.lock().unwrap().remove(&self.broadcast.id);
+		let mut cache = self.origin.cache.lock().unwrap();
+
+		// Don't remove a newer broadcast with the same ID.
+		if cache
+			.get(&self.broadcast.id)
+			.is_some_and(|entry| entry.strong_count() == 0)
+		{
+			cache.remove(&self.broadcast.id);
+		}
 	}
 }
 
@@ -170,37 +813,99 @@ impl Deref for Subscriber {
 pub struct Publisher {
 	pub broadcast: broadcast::Publisher,
 
-	api: Option<(moq_api::Client, moq_api::Origin)>,
+	api: Option<(moq_api::Client, Registration)>,
+
+	metrics: Metrics,
+
+	// Shared with the Origin to count the published broadcasts.
+	published: Arc<atomic::AtomicUsize>,
+	refresh: time::Duration,
+
+	// Identifies this publisher, so it doesn't end the broadcast after being replaced.
+	seq: u64,
+
+	// Resolves when another publisher takes over, or the broadcast is closed.
+	replaced: oneshot::Receiver<()>,
 
-	#[allow(dead_code)]
 	subscriber: Arc<Subscriber>,
 }
 
 impl Publisher {
+	/// Refresh the origin in moq-api until another publisher takes over.
 	pub async fn run(&mut self) -> Result<(), ApiError> {
-		// Every 5m tell the API we're still alive.
-		// TODO don't hard-code these values
-		let mut interval = time::interval(time::Duration::from_secs(60 * 5));
+		// Periodically tell the API we're still alive.
+		let mut interval = time::interval(self.refresh);
 
 		loop {
-			if let Some((api, origin)) = self.api.as_mut() {
-				api.patch_origin(&self.broadcast.id, origin).await?;
-			}
+			self.refresh_origin().await?;
 
 			// TODO move to start of loop; this is just for testing
-			interval.tick().await;
+			tokio::select! {
+				_ = interval.tick() => {},
+				_ = &mut self.replaced => {
+					log::info!("publisher replaced: id={}", self.broadcast.id);
+					return Ok(());
+				}
+			}
 		}
 	}
 
-	pub async fn close(&mut self) -> Result<(), ApiError> {
-		if let Some((api, _)) = self.api.as_mut() {
-			api.delete_origin(&self.broadcast.id).await?;
+	// Reset the origin's expiration, or register again as an alternate in case the origin has gone away.
+	async fn refresh_origin(&mut self) -> Result<(), ApiError> {
+		let (api, registration) = match self.api.as_mut() {
+			Some(api) => api,
+			None => return Ok(()),
+		};
+
+		let id = &self.broadcast.id;
+
+		match registration {
+			Registration::Origin(origin) => self.metrics.api(api.patch_origin(id, origin)).await,
+			Registration::Alternate(origin) => {
+				let updated = self.metrics.api(register(api, id, origin.clone())).await?;
+				if let Registration::Origin(_) = updated {
+					log::info!("promoted from alternate to origin: id={}", id);
+					self.subscriber.origin.registered(id, self.seq, &updated);
+				}
+
+				*registration = updated;
+				Ok(())
+			}
 		}
+	}
+
+	/// Close the broadcast and delete the origin now, rather than waiting for another publisher to take over.
+	pub async fn close(&mut self) -> Result<(), ApiError> {
+		let origin = &self.subscriber.origin;
+		origin
+			.end(&self.broadcast.id, Some(self.seq), CacheError::Closed)
+			.await?;
 
 		Ok(())
 	}
 }
 
+impl Drop for Publisher {
+	fn drop(&mut self) {
+		self.published.fetch_sub(1, atomic::Ordering::Relaxed);
+
+		// Keep the broadcast open for a while, so a reconnecting publisher can take over.
+		let origin = self.subscriber.origin.clone();
+		origin.left(&self.broadcast.id, self.seq);
+
+		let id = self.broadcast.id.clone();
+		let seq = self.seq;
+
+		tokio::spawn(async move {
+			time::sleep(origin.grace).await;
+
+			if let Err(err) = origin.end(&id, Some(seq), CacheError::Closed).await {
+				log::warn!("failed to delete origin: id={} err={}", id, err);
+			}
+		});
+	}
+}
+
 impl Deref for Publisher {
 	type Target = broadcast::Publisher;
 
@@ -214,3 +919,193 @@ impl DerefMut for Publisher {
 		&mut self.broadcast
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+	use clap::Parser;
+	use moq_transport::cache::broadcast;
+
+	// Serve GET /origin/:id with the given origin, counting the requests.
+	async fn api(origin: Option<moq_api::Origin>) -> (Url, Arc<atomic::AtomicUsize>) {
+		let requests = Arc::new(atomic::AtomicUsize::new(0));
+		let counter = requests.clone();
+
+		let app = Router::new().route(
+			"/origin/:id",
+			get(move |Path(_id): Path<String>| async move {
+				counter.fetch_add(1, atomic::Ordering::SeqCst);
+				origin.map(Json).ok_or(StatusCode::NOT_FOUND)
+			}),
+		);
+
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}/", listener.local_addr().unwrap()).parse().unwrap();
+
+		tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+		(url, requests)
+	}
+
+	fn origin(args: &[&str]) -> Origin {
+		let config = Config::try_parse_from(iter::once("moq-relay").chain(args.iter().copied())).unwrap();
+		let quic = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+		let (_, limits) = watch::channel(Limits::default());
+
+		Origin::new(&config, quic, Metrics::default(), limits)
+	}
+
+	// An origin that can never be reached, since the .invalid TLD doesn't resolve.
+	fn unreachable(host: &str) -> Url {
+		format!("https://{}.invalid:4443/test", host).parse().unwrap()
+	}
+
+	// Track the fetch like subscribe() does, so the URL being tried is recorded.
+	fn track(origin: &Origin, publisher: &broadcast::Publisher) {
+		let fetch = Fetch {
+			seq: 0,
+			broadcast: publisher.clone(),
+			url: None,
+			started: std::time::SystemTime::now(),
+			abort: tokio::spawn(async {}).abort_handle(),
+			relayed: false,
+		};
+
+		origin.fetches.lock().unwrap().insert(publisher.id.clone(), fetch);
+	}
+
+	fn fetch_url(origin: &Origin, id: &str) -> Option<Url> {
+		origin.fetches.lock().unwrap().get(id)?.url.clone()
+	}
+
+	#[tokio::test]
+	async fn failover() {
+		let record = moq_api::Origin {
+			url: unreachable("origin"),
+			alternates: vec![unreachable("first"), unreachable("second")],
+			owner: None,
+		};
+
+		let (api, requests) = api(Some(record)).await;
+		let mut origin = origin(&["--api", api.as_str(), "--api-retries", "0"]);
+
+		let (publisher, _subscriber) = broadcast::new("test");
+		track(&origin, &publisher);
+
+		// Each alternate is tried in order after the origin fails, until the last one fails too.
+		assert!(origin.fetch("test", &publisher).await.is_err());
+		assert_eq!(fetch_url(&origin, "test"), Some(unreachable("second")));
+		assert_eq!(requests.load(atomic::Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test]
+	async fn retry_backoff() {
+		let record = moq_api::Origin {
+			url: unreachable("origin"),
+			alternates: Vec::new(),
+			owner: None,
+		};
+
+		let (api, requests) = api(Some(record)).await;
+		let mut origin = origin(&["--api", api.as_str(), "--api-retries", "2"]);
+
+		let (publisher, _subscriber) = broadcast::new("test");
+
+		let started = time::Instant::now();
+		assert!(origin.serve("test", &publisher).await.is_err());
+
+		// The origin is looked up again for each retry, doubling the delay each time.
+		assert_eq!(requests.load(atomic::Ordering::SeqCst), 3);
+		assert!(started.elapsed() >= Origin::RETRY_DELAY * 3);
+	}
+
+	#[tokio::test]
+	async fn not_found() {
+		let (api, requests) = api(None).await;
+		let mut origin = origin(&["--api", api.as_str(), "--api-retries", "2"]);
+
+		let (publisher, _subscriber) = broadcast::new("test");
+
+		// There's nothing to retry when the broadcast doesn't exist.
+		let res = origin.serve("test", &publisher).await;
+		assert!(matches!(res, Err(RelayError::Cache(CacheError::NotFound))));
+		assert_eq!(requests.load(atomic::Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test]
+	async fn retry_until_unsubscribed() {
+		let record = moq_api::Origin {
+			url: unreachable("origin"),
+			alternates: Vec::new(),
+			owner: None,
+		};
+
+		let (api, requests) = api(Some(record)).await;
+		let mut origin = origin(&["--api", api.as_str(), "--api-retries", "100"]);
+
+		let (publisher, subscriber) = broadcast::new("test");
+		drop(subscriber);
+
+		// Every subscriber has left, so it gives up without an error after the first attempt.
+		assert!(origin.serve("test", &publisher).await.is_ok());
+		assert_eq!(requests.load(atomic::Ordering::SeqCst), 1);
+	}
+
+	fn duplicate(res: Result<Publisher, RelayError>) -> bool {
+		matches!(res, Err(RelayError::Cache(CacheError::Duplicate)))
+	}
+
+	#[tokio::test]
+	async fn resume_requires_token() {
+		let mut origin = origin(&[]);
+
+		let publisher = origin.publish("test", false, Some("secret")).await.unwrap();
+		drop(publisher);
+
+		// The publisher left, but only the same token can resume the broadcast during the grace period.
+		assert!(duplicate(origin.publish("test", false, Some("other")).await));
+		assert!(duplicate(origin.publish("test", false, None).await));
+		assert!(origin.publish("test", false, Some("secret")).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn resume_without_token() {
+		let mut origin = origin(&["--publish-takeover"]);
+
+		// Without a token, nobody can take over an active broadcast.
+		let publisher = origin.publish("test", false, None).await.unwrap();
+		assert!(duplicate(origin.publish("test", false, None).await));
+		assert!(duplicate(origin.publish("test", false, Some("secret")).await));
+
+		// But anybody can resume it once the publisher has left.
+		drop(publisher);
+		assert!(origin.publish("test", false, None).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn takeover_requires_token() {
+		let mut origin = origin(&["--publish-takeover"]);
+
+		let mut publisher = origin.publish("test", false, Some("secret")).await.unwrap();
+		assert!(duplicate(origin.publish("test", false, Some("other")).await));
+		assert!(duplicate(origin.publish("test", false, None).await));
+
+		let _replacement = origin.publish("test", false, Some("secret")).await.unwrap();
+
+		// The previous publisher is told to stop.
+		time::timeout(time::Duration::from_secs(1), publisher.run())
+			.await
+			.unwrap()
+			.unwrap();
+	}
+
+	#[tokio::test]
+	async fn takeover_disabled() {
+		let mut origin = origin(&[]);
+
+		let _publisher = origin.publish("test", false, Some("secret")).await.unwrap();
+		assert!(duplicate(origin.publish("test", false, Some("secret")).await));
+	}
+}
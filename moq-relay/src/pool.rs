@@ -0,0 +1,419 @@
+use std::{
+	collections::HashMap,
+	sync::{atomic, Arc, Mutex},
+};
+
+use moq_transport::{
+	cache::broadcast,
+	code,
+	session::{SessionError, Subscriber},
+};
+use tokio::{sync::OnceCell, time};
+use url::Url;
+
+use crate::RelayError;
+
+/// Shares one upstream session per origin node, fetching each broadcast over it by namespace.
+///
+/// Sessions are closed once they've been idle for a while, and replaced by the next fetch if they fail.
+/// Nodes without the `subscribe_split` extension get a session per broadcast instead, using the ID as the path.
+#[derive(Clone)]
+pub struct Pool {
+	// A QUIC endpoint we'll use to connect to other origins.
+	quic: quinn::Endpoint,
+
+	// The session for each node, by URL.
+	nodes: Arc<Mutex<HashMap<Url, Node>>>,
+
+	// The UDP bytes sent and received by sessions that have been removed.
+	// NOTE: These are only modified while holding the nodes lock, so bytes() is consistent.
+	sent: Arc<atomic::AtomicU64>,
+	received: Arc<atomic::AtomicU64>,
+}
+
+// A session shared by every fetch from the same node.
+struct Node {
+	// Connected by the first fetch, while the others wait.
+	upstream: Arc<OnceCell<Upstream>>,
+
+	// The number of active fetches.
+	refs: usize,
+
+	// Incremented each time the node becomes idle, so an earlier timer won't close it.
+	idle: u64,
+}
+
+// There's only one per node, so the size difference doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum Upstream {
+	// A session shared by every broadcast fetched from the node.
+	Shared {
+		session: webtransport_quinn::Session,
+		subscriber: Subscriber,
+	},
+
+	// The node doesn't support subscribe_split, so each broadcast needs its own session.
+	Legacy,
+}
+
+impl Upstream {
+	fn session(&self) -> Option<&webtransport_quinn::Session> {
+		match self {
+			Self::Shared { session, .. } => Some(session),
+			Self::Legacy => None,
+		}
+	}
+}
+
+impl Pool {
+	// Close a session after it hasn't been used for this long.
+	const IDLE_TIMEOUT: time::Duration = time::Duration::from_secs(30);
+
+	pub fn new(quic: quinn::Endpoint) -> Self {
+		Self {
+			quic,
+			nodes: Default::default(),
+			sent: Default::default(),
+			received: Default::default(),
+		}
+	}
+
+	/// Connect to the node for the origin URL, reusing the existing session if any.
+	pub async fn connect(&self, url: &Url) -> Result<Connected, RelayError> {
+		// The path is the broadcast ID, which is sent as the namespace instead.
+		let mut node = url.clone();
+		node.set_path("");
+		node.set_query(None);
+
+		let lease = self.acquire(&node);
+		let upstream = lease
+			.upstream
+			.get_or_try_init(|| self.start(&node, lease.upstream.clone()))
+			.await?
+			.clone();
+
+		Ok(Connected {
+			upstream,
+			url: url.clone(),
+			pool: self.clone(),
+			_lease: lease,
+		})
+	}
+
+	/// Returns the total UDP bytes sent and received by every session, including those that have been closed.
+	pub fn bytes(&self) -> (u64, u64) {
+		let nodes = self.nodes.lock().unwrap();
+
+		let mut sent = self.sent.load(atomic::Ordering::Relaxed);
+		let mut received = self.received.load(atomic::Ordering::Relaxed);
+
+		for session in nodes.values().filter_map(|node| node.upstream.get()?.session()) {
+			let stats = session.stats();
+			sent += stats.udp_tx.bytes;
+			received += stats.udp_rx.bytes;
+		}
+
+		(sent, received)
+	}
+
+	// Remove the node while holding the lock, keeping the byte counters of its session.
+	fn remove(&self, nodes: &mut HashMap<Url, Node>, node: &Url) -> Option<webtransport_quinn::Session> {
+		let session = nodes.remove(node)?.upstream.get()?.session()?.clone();
+		self.count(&session);
+		Some(session)
+	}
+
+	// Add the bytes sent and received by a session that's no longer in the pool.
+	fn count(&self, session: &webtransport_quinn::Session) {
+		let stats = session.stats();
+		self.sent.fetch_add(stats.udp_tx.bytes, atomic::Ordering::Relaxed);
+		self.received.fetch_add(stats.udp_rx.bytes, atomic::Ordering::Relaxed);
+	}
+
+	// Increment the number of fetches for the node, which is decremented when the lease is dropped.
+	fn acquire(&self, node: &Url) -> Lease {
+		let mut nodes = self.nodes.lock().unwrap();
+		let entry = nodes.entry(node.clone()).or_insert_with(|| Node {
+			upstream: Default::default(),
+			refs: 0,
+			idle: 0,
+		});
+
+		entry.refs += 1;
+
+		Lease {
+			pool: self.clone(),
+			node: node.clone(),
+			upstream: entry.upstream.clone(),
+		}
+	}
+
+	async fn start(&self, node: &Url, cell: Arc<OnceCell<Upstream>>) -> Result<Upstream, RelayError> {
+		log::info!("connecting to node: node={}", node);
+
+		let session = webtransport_quinn::connect(&self.quic, node).await?;
+
+		let subscriber = match moq_transport::session::Client::fetcher(session.clone()).await {
+			Ok(subscriber) => subscriber,
+
+			// An older relay that needs a session per broadcast, so remember that until the node is idle.
+			Err(SessionError::RequiredExtension(_)) => {
+				log::info!(
+					"node doesn't support subscribe_split, using a session per broadcast: node={}",
+					node
+				);
+				session.close(code::STOP, b"subscribe_split unsupported");
+
+				return Ok(Upstream::Legacy);
+			}
+			Err(err) => return Err(err.into()),
+		};
+
+		// Run the session in the background, removing it from the pool when it fails so the next fetch reconnects.
+		let this = self.clone();
+		let node = node.clone();
+		let run = subscriber.clone();
+
+		tokio::spawn(async move {
+			let res = run.run().await;
+
+			// The node was already removed if we closed it for being idle.
+			let mut nodes = this.nodes.lock().unwrap();
+			if nodes
+				.get(&node)
+				.is_some_and(|entry| Arc::ptr_eq(&entry.upstream, &cell))
+			{
+				if let Err(err) = res {
+					log::warn!("upstream session failed: node={} err={}", node, err);
+				}
+
+				this.remove(&mut nodes, &node);
+			}
+		});
+
+		Ok(Upstream::Shared { session, subscriber })
+	}
+
+	// Close the session if nobody has used it since the timer started.
+	fn expire(&self, node: &Url, idle: u64) {
+		let mut nodes = self.nodes.lock().unwrap();
+
+		let expired = nodes
+			.get(node)
+			.is_some_and(|entry| entry.refs == 0 && entry.idle == idle);
+
+		if !expired {
+			return;
+		}
+
+		if let Some(session) = self.remove(&mut nodes, node) {
+			log::info!("closing idle node: node={}", node);
+			session.close(code::STOP, b"idle");
+		}
+	}
+}
+
+/// A session to an origin node, which isn't closed for being idle until this is dropped.
+pub struct Connected {
+	upstream: Upstream,
+	url: Url,
+	pool: Pool,
+	_lease: Lease,
+}
+
+impl Connected {
+	/// Fetch the broadcast from the node until it's closed.
+	pub async fn fetch(self, broadcast: broadcast::Publisher) -> Result<(), RelayError> {
+		let (session, subscriber) = match &self.upstream {
+			Upstream::Shared { session, subscriber } => (session, subscriber),
+			Upstream::Legacy => return self.fetch_legacy(broadcast).await,
+		};
+
+		log::debug!("fetching from node: id={} url={}", broadcast.id, self.url);
+
+		tokio::select! {
+			res = subscriber.fetch(broadcast) => res?,
+			err = session.closed() => return Err(SessionError::from(err).into()),
+		};
+
+		Ok(())
+	}
+
+	// Connect a session just for this broadcast, where the URL path is the broadcast ID.
+	async fn fetch_legacy(&self, broadcast: broadcast::Publisher) -> Result<(), RelayError> {
+		log::debug!("fetching from legacy node: id={} url={}", broadcast.id, self.url);
+
+		let session = webtransport_quinn::connect(&self.pool.quic, &self.url).await?;
+		let closed = broadcast.clone();
+		let subscriber = moq_transport::session::Client::subscriber(session.clone(), broadcast).await?;
+
+		let res = tokio::select! {
+			res = subscriber.run() => res,
+			_ = closed.closed() => Ok(()),
+		};
+
+		session.close(code::STOP, b"closed");
+
+		// Hold the lock so bytes() is consistent.
+		let _nodes = self.pool.nodes.lock().unwrap();
+		self.pool.count(&session);
+
+		Ok(res?)
+	}
+}
+
+// A handle to a node's session, starting the idle timer when the last one is dropped.
+struct Lease {
+	pool: Pool,
+	node: Url,
+	upstream: Arc<OnceCell<Upstream>>,
+}
+
+impl Drop for Lease {
+	fn drop(&mut self) {
+		let mut nodes = self.pool.nodes.lock().unwrap();
+
+		// The node was removed if the session failed.
+		let entry = match nodes.get_mut(&self.node) {
+			Some(entry) if Arc::ptr_eq(&entry.upstream, &self.upstream) => entry,
+			_ => return,
+		};
+
+		entry.refs -= 1;
+		if entry.refs > 0 {
+			return;
+		}
+
+		entry.idle += 1;
+		let idle = entry.idle;
+
+		let pool = self.pool.clone();
+		let node = self.node.clone();
+
+		tokio::spawn(async move {
+			time::sleep(Pool::IDLE_TIMEOUT).await;
+			pool.expire(&node, idle);
+		});
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn pool() -> Pool {
+		let quic = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+		Pool::new(quic)
+	}
+
+	fn node() -> Url {
+		"https://node.example.com:4443".parse().unwrap()
+	}
+
+	// Returns the number of fetches using the node, or None if it's not in the pool.
+	fn refs(pool: &Pool, node: &Url) -> Option<usize> {
+		pool.nodes.lock().unwrap().get(node).map(|entry| entry.refs)
+	}
+
+	// Acquire the node as if it was already connected, without needing a real session.
+	fn lease(pool: &Pool, node: &Url) -> Lease {
+		let lease = pool.acquire(node);
+		lease.upstream.set(Upstream::Legacy).ok();
+		lease
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn idle_expiry() {
+		let pool = pool();
+		let node = node();
+
+		let lease = lease(&pool, &node);
+		assert_eq!(refs(&pool, &node), Some(1));
+
+		drop(lease);
+		assert_eq!(refs(&pool, &node), Some(0));
+
+		time::sleep(Pool::IDLE_TIMEOUT - time::Duration::from_secs(1)).await;
+		assert_eq!(refs(&pool, &node), Some(0));
+
+		time::sleep(time::Duration::from_secs(2)).await;
+		assert_eq!(refs(&pool, &node), None);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn idle_reset() {
+		let pool = pool();
+		let node = node();
+
+		drop(lease(&pool, &node));
+		time::sleep(Pool::IDLE_TIMEOUT / 2).await;
+
+		// Used again before the timer fires, so the first timer doesn't close it.
+		drop(lease(&pool, &node));
+		time::sleep(Pool::IDLE_TIMEOUT / 2 + time::Duration::from_secs(1)).await;
+		assert_eq!(refs(&pool, &node), Some(0));
+
+		time::sleep(Pool::IDLE_TIMEOUT / 2).await;
+		assert_eq!(refs(&pool, &node), None);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn active_not_expired() {
+		let pool = pool();
+		let node = node();
+
+		let first = lease(&pool, &node);
+		drop(lease(&pool, &node));
+
+		time::sleep(Pool::IDLE_TIMEOUT * 2).await;
+		assert_eq!(refs(&pool, &node), Some(1));
+
+		drop(first);
+		time::sleep(Pool::IDLE_TIMEOUT * 2).await;
+		assert_eq!(refs(&pool, &node), None);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn replaced_node() {
+		let pool = pool();
+		let node = node();
+
+		let failed = lease(&pool, &node);
+
+		// The session failed, so it's removed and the next fetch connects a new one.
+		pool.remove(&mut pool.nodes.lock().unwrap(), &node);
+		let replaced = lease(&pool, &node);
+		assert!(!Arc::ptr_eq(&failed.upstream, &replaced.upstream));
+
+		// Dropping a lease for the failed session doesn't touch the new one.
+		drop(failed);
+		assert_eq!(refs(&pool, &node), Some(1));
+
+		time::sleep(Pool::IDLE_TIMEOUT * 2).await;
+		assert_eq!(refs(&pool, &node), Some(1));
+
+		drop(replaced);
+		time::sleep(Pool::IDLE_TIMEOUT * 2).await;
+		assert_eq!(refs(&pool, &node), None);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn connect_reuses_node() {
+		let pool = pool();
+		let node = node();
+
+		let _lease = lease(&pool, &node);
+
+		// The broadcast ID in the path is ignored when looking up the node.
+		let url: Url = "https://node.example.com:4443/broadcast?foo=bar".parse().unwrap();
+		let connected = pool.connect(&url).await.unwrap();
+
+		assert!(matches!(connected.upstream, Upstream::Legacy));
+		assert_eq!(connected.url, url);
+		assert_eq!(refs(&pool, &node), Some(2));
+
+		drop(connected);
+		assert_eq!(refs(&pool, &node), Some(1));
+	}
+}
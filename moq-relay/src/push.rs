@@ -0,0 +1,164 @@
+use std::future;
+
+use moq_transport::{
+	cache::broadcast,
+	code,
+	session::{Client, Event, Events},
+};
+use tokio::time;
+use url::Url;
+
+use crate::RelayError;
+
+/// Pushes each broadcast published to this relay to upstream relays, as if we were the publisher.
+///
+/// This lets an edge relay feed a central origin without sharing moq-api.
+/// The `push` extension tells the upstream relay not to register the broadcast in moq-api, in case it's shared.
+/// The upstream relay only trusts the extension if we send the same token, see Self::trusted.
+/// Each session is reconnected with backoff until the broadcast is closed.
+#[derive(Clone)]
+pub struct Push {
+	// The endpoint used to connect to the upstream relays.
+	quic: quinn::Endpoint,
+
+	// The upstream relays, with the broadcast ID appended to the path.
+	upstreams: Vec<Url>,
+
+	// Only broadcasts with an ID starting with this prefix are pushed.
+	prefix: String,
+
+	// Sent to the upstream relays, and required from relays pushing to us.
+	token: Option<String>,
+}
+
+impl Push {
+	// The delay before the first reconnect, doubled after each failed attempt up to the maximum.
+	const RETRY_DELAY: time::Duration = time::Duration::from_secs(1);
+	const RETRY_DELAY_MAX: time::Duration = time::Duration::from_secs(30);
+
+	pub fn new(quic: quinn::Endpoint, upstreams: Vec<Url>, prefix: String, token: Option<String>) -> Self {
+		Self {
+			quic,
+			upstreams,
+			prefix,
+			token,
+		}
+	}
+
+	/// Returns true if the token sent by a publisher identifies another relay, so its `push` extension can be trusted.
+	///
+	/// Nobody is trusted without a token.
+	pub fn trusted(&self, token: Option<&str>) -> bool {
+		match (&self.token, token) {
+			(Some(expected), Some(token)) => {
+				ring::constant_time::verify_slices_are_equal(expected.as_bytes(), token.as_bytes()).is_ok()
+			}
+			_ => false,
+		}
+	}
+
+	/// Push the broadcast to each upstream relay in the background, if it matches the prefix.
+	pub fn push(&self, broadcast: broadcast::Subscriber) {
+		if !broadcast.id.starts_with(&self.prefix) {
+			return;
+		}
+
+		for upstream in &self.upstreams {
+			// Keep any path on the upstream URL, so broadcasts can be pushed under a prefix.
+			let mut url = upstream.clone();
+			url.set_path(&format!("{}/{}", upstream.path().trim_end_matches('/'), broadcast.id));
+
+			let this = self.clone();
+			let broadcast = broadcast.clone();
+
+			tokio::spawn(async move { this.run(url, broadcast).await });
+		}
+	}
+
+	// Publish the broadcast to the URL until it's closed, reconnecting on failure.
+	async fn run(self, url: Url, broadcast: broadcast::Subscriber) {
+		let mut delay = Self::RETRY_DELAY;
+
+		loop {
+			let started = time::Instant::now();
+
+			match self.serve(&url, broadcast.clone()).await {
+				Ok(()) => log::info!("push session ended: id={} url={}", broadcast.id, url),
+				Err(err) => log::warn!("push session failed: id={} url={} err={}", broadcast.id, url, err),
+			}
+
+			if let Some(err) = broadcast.is_closed() {
+				log::info!("stopped pushing: id={} url={} err={}", broadcast.id, url, err);
+				return;
+			}
+
+			// The session was working for a while, so reconnect quickly.
+			if started.elapsed() > Self::RETRY_DELAY_MAX {
+				delay = Self::RETRY_DELAY;
+			}
+
+			tokio::select! {
+				_ = time::sleep(delay) => {},
+				_ = broadcast.closed() => return,
+			}
+
+			delay = (delay * 2).min(Self::RETRY_DELAY_MAX);
+		}
+	}
+
+	async fn serve(&self, url: &Url, broadcast: broadcast::Subscriber) -> Result<(), RelayError> {
+		log::info!("pushing broadcast: id={} url={}", broadcast.id, url);
+
+		let session = webtransport_quinn::connect(&self.quic, url).await?;
+		let publisher = Client::pusher(session.clone(), broadcast, self.token.as_deref()).await?;
+		let events = publisher.events();
+
+		tokio::select! {
+			res = publisher.run() => res?,
+
+			// Reconnect when the upstream relay is going away, so it doesn't wait for us.
+			_ = Self::go_away(events) => session.close(code::CLOSED, b"go away"),
+		}
+
+		Ok(())
+	}
+
+	// Wait until the peer sends a GOAWAY.
+	async fn go_away(mut events: Events) {
+		while let Some(event) = events.next().await {
+			if let Event::GoAway { .. } = event {
+				return;
+			}
+		}
+
+		future::pending().await
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn push(token: Option<&str>) -> Push {
+		let quic = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+		Push::new(quic, Vec::new(), String::new(), token.map(str::to_string))
+	}
+
+	#[tokio::test]
+	async fn trusted() {
+		let push = push(Some("secret"));
+
+		assert!(push.trusted(Some("secret")));
+		assert!(!push.trusted(Some("secret2")));
+		assert!(!push.trusted(Some("")));
+		assert!(!push.trusted(None));
+	}
+
+	#[tokio::test]
+	async fn untrusted_without_token() {
+		let push = push(None);
+
+		assert!(!push.trusted(Some("")));
+		assert!(!push.trusted(None));
+	}
+}
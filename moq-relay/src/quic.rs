@@ -2,9 +2,11 @@ use std::{sync::Arc, time};
 
 use anyhow::Context;
 
-use tokio::task::JoinSet;
+use moq_transport::code;
+use tokio::{sync::watch, task::JoinSet};
+use tracing::Instrument;
 
-use crate::{Config, Origin, Session, Tls};
+use crate::{Config, Limits, Metrics, Origin, Registry, Session, Shutdown, Tls};
 
 pub struct Quic {
 	quic: quinn::Endpoint,
@@ -14,21 +16,79 @@ pub struct Quic {
 
 	// The map of active broadcasts by path.
 	origin: Origin,
+
+	// The active sessions, for the admin API.
+	registry: Registry,
+
+	// Counters served by the admin API.
+	metrics: Metrics,
+
+	// Limits for new sessions, updated on reload.
+	limits: watch::Receiver<Limits>,
+
+	// Stops accepting connections and tells each session to go away.
+	shutdown: Shutdown,
+
+	// How long to wait for sessions to leave when shutting down.
+	shutdown_timeout: time::Duration,
 }
 
 impl Quic {
+	// Give sessions a moment to clean up, ex. delete their origins, after the connections are closed.
+	const CLOSE_TIMEOUT: time::Duration = time::Duration::from_secs(2);
+
 	// Create a QUIC endpoint that can be used for both clients and servers.
-	pub async fn new(config: Config, tls: Tls) -> anyhow::Result<Self> {
+	pub async fn new(
+		config: Config,
+		tls: Tls,
+		limits: watch::Receiver<Limits>,
+		shutdown: Shutdown,
+	) -> anyhow::Result<Self> {
+		let (server_config, client_config) = Self::configs(&config, &tls)?;
+
+		// There's a bit more boilerplate to make a generic endpoint.
+		let runtime = quinn::default_runtime().context("no async runtime")?;
+		let endpoint_config = quinn::EndpointConfig::default();
+		let socket = std::net::UdpSocket::bind(config.listen).context("failed to bind UDP socket")?;
+
+		// Create the generic QUIC endpoint.
+		let mut quic = quinn::Endpoint::new(endpoint_config, Some(server_config), socket, runtime)
+			.context("failed to create QUIC endpoint")?;
+		quic.set_default_client_config(client_config);
+
+		let metrics = Metrics::default();
+		let origin = Origin::new(&config, quic.clone(), metrics.clone(), limits.clone());
+		let conns = JoinSet::new();
+		let registry = Registry::default();
+
+		Ok(Self {
+			quic,
+			origin,
+			conns,
+			registry,
+			metrics,
+			limits,
+			shutdown,
+			shutdown_timeout: config.shutdown_timeout(),
+		})
+	}
+
+	// Create the server and client configuration, which is also used to apply a reload.
+	pub fn configs(config: &Config, tls: &Tls) -> anyhow::Result<(quinn::ServerConfig, quinn::ClientConfig)> {
 		let mut client_config = tls.client.clone();
 		let mut server_config = tls.server.clone();
 		client_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec()];
 		server_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec()];
 
+		let idle_timeout = time::Duration::from_secs(config.quic_idle_timeout)
+			.try_into()
+			.context("invalid idle timeout")?;
+
 		// Enable BBR congestion control
 		// TODO validate the implementation
 		let mut transport_config = quinn::TransportConfig::default();
-		transport_config.max_idle_timeout(Some(time::Duration::from_secs(10).try_into().unwrap()));
-		transport_config.keep_alive_interval(Some(time::Duration::from_secs(4))); // TODO make this smarter
+		transport_config.max_idle_timeout(Some(idle_timeout));
+		transport_config.keep_alive_interval(Some(time::Duration::from_secs(config.quic_keep_alive))); // TODO make this smarter
 		transport_config.congestion_controller_factory(Arc::new(quinn::congestion::BbrConfig::default()));
 		transport_config.mtu_discovery_config(None); // Disable MTU discovery
 		let transport_config = Arc::new(transport_config);
@@ -38,29 +98,24 @@ impl Quic {
 		server_config.transport_config(transport_config.clone());
 		client_config.transport_config(transport_config);
 
-		// There's a bit more boilerplate to make a generic endpoint.
-		let runtime = quinn::default_runtime().context("no async runtime")?;
-		let endpoint_config = quinn::EndpointConfig::default();
-		let socket = std::net::UdpSocket::bind(config.listen).context("failed to bind UDP socket")?;
-
-		// Create the generic QUIC endpoint.
-		let mut quic = quinn::Endpoint::new(endpoint_config, Some(server_config), socket, runtime)
-			.context("failed to create QUIC endpoint")?;
-		quic.set_default_client_config(client_config);
+		Ok((server_config, client_config))
+	}
 
-		let api = config.api.map(|url| {
-			log::info!("using moq-api: url={}", url);
-			moq_api::Client::new(url)
-		});
+	/// Returns the QUIC endpoint, used to apply new settings on reload.
+	pub fn endpoint(&self) -> quinn::Endpoint {
+		self.quic.clone()
+	}
 
-		if let Some(ref node) = config.api_node {
-			log::info!("advertising origin: url={}", node);
-		}
+	pub fn origin(&self) -> Origin {
+		self.origin.clone()
+	}
 
-		let origin = Origin::new(api, config.api_node, quic.clone());
-		let conns = JoinSet::new();
+	pub fn registry(&self) -> Registry {
+		self.registry.clone()
+	}
 
-		Ok(Self { quic, origin, conns })
+	pub fn metrics(&self) -> Metrics {
+		self.metrics.clone()
 	}
 
 	pub async fn serve(mut self) -> anyhow::Result<()> {
@@ -70,8 +125,15 @@ impl Quic {
 			tokio::select! {
 				res = self.quic.accept() => {
 					let conn = res.context("failed to accept QUIC connection")?;
-					let mut session = Session::new(self.origin.clone());
-					self.conns.spawn(async move { session.run(conn).await });
+					let mut session = Session::new(self.origin.clone(), self.registry.clone(), self.metrics.clone(), self.limits.clone(), self.shutdown.clone());
+					let active = self.metrics.connection();
+
+					// The connection ID is recorded once the handshake completes.
+					let span = tracing::info_span!("connection", ip = %conn.remote_address(), id = tracing::field::Empty);
+					self.conns.spawn(async move {
+						let _active = active;
+						session.run(conn).await
+					}.instrument(span));
 				},
 				res = self.conns.join_next(), if !self.conns.is_empty() => {
 					let res = res.expect("no tasks").expect("task aborted");
@@ -79,6 +141,47 @@ impl Quic {
 						log::warn!("connection terminated: {:?}", err);
 					}
 				},
+				_ = self.shutdown.started() => break,
+			}
+		}
+
+		self.drain().await;
+
+		Ok(())
+	}
+
+	// Refuse new connections and wait for the existing sessions to leave, closing them at the deadline.
+	async fn drain(&mut self) {
+		// NOTE: A reload won't set this again, see Reloader.
+		self.quic.set_server_config(None);
+
+		log::info!(
+			"waiting for sessions to leave: sessions={} timeout={:?}",
+			self.conns.len(),
+			self.shutdown_timeout
+		);
+
+		if tokio::time::timeout(self.shutdown_timeout, self.join_all())
+			.await
+			.is_ok()
+		{
+			log::info!("all sessions left");
+			return;
+		}
+
+		log::warn!(
+			"shutdown timeout reached, closing sessions: sessions={}",
+			self.conns.len()
+		);
+		self.quic.close(quinn::VarInt::from_u32(code::STOP), b"shutdown");
+
+		tokio::time::timeout(Self::CLOSE_TIMEOUT, self.join_all()).await.ok();
+	}
+
+	async fn join_all(&mut self) {
+		while let Some(res) = self.conns.join_next().await {
+			if let Ok(Err(err)) = res {
+				log::warn!("connection terminated: {:?}", err);
 			}
 		}
 	}
@@ -0,0 +1,172 @@
+use std::{
+	collections::HashMap,
+	net,
+	sync::{atomic, Arc, Mutex},
+	time,
+};
+
+use moq_transport::{code, setup::Role};
+use serde::Serialize;
+
+/// The active sessions, so they can be listed and kicked via the admin API.
+#[derive(Clone, Default)]
+pub struct Registry {
+	sessions: Arc<Mutex<HashMap<usize, Entry>>>,
+
+	// The UDP bytes sent and received by sessions that have been removed.
+	// NOTE: These are only modified while holding the sessions lock, so bytes() is consistent.
+	sent: Arc<atomic::AtomicU64>,
+	received: Arc<atomic::AtomicU64>,
+}
+
+impl Registry {
+	/// Add a session after the handshake, which is removed when the returned handle is dropped.
+	pub fn register(&self, id: usize, role: Role, path: &str, session: webtransport_quinn::Session) -> Registered {
+		let entry = Entry {
+			role,
+			path: path.to_string(),
+			remote: session.remote_address(),
+			connected: time::SystemTime::now(),
+			session,
+		};
+
+		self.sessions.lock().unwrap().insert(id, entry);
+
+		Registered {
+			registry: self.clone(),
+			id,
+		}
+	}
+
+	/// Returns each active session, sorted by ID.
+	pub fn sessions(&self) -> Vec<SessionInfo> {
+		let sessions = self.sessions.lock().unwrap();
+
+		let mut sessions: Vec<SessionInfo> = sessions
+			.iter()
+			.map(|(id, entry)| SessionInfo {
+				id: *id,
+				role: match entry.role {
+					Role::Publisher => "publisher",
+					Role::Subscriber => "subscriber",
+					Role::Both => "both",
+				},
+				path: entry.path.clone(),
+				remote: entry.remote,
+				connected: unix_secs(entry.connected),
+				rtt_ms: entry.session.rtt().as_millis() as u64,
+			})
+			.collect();
+
+		sessions.sort_by_key(|session| session.id);
+		sessions
+	}
+
+	/// Returns the number of active sessions.
+	pub fn count(&self) -> usize {
+		self.sessions.lock().unwrap().len()
+	}
+
+	/// Returns the total UDP bytes sent and received by every session, including those that have been removed.
+	pub fn bytes(&self) -> (u64, u64) {
+		let sessions = self.sessions.lock().unwrap();
+
+		let mut sent = self.sent.load(atomic::Ordering::Relaxed);
+		let mut received = self.received.load(atomic::Ordering::Relaxed);
+
+		for entry in sessions.values() {
+			let stats = entry.session.stats();
+			sent += stats.udp_tx.bytes;
+			received += stats.udp_rx.bytes;
+		}
+
+		(sent, received)
+	}
+
+	/// Returns the number of subscriber sessions for the given broadcast.
+	pub fn subscribers(&self, path: &str) -> usize {
+		self.sessions
+			.lock()
+			.unwrap()
+			.values()
+			.filter(|entry| entry.role == Role::Subscriber && entry.path == path)
+			.count()
+	}
+
+	/// Close the session with the given ID, returning false if it doesn't exist.
+	pub fn kick(&self, id: usize) -> bool {
+		match self.sessions.lock().unwrap().get(&id) {
+			Some(entry) => {
+				entry.session.close(code::STOP, b"kicked");
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Close every publisher session for the given broadcast, returning the number closed.
+	pub fn kick_publishers(&self, path: &str) -> usize {
+		let sessions = self.sessions.lock().unwrap();
+
+		let publishers: Vec<&Entry> = sessions
+			.values()
+			.filter(|entry| entry.role == Role::Publisher && entry.path == path)
+			.collect();
+
+		for entry in &publishers {
+			entry.session.close(code::STOP, b"broadcast closed");
+		}
+
+		publishers.len()
+	}
+}
+
+// An active session, including a handle to close it.
+struct Entry {
+	role: Role,
+	path: String,
+	remote: net::SocketAddr,
+	connected: time::SystemTime,
+	session: webtransport_quinn::Session,
+}
+
+/// A handle that removes the session from the [Registry] when dropped.
+pub struct Registered {
+	registry: Registry,
+	id: usize,
+}
+
+impl Drop for Registered {
+	fn drop(&mut self) {
+		let mut sessions = self.registry.sessions.lock().unwrap();
+
+		// Keep the byte counters when the session goes away.
+		if let Some(entry) = sessions.remove(&self.id) {
+			let stats = entry.session.stats();
+			self.registry
+				.sent
+				.fetch_add(stats.udp_tx.bytes, atomic::Ordering::Relaxed);
+			self.registry
+				.received
+				.fetch_add(stats.udp_rx.bytes, atomic::Ordering::Relaxed);
+		}
+	}
+}
+
+/// A session as returned by the admin API.
+#[derive(Serialize)]
+pub struct SessionInfo {
+	pub id: usize,
+	pub role: &'static str,
+	pub path: String,
+	pub remote: net::SocketAddr,
+
+	/// The time the handshake completed, in seconds since the UNIX epoch.
+	pub connected: u64,
+	pub rtt_ms: u64,
+}
+
+// Convert a timestamp into seconds since the UNIX epoch for JSON.
+pub(crate) fn unix_secs(t: time::SystemTime) -> u64 {
+	t.duration_since(time::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
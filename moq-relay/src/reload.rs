@@ -0,0 +1,120 @@
+use std::time;
+
+use anyhow::Context;
+use tokio::{
+	signal::unix::{signal, SignalKind},
+	sync::watch,
+};
+
+use crate::{Config, Limits, Quic, Shutdown, Tls};
+
+/// Reloads the configuration on SIGHUP without dropping existing sessions.
+///
+/// The TLS certificates, limits and QUIC transport settings apply to new sessions, and the admin token to new requests.
+/// Everything else, such as the listen address, requires a restart.
+/// NOTE: Connections to other relays keep the original QUIC transport settings.
+///
+/// The certificate files are also polled, so they're reloaded when rotated without a signal.
+/// A generated certificate is renewed before it expires, even if polling is disabled.
+pub struct Reloader {
+	tls: Tls,
+	endpoint: quinn::Endpoint,
+	limits: watch::Sender<Limits>,
+	admin_token: watch::Sender<Option<String>>,
+	poll: Option<time::Duration>,
+
+	// New connections are refused while shutting down, so don't apply a new server config.
+	shutdown: Shutdown,
+}
+
+impl Reloader {
+	// How often to check if a generated certificate needs to be renewed.
+	const RENEW_INTERVAL: time::Duration = time::Duration::from_secs(60 * 60);
+
+	pub fn new(
+		tls: Tls,
+		endpoint: quinn::Endpoint,
+		limits: watch::Sender<Limits>,
+		admin_token: watch::Sender<Option<String>>,
+		poll: Option<time::Duration>,
+		shutdown: Shutdown,
+	) -> Self {
+		Self {
+			tls,
+			endpoint,
+			limits,
+			admin_token,
+			poll,
+			shutdown,
+		}
+	}
+
+	pub async fn run(self) -> anyhow::Result<()> {
+		let mut hangup = signal(SignalKind::hangup()).context("failed to register SIGHUP handler")?;
+
+		// Never tick if polling is disabled.
+		let mut poll = self.poll.map(tokio::time::interval);
+		let mut renew = tokio::time::interval(Self::RENEW_INTERVAL);
+
+		loop {
+			tokio::select! {
+				res = hangup.recv() => {
+					if res.is_none() {
+						return Ok(());
+					}
+
+					log::info!("reloading config");
+
+					// Keep the previous settings if the new config is invalid.
+					if let Err(err) = self.reload() {
+						log::warn!("failed to reload config: {:#}", err);
+					}
+				},
+				_ = async { poll.as_mut().unwrap().tick().await }, if poll.is_some() => {
+					if !self.tls.certs_changed() {
+						continue;
+					}
+
+					log::info!("certificate files changed, reloading");
+
+					// Keep the previous certificates if the files are invalid, ex. only half written.
+					// We'll try again on the next tick since the modification time isn't updated.
+					match self.tls.reload_certs() {
+						Ok(()) => log::info!("reloaded certificates: fingerprints={:?}", self.tls.fingerprints()),
+						Err(err) => log::warn!("failed to reload certificates: {:#}", err),
+					}
+				},
+				_ = renew.tick() => {
+					if !self.tls.cert_expiring() {
+						continue;
+					}
+
+					log::info!("generated certificate expiring, renewing");
+
+					match self.tls.reload_certs() {
+						Ok(()) => log::info!("renewed certificate: fingerprints={:?}", self.tls.fingerprints()),
+						Err(err) => log::warn!("failed to renew certificate: {:#}", err),
+					}
+				},
+			}
+		}
+	}
+
+	fn reload(&self) -> anyhow::Result<()> {
+		let config = Config::load()?;
+
+		// Load everything before applying anything, so an error doesn't leave a partial reload.
+		let (server_config, _) = Quic::configs(&config, &self.tls)?;
+		self.tls.reload(&config)?;
+
+		if !self.shutdown.is_started() {
+			self.endpoint.set_server_config(Some(server_config));
+		}
+		self.limits.send_replace(config.limits());
+		self.admin_token.send_replace(config.admin_token.clone());
+
+		log::info!("reloaded config: limits={:?}", config.limits());
+
+		Ok(())
+	}
+}
@@ -1,17 +1,42 @@
+use std::{future::Future, sync::Arc};
+
 use anyhow::Context;
 
-use moq_transport::{session::Request, setup::Role, MoqError};
+use moq_transport::{
+	code,
+	session::{Request, SessionError},
+	setup::Role,
+	MoqError,
+};
+use tokio::sync::watch;
+use tracing::Instrument;
 
-use crate::Origin;
+use crate::{Limits, Metrics, Origin, Registry, Shutdown};
 
 #[derive(Clone)]
 pub struct Session {
 	origin: Origin,
+	registry: Registry,
+	metrics: Metrics,
+	limits: watch::Receiver<Limits>,
+	shutdown: Shutdown,
 }
 
 impl Session {
-	pub fn new(origin: Origin) -> Self {
-		Self { origin }
+	pub fn new(
+		origin: Origin,
+		registry: Registry,
+		metrics: Metrics,
+		limits: watch::Receiver<Limits>,
+		shutdown: Shutdown,
+	) -> Self {
+		Self {
+			origin,
+			registry,
+			metrics,
+			limits,
+			shutdown,
+		}
 	}
 
 	pub async fn run(&mut self, conn: quinn::Connecting) -> anyhow::Result<()> {
@@ -26,6 +51,7 @@ impl Session {
 			conn.stable_id()
 		);
 		let id = conn.stable_id();
+		tracing::Span::current().record("id", id);
 
 		// Wait for the CONNECT request.
 		let request = webtransport_quinn::accept(conn)
@@ -43,6 +69,9 @@ impl Session {
 			.await
 			.context("failed to respond to WebTransport request")?;
 
+		// Keep a handle so the admin API can close the session.
+		let handle = session.clone();
+
 		// Perform the MoQ handshake.
 		let request = moq_transport::session::Server::accept(session)
 			.await
@@ -52,14 +81,41 @@ impl Session {
 
 		let role = request.role();
 
+		// Identifies the publisher, so only it can resume or take over its broadcast.
+		let token = request.token().map(str::to_string);
+
+		// Another relay pushing a broadcast it's already registered in moq-api, see Push.
+		// Only trusted with the push token, otherwise it's treated like any other publisher.
+		let pushed = request.extensions().push && self.origin.is_pusher(token.as_deref());
+		if request.extensions().push && !pushed {
+			log::warn!("ignoring push without a valid token: id={} path={}", id, path);
+		}
+
+		// NOTE: This is racey, so a burst of sessions could slightly exceed the limit.
+		let max = self.limits.borrow().sessions;
+		if max.is_some_and(|max| self.registry.count() >= max) {
+			log::warn!("too many sessions: id={} max={:?}", id, max);
+			request.reject(code::LIMIT_EXCEEDED);
+			return Ok(());
+		}
+
+		let _registered = self.registry.register(id, role, &path, handle);
+		let _active = self.metrics.session(role);
+
 		match role {
 			Role::Publisher => {
-				if let Err(err) = self.serve_publisher(id, request, &path).await {
+				let span = tracing::info_span!("publisher", broadcast = %path);
+				if let Err(err) = self
+					.serve_publisher(id, request, &path, pushed, token.as_deref())
+					.instrument(span)
+					.await
+				{
 					log::warn!("error serving publisher: id={} path={} err={:#?}", id, path, err);
 				}
 			}
 			Role::Subscriber => {
-				if let Err(err) = self.serve_subscriber(id, request, &path).await {
+				let span = tracing::info_span!("subscriber", broadcast = %path);
+				if let Err(err) = self.serve_subscriber(id, request, &path).instrument(span).await {
 					log::warn!("error serving subscriber: id={} path={} err={:#?}", id, path, err);
 				}
 			}
@@ -74,10 +130,17 @@ impl Session {
 		Ok(())
 	}
 
-	async fn serve_publisher(&mut self, id: usize, request: Request, path: &str) -> anyhow::Result<()> {
-		log::info!("serving publisher: id={}, path={}", id, path);
-
-		let mut origin = match self.origin.publish(path).await {
+	async fn serve_publisher(
+		&mut self,
+		id: usize,
+		request: Request,
+		path: &str,
+		pushed: bool,
+		token: Option<&str>,
+	) -> anyhow::Result<()> {
+		log::info!("serving publisher: id={}, path={}, pushed={}", id, path, pushed);
+
+		let mut origin = match self.origin.publish(path, pushed, token).await {
 			Ok(origin) => origin,
 			Err(err) => {
 				request.reject(err.code());
@@ -87,25 +150,68 @@ impl Session {
 
 		let session = request.subscriber(origin.broadcast.clone()).await?;
 
+		// A publisher restarts its numbering when it reconnects, so number its groups after any left by the previous publisher.
+		// Another relay pushing the broadcast keeps the original numbering, so duplicate groups are merged instead.
+		session.set_rebase(!pushed);
+
+		// The broadcast stays open for a moment after the session ends, so a reconnecting publisher can take over.
 		tokio::select! {
-			_ = session.run() => origin.close().await?,
-			_ = origin.run() => (), // TODO send error to session
+			_ = self.drain(session.clone().run(), session.go_away("")) => (),
+			_ = origin.run() => {
+				// Another publisher took over or the origin couldn't be refreshed, so close the session.
+				// TODO send the error to the session
+				self.registry.kick(id);
+			}
 		};
 
+		// We're not coming back, so delete the origin now rather than waiting for the publisher to reconnect.
+		if self.shutdown.is_started() {
+			origin.close().await?;
+		}
+
 		Ok(())
 	}
 
 	async fn serve_subscriber(&mut self, id: usize, request: Request, path: &str) -> anyhow::Result<()> {
+		// Another relay connects without a path, and fetches each broadcast by namespace.
+		if path.is_empty() {
+			log::info!("serving relay: id={}", id);
+
+			let session = request.resolver(Arc::new(self.origin.clone())).await?;
+			self.drain(session.clone().run(), session.go_away("")).await?;
+
+			return Ok(());
+		}
+
 		log::info!("serving subscriber: id={} path={}", id, path);
 
 		let subscriber = self.origin.subscribe(path);
 
 		let session = request.publisher(subscriber.broadcast.clone()).await?;
-		session.run().await?;
+		self.drain(session.clone().run(), session.go_away("")).await?;
 
 		// Make sure this doesn't get dropped too early
 		drop(subscriber);
 
 		Ok(())
 	}
+
+	// Run the session until it ends, sending a GOAWAY if we start shutting down first.
+	async fn drain<R, G>(&self, run: R, go_away: G) -> Result<(), SessionError>
+	where
+		R: Future<Output = Result<(), SessionError>>,
+		G: Future<Output = Result<(), SessionError>>,
+	{
+		tokio::pin!(run);
+
+		tokio::select! {
+			res = &mut run => return res,
+			_ = self.shutdown.started() => {},
+		}
+
+		log::debug!("sending GOAWAY");
+		go_away.await?;
+
+		run.await
+	}
 }
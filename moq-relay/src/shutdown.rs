@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use tokio::{
+	signal::unix::{signal, SignalKind},
+	sync::watch,
+};
+
+/// Starts a graceful shutdown on SIGTERM or via the admin API.
+///
+/// New connections are refused and existing sessions are sent a GOAWAY, then given until the deadline to leave.
+/// A second SIGTERM exits immediately.
+#[derive(Clone)]
+pub struct Shutdown {
+	started: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+	pub fn new() -> Self {
+		Self {
+			started: Arc::new(watch::channel(false).0),
+		}
+	}
+
+	/// Start shutting down, returning false if we already were.
+	pub fn start(&self) -> bool {
+		self.started.send_if_modified(|started| {
+			if *started {
+				return false;
+			}
+
+			log::info!("starting graceful shutdown");
+			*started = true;
+			true
+		})
+	}
+
+	pub fn is_started(&self) -> bool {
+		*self.started.borrow()
+	}
+
+	/// Wait until the shutdown has started.
+	pub async fn started(&self) {
+		let mut started = self.started.subscribe();
+
+		// NOTE: This can't fail because we hold the sender.
+		started.wait_for(|started| *started).await.ok();
+	}
+
+	/// Start shutting down on the first SIGTERM, returning on the second.
+	pub async fn run(self) -> anyhow::Result<()> {
+		let mut terminate = signal(SignalKind::terminate()).context("failed to register SIGTERM handler")?;
+
+		while terminate.recv().await.is_some() {
+			if !self.start() {
+				log::warn!("received second SIGTERM, exiting immediately");
+				break;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+impl Default for Shutdown {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -4,9 +4,9 @@ use rustls::server::{ClientHello, ResolvesServerCert};
 use rustls::sign::CertifiedKey;
 use rustls::{Certificate, PrivateKey, RootCertStore};
 use std::io::{self, Cursor, Read};
-use std::path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, RwLock};
 use std::{fs, time};
+use std::{net, path};
 use webpki::{DnsNameRef, EndEntityCert};
 
 use crate::Config;
@@ -15,21 +15,15 @@ use crate::Config;
 pub struct Tls {
 	pub server: rustls::ServerConfig,
 	pub client: rustls::ClientConfig,
-	pub fingerprints: Vec<String>,
+
+	// The certificates used by the server, which can be swapped on reload.
+	serve: Arc<ServeCerts>,
 }
 
 impl Tls {
 	pub fn load(config: &Config) -> anyhow::Result<Self> {
-		let mut serve = ServeCerts::default();
-
-		// Load the certificate and key files based on their index.
-		anyhow::ensure!(
-			config.tls_cert.len() == config.tls_key.len(),
-			"--tls-cert and --tls-key counts differ"
-		);
-		for (chain, key) in config.tls_cert.iter().zip(config.tls_key.iter()) {
-			serve.load(chain, key)?;
-		}
+		let serve = Arc::new(ServeCerts::default());
+		serve.load(config)?;
 
 		// Create a list of acceptable root certificates.
 		let mut roots = RootCertStore::empty();
@@ -64,32 +58,186 @@ impl Tls {
 			client.dangerous().set_certificate_verifier(Arc::new(noop));
 		}
 
-		let fingerprints = serve.fingerprints();
-
 		// Create the TLS configuration we'll use as a server (relay <- browser)
 		let server = rustls::ServerConfig::builder()
 			.with_safe_defaults()
 			.with_no_client_auth()
-			.with_cert_resolver(Arc::new(serve));
+			.with_cert_resolver(serve.clone());
 
-		let certs = Self {
-			server,
-			client,
-			fingerprints,
-		};
+		let certs = Self { server, client, serve };
 
 		Ok(certs)
 	}
+
+	/// Load the certificates from a new config, used for new connections once they've all loaded successfully.
+	pub fn reload(&self, config: &Config) -> anyhow::Result<()> {
+		self.serve.load(config)
+	}
+
+	/// Load the certificates again from the same files, ex. after they were rotated.
+	/// A generated certificate is replaced with a new one instead.
+	pub fn reload_certs(&self) -> anyhow::Result<()> {
+		let (paths, generate) = {
+			let files = self.serve.files.lock().unwrap();
+			(files.paths.clone(), files.generate.clone())
+		};
+
+		match generate.is_empty() {
+			true => self.serve.load_files(paths),
+			false => self.serve.generate(generate),
+		}
+	}
+
+	/// Returns true if any of the certificate or key files were modified since they were loaded.
+	pub fn certs_changed(&self) -> bool {
+		let files = self.serve.files.lock().unwrap();
+		files.modified != Files::modified(&files.paths)
+	}
+
+	/// Returns true if the certificate was generated and is about to expire, so it should be renewed.
+	pub fn cert_expiring(&self) -> bool {
+		let files = self.serve.files.lock().unwrap();
+		files
+			.expires
+			.is_some_and(|expires| expires < time::SystemTime::now() + GENERATE_RENEW)
+	}
+
+	/// Return the SHA256 fingerprint of each certificate, which changes on reload.
+	pub fn fingerprints(&self) -> Vec<String> {
+		self.serve.fingerprints()
+	}
 }
 
 #[derive(Default)]
 struct ServeCerts {
-	list: Vec<Arc<CertifiedKey>>,
+	list: RwLock<Vec<Arc<CertifiedKey>>>,
+
+	// The files used to load the list, so they can be reloaded.
+	files: Mutex<Files>,
+}
+
+// The certificate and key file pairs, and the time each file was last modified.
+// Otherwise the hostnames and expiration of a generated certificate.
+#[derive(Default)]
+struct Files {
+	paths: Vec<(path::PathBuf, path::PathBuf)>,
+	modified: Vec<Option<time::SystemTime>>,
+
+	generate: Vec<String>,
+	expires: Option<time::SystemTime>,
+}
+
+// WebTransport's serverCertificateHashes requires a certificate valid for at most 14 days.
+const GENERATE_VALIDITY: time::Duration = time::Duration::from_secs(10 * 24 * 60 * 60);
+
+// Generate a new certificate when the current one expires within this duration.
+const GENERATE_RENEW: time::Duration = time::Duration::from_secs(24 * 60 * 60);
+
+impl Files {
+	fn modified(paths: &[(path::PathBuf, path::PathBuf)]) -> Vec<Option<time::SystemTime>> {
+		paths
+			.iter()
+			.flat_map(|(chain, key)| [chain, key])
+			.map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+			.collect()
+	}
 }
 
 impl ServeCerts {
+	// Load every certificate and key, replacing the current list only if they're all valid.
+	pub fn load(&self, config: &Config) -> anyhow::Result<()> {
+		// Generate a self-signed certificate for local development if none was provided.
+		if config.dev && config.tls_cert.is_empty() && config.tls_key.is_empty() {
+			return self.generate(config.tls_generate.clone());
+		}
+
+		anyhow::ensure!(
+			!config.tls_cert.is_empty(),
+			"missing --tls-cert, or use --dev to generate one"
+		);
+
+		// Load the certificate and key files based on their index.
+		anyhow::ensure!(
+			config.tls_cert.len() == config.tls_key.len(),
+			"--tls-cert and --tls-key counts differ"
+		);
+
+		let paths = config
+			.tls_cert
+			.iter()
+			.cloned()
+			.zip(config.tls_key.iter().cloned())
+			.collect();
+
+		self.load_files(paths)
+	}
+
+	fn load_files(&self, paths: Vec<(path::PathBuf, path::PathBuf)>) -> anyhow::Result<()> {
+		// Check the modification time first, so a write while loading will trigger another reload.
+		let modified = Files::modified(&paths);
+
+		let list = paths
+			.iter()
+			.map(|(chain, key)| Self::load_one(chain, key))
+			.collect::<anyhow::Result<Vec<_>>>()?;
+
+		*self.list.write().unwrap() = list;
+		*self.files.lock().unwrap() = Files {
+			paths,
+			modified,
+			..Default::default()
+		};
+
+		Ok(())
+	}
+
+	// Generate a short-lived, self-signed ECDSA certificate for the given hostnames.
+	fn generate(&self, hostnames: Vec<String>) -> anyhow::Result<()> {
+		anyhow::ensure!(!hostnames.is_empty(), "missing --tls-generate hostnames");
+
+		// Backdate the certificate slightly in case the client's clock is behind.
+		let not_before = time::SystemTime::now() - time::Duration::from_secs(60);
+		let expires = not_before + GENERATE_VALIDITY;
+
+		let mut params = rcgen::CertificateParams::default();
+
+		// IP literals need an IP address SAN, since clients won't match them against a DNS name.
+		params.subject_alt_names = hostnames
+			.iter()
+			.map(|hostname| match hostname.parse::<net::IpAddr>() {
+				Ok(ip) => rcgen::SanType::IpAddress(ip),
+				Err(_) => rcgen::SanType::DnsName(hostname.clone()),
+			})
+			.collect();
+
+		params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+		params.not_before = not_before.into();
+		params.not_after = expires.into();
+
+		let cert = rcgen::Certificate::from_params(params).context("failed to generate certificate")?;
+		let chain = vec![Certificate(cert.serialize_der()?)];
+		let key = PrivateKey(cert.serialize_private_key_der());
+		let key = rustls::sign::any_supported_type(&key)?;
+
+		*self.list.write().unwrap() = vec![Arc::new(CertifiedKey::new(chain, key))];
+
+		log::info!(
+			"generated self-signed certificate: hostnames={:?} fingerprints={:?}",
+			hostnames,
+			self.fingerprints()
+		);
+
+		*self.files.lock().unwrap() = Files {
+			generate: hostnames,
+			expires: Some(expires),
+			..Default::default()
+		};
+
+		Ok(())
+	}
+
 	// Load a certificate and cooresponding key from a file
-	pub fn load(&mut self, chain: &path::PathBuf, key: &path::PathBuf) -> anyhow::Result<()> {
+	fn load_one(chain: &path::PathBuf, key: &path::PathBuf) -> anyhow::Result<Arc<CertifiedKey>> {
 		// Read the PEM certificate chain
 		let chain = fs::File::open(chain).context("failed to open cert file")?;
 		let mut chain = io::BufReader::new(chain);
@@ -124,15 +272,14 @@ impl ServeCerts {
 		let key = PrivateKey(keys.remove(0));
 		let key = rustls::sign::any_supported_type(&key)?;
 
-		let certified = Arc::new(CertifiedKey::new(chain, key));
-		self.list.push(certified);
-
-		Ok(())
+		Ok(Arc::new(CertifiedKey::new(chain, key)))
 	}
 
 	// Return the SHA256 fingerprint of our certificates.
 	pub fn fingerprints(&self) -> Vec<String> {
 		self.list
+			.read()
+			.unwrap()
 			.iter()
 			.map(|ck| {
 				let fingerprint = digest(&SHA256, ck.cert[0].as_ref());
@@ -145,9 +292,11 @@ impl ServeCerts {
 
 impl ResolvesServerCert for ServeCerts {
 	fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+		let list = self.list.read().unwrap();
+
 		if let Some(name) = client_hello.server_name() {
 			if let Ok(dns_name) = DnsNameRef::try_from_ascii_str(name) {
-				for ck in &self.list {
+				for ck in list.iter() {
 					// TODO I gave up on caching the parsed result because of lifetime hell.
 					// If this shows up on benchmarks, somebody should fix it.
 					let leaf = ck.cert.first().expect("missing certificate");
@@ -161,7 +310,7 @@ impl ResolvesServerCert for ServeCerts {
 		}
 
 		// Default to the last certificate if we couldn't find one.
-		self.list.last().cloned()
+		list.last().cloned()
 	}
 }
 
@@ -15,10 +15,6 @@ pub struct Web {
 
 impl Web {
 	pub fn new(config: Config, tls: Tls) -> Self {
-		// Get the first certificate's fingerprint.
-		// TODO serve all of them so we can support multiple signature algorithms.
-		let fingerprint = tls.fingerprints.first().expect("missing certificate").clone();
-
 		let mut tls_config = tls.server.clone();
 		tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
 		let tls_config = axum_server::tls_rustls::RustlsConfig::from_config(Arc::new(tls_config));
@@ -26,7 +22,7 @@ impl Web {
 		let app = Router::new()
 			.route("/fingerprint", get(serve_fingerprint))
 			.layer(CorsLayer::new().allow_origin(Any).allow_methods([Method::GET]))
-			.with_state(fingerprint);
+			.with_state(tls);
 
 		let server = axum_server::bind_rustls(config.listen, tls_config);
 
@@ -39,6 +35,8 @@ impl Web {
 	}
 }
 
-async fn serve_fingerprint(State(fingerprint): State<String>) -> impl IntoResponse {
-	fingerprint
+// Get the first certificate's fingerprint, which changes when the certificates are reloaded.
+// TODO serve all of them so we can support multiple signature algorithms.
+async fn serve_fingerprint(State(tls): State<Tls>) -> impl IntoResponse {
+	tls.fingerprints().first().expect("missing certificate").clone()
 }
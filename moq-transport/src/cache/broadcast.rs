@@ -2,22 +2,28 @@
 //!
 //! The [Publisher] can create tracks, either manually or on request.
 //! It receives all requests by a [Subscriber] for a tracks that don't exist.
-//! The simplest implementation is to close every unknown track with [CacheError::NotFound].
+//! The simplest implementation is to close every unknown track with [CacheError::NotFound], which is what [NotFound] does.
+//! Implement [Handler] and call [Publisher::serve] to generate tracks on demand instead.
 //!
 //! A [Subscriber] can request tracks by name.
 //! If the track already exists, it will be returned.
 //! If the track doesn't exist, it will be sent to [Unknown] to be handled.
 //! A [Subscriber] can be cloned to create multiple subscriptions.
+//! A [Subscriber] can also list the current tracks via [Subscriber::tracks], or be notified as they're added and removed via [Subscriber::track_events].
 //!
 //! The broadcast is automatically closed with [CacheError::Closed] when [Publisher] is dropped, or all [Subscriber]s are dropped.
 use std::{
-	collections::{hash_map, HashMap, VecDeque},
+	collections::{hash_map, HashMap, HashSet, VecDeque},
 	fmt,
+	future::{self, Future},
 	ops::Deref,
+	pin::Pin,
 	sync::Arc,
+	task, time,
 };
 
-use super::{track, CacheError, Watch};
+use super::{metrics::Metrics, track, CacheError, Watch};
+use crate::message::{GroupOrder, Metadata};
 
 /// Create a new broadcast.
 pub fn new(id: &str) -> (Publisher, Subscriber) {
@@ -41,7 +47,11 @@ pub struct Info {
 struct State {
 	tracks: HashMap<String, track::Subscriber>,
 	requested: VecDeque<track::Publisher>,
+	metadata: Metadata,
 	closed: Result<(), CacheError>,
+
+	// Passed to each track created or requested, see Publisher::set_metrics.
+	metrics: Option<Metrics>,
 }
 
 impl State {
@@ -61,11 +71,12 @@ impl State {
 		Ok(())
 	}
 
-	pub fn request(&mut self, name: &str) -> Result<track::Subscriber, CacheError> {
+	pub fn request(&mut self, name: &str, order: GroupOrder) -> Result<track::Subscriber, CacheError> {
 		self.closed.clone()?;
 
-		// Create a new track.
-		let (publisher, subscriber) = track::new(name);
+		// Create a new track, which won't have any metadata until the publisher answers.
+		let (mut publisher, subscriber) = track::requested(name, order);
+		publisher.set_metrics(self.metrics.clone());
 
 		// Insert the track into our Map so we deduplicate future requests.
 		self.tracks.insert(name.to_string(), subscriber.clone());
@@ -76,6 +87,16 @@ impl State {
 		Ok(subscriber)
 	}
 
+	pub fn remove(&mut self, name: &str) -> Result<track::Subscriber, CacheError> {
+		self.tracks.remove(name).ok_or(CacheError::NotFound)
+	}
+
+	pub fn requeue(&mut self, track: track::Publisher) -> Result<(), CacheError> {
+		self.closed.clone()?;
+		self.requested.push_back(track);
+		Ok(())
+	}
+
 	pub fn has_next(&self) -> Result<bool, CacheError> {
 		// Check if there's any elements in the queue before checking closed.
 		if !self.requested.is_empty() {
@@ -93,7 +114,13 @@ impl State {
 
 	pub fn close(&mut self, err: CacheError) -> Result<(), CacheError> {
 		self.closed.clone()?;
-		self.closed = Err(err);
+		self.closed = Err(err.clone());
+
+		// Close any requests that will never be answered.
+		for track in self.requested.drain(..) {
+			track.close(err.clone()).ok();
+		}
+
 		Ok(())
 	}
 }
@@ -104,6 +131,8 @@ impl Default for State {
 			tracks: HashMap::new(),
 			closed: Ok(()),
 			requested: VecDeque::new(),
+			metadata: Metadata::default(),
+			metrics: None,
 		}
 	}
 }
@@ -126,15 +155,61 @@ impl Publisher {
 	/// Create a new track with the given name, inserting it into the broadcast.
 	pub fn create_track(&mut self, name: &str) -> Result<track::Publisher, CacheError> {
 		let (publisher, subscriber) = track::new(name);
-		self.state.lock_mut().insert(subscriber)?;
+		self.insert_created(publisher, subscriber)
+	}
+
+	/// Create a new track with the given name and metadata, inserting it into the broadcast.
+	///
+	/// Unlike calling [track::Publisher::set_metadata] afterwards, no subscriber can see the track without its metadata.
+	pub fn create_track_with(&mut self, name: &str, metadata: Metadata) -> Result<track::Publisher, CacheError> {
+		let (publisher, subscriber) = track::new_with_metadata(name, metadata);
+		self.insert_created(publisher, subscriber)
+	}
+
+	fn insert_created(
+		&mut self,
+		mut publisher: track::Publisher,
+		subscriber: track::Subscriber,
+	) -> Result<track::Publisher, CacheError> {
+		let mut state = self.state.lock_mut();
+		publisher.set_metrics(state.metrics.clone());
+		state.insert(subscriber)?;
 		Ok(publisher)
 	}
 
+	/// Count the segments cached by each track created or requested from now on.
+	///
+	/// Tracks inserted via [Self::insert_track] aren't counted, since they were created elsewhere.
+	pub fn set_metrics(&mut self, metrics: Metrics) {
+		self.state.lock_mut().metrics = Some(metrics);
+	}
+
+	/// Set the metadata for the broadcast, which is sent to subscribers along with each track.
+	pub fn set_metadata(&mut self, metadata: Metadata) {
+		self.state.lock_mut().metadata = metadata;
+	}
+
 	/// Insert a track into the broadcast.
 	pub fn insert_track(&mut self, track: track::Subscriber) -> Result<(), CacheError> {
 		self.state.lock_mut().insert(track)
 	}
 
+	/// Remove a track from the broadcast, so future requests are sent to the publisher again.
+	///
+	/// Existing subscriptions to the track are unaffected.
+	pub fn remove_track(&mut self, name: &str) -> Result<track::Subscriber, CacheError> {
+		self.state.lock_mut().remove(name)
+	}
+
+	/// Return a track from [Self::next_track] to the queue, so it's requested again, ex. after the session serving it failed.
+	///
+	/// This can also queue a track from another broadcast, see [Self::serve_sources].
+	///
+	/// The track is dropped, closing it, if the broadcast is already closed.
+	pub fn requeue_track(&mut self, track: track::Publisher) -> Result<(), CacheError> {
+		self.state.lock_mut().requeue(track)
+	}
+
 	/// Block until the next track requested by a subscriber.
 	pub async fn next_track(&mut self) -> Result<track::Publisher, CacheError> {
 		loop {
@@ -151,10 +226,98 @@ impl Publisher {
 		}
 	}
 
+	/// Answer each track requested by a subscriber using the handler, until the broadcast is closed.
+	///
+	/// The requested track is closed with [CacheError::Timeout] if the handler doesn't answer, or the answered track has no metadata, within the timeout.
+	pub async fn serve<H: Handler>(&mut self, handler: H, timeout: time::Duration) -> Result<(), CacheError> {
+		let handler = Arc::new(handler);
+
+		loop {
+			// NOTE: This returns Closed when the broadcast is closed.
+			let track = self.next_track().await?;
+			let handler = handler.clone();
+
+			tokio::spawn(async move {
+				let name = track.name.clone();
+				if let Err(err) = Self::serve_track(handler.as_ref(), track, timeout).await {
+					log::debug!("failed to serve requested track: name={} err={:?}", name, err);
+				}
+			});
+		}
+	}
+
+	/// Forward each track requested from this broadcast to every source, until the broadcast is closed.
+	///
+	/// Each source receives its own handle from [track::Publisher::add_source], so the sources can be fetched over separate sessions,
+	/// ex. from two origins carrying the same broadcast, and the track stays open until every source has failed.
+	pub async fn serve_sources(&mut self, sources: &mut [Publisher]) -> Result<(), CacheError> {
+		loop {
+			// NOTE: This returns Closed when the broadcast is closed.
+			let track = self.next_track().await?;
+
+			// The handle is dropped, counting the source as closed, if its broadcast is already closed.
+			for source in sources.iter_mut() {
+				source.requeue_track(track.add_source()).ok();
+			}
+		}
+	}
+
+	async fn serve_track<H: Handler>(
+		handler: &H,
+		mut track: track::Publisher,
+		timeout: time::Duration,
+	) -> Result<(), CacheError> {
+		// The timeout covers waiting for the metadata too, otherwise a source that never starts would leave the track pending.
+		let request = async {
+			let source = handler.request(&track.name, track.order).await?;
+			let metadata = source.metadata().await?;
+			Ok((source, metadata))
+		};
+
+		let res = match tokio::time::timeout(timeout, request).await {
+			Ok(res) => res,
+			Err(_) => Err(CacheError::Timeout),
+		};
+
+		let mut source = match res {
+			Ok((source, metadata)) => {
+				track.set_metadata(metadata);
+				source
+			}
+			Err(err) => return track.close(err),
+		};
+
+		// Forward each segment from the generated track to the requested track.
+		// This doesn't copy any data, as segments are reference counted.
+		loop {
+			match source.segment().await {
+				Ok(Some(segment)) => track.insert_segment(segment)?,
+				Ok(None) => return track.close(CacheError::Closed),
+				Err(err) => return track.close(err),
+			}
+		}
+	}
+
 	/// Close the broadcast with an error.
 	pub fn close(self, err: CacheError) -> Result<(), CacheError> {
 		self.state.lock_mut().close(err)
 	}
+
+	/// Wait until the broadcast is closed, either by [Self::close] or because all [Subscriber]s were dropped.
+	pub async fn closed(&self) -> CacheError {
+		loop {
+			let notify = {
+				let state = self.state.lock();
+				if let Some(err) = state.closed.as_ref().err() {
+					return err.clone();
+				}
+
+				state.changed()
+			};
+
+			notify.await;
+		}
+	}
 }
 
 impl Deref for Publisher {
@@ -174,6 +337,26 @@ impl fmt::Debug for Publisher {
 	}
 }
 
+/// Generates tracks on demand when a [Subscriber] requests a track that doesn't exist, see [Publisher::serve].
+///
+/// This can be used to lazily produce tracks, such as a per-viewer rendition or a computed stats track.
+#[async_trait::async_trait]
+pub trait Handler: Send + Sync + 'static {
+	/// Return the requested track, typically by calling [track::new] and spawning a task to produce segments.
+	///
+	/// The default implementation rejects every request with [CacheError::NotFound].
+	async fn request(&self, name: &str, order: GroupOrder) -> Result<track::Subscriber, CacheError> {
+		let _ = (name, order);
+		Err(CacheError::NotFound)
+	}
+}
+
+/// A [Handler] that rejects every requested track with [CacheError::NotFound].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NotFound;
+
+impl Handler for NotFound {}
+
 /// Subscribe to a broadcast by requesting tracks.
 ///
 /// This can be cloned to create handles.
@@ -194,13 +377,48 @@ impl Subscriber {
 	/// If the track does not exist, it will be created and potentially fufilled by the publisher (via Unknown).
 	/// Otherwise, it will return [CacheError::NotFound].
 	pub fn get_track(&self, name: &str) -> Result<track::Subscriber, CacheError> {
+		self.get_track_ordered(name, GroupOrder::default())
+	}
+
+	/// Get a track from the broadcast by name, returning segments in the given order.
+	/// If the track needs to be requested, the order is also passed along to the publisher.
+	pub fn get_track_ordered(&self, name: &str, order: GroupOrder) -> Result<track::Subscriber, CacheError> {
 		let state = self.state.lock();
-		if let Some(track) = state.get(name)? {
+		if let Some(mut track) = state.get(name)? {
+			track.set_order(order);
 			return Ok(track);
 		}
 
 		// Request a new track if it does not exist.
-		state.into_mut().request(name)
+		state.into_mut().request(name, order)
+	}
+
+	/// Returns the names of all tracks currently in the broadcast, sorted.
+	///
+	/// This excludes tracks that are closed, or were requested by subscribers and the publisher has not answered yet.
+	pub fn tracks(&self) -> Vec<String> {
+		let mut names: Vec<String> = self
+			.state
+			.lock()
+			.tracks
+			.iter()
+			.filter(|(_, track)| track.is_live())
+			.map(|(name, _)| name.clone())
+			.collect();
+		names.sort();
+		names
+	}
+
+	/// Returns a stream of tracks added to and removed from the broadcast.
+	///
+	/// Each existing track is returned first as [TrackEvent::Added], followed by any changes.
+	pub fn track_events(&self) -> TrackEvents {
+		TrackEvents::new(self.state.clone(), self._dropped.clone())
+	}
+
+	/// Returns the metadata for the broadcast, which may be updated when a track is received from upstream.
+	pub fn metadata(&self) -> Metadata {
+		self.state.lock().metadata.clone()
 	}
 
 	/// Check if the broadcast is closed, either because the publisher was dropped or called [Publisher::close].
@@ -242,6 +460,120 @@ impl fmt::Debug for Subscriber {
 	}
 }
 
+/// A change to the tracks in a broadcast, see [Subscriber::track_events].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrackEvent {
+	/// A track was created or inserted, or a track requested by a subscriber was answered by the publisher.
+	Added(String),
+
+	/// A track was closed, or removed via [Publisher::remove_track].
+	Removed(String),
+}
+
+/// A stream of [TrackEvent]s for a broadcast.
+///
+/// This compares the tracks in the broadcast against the last seen set, so a track added and removed in quick succession may not be reported.
+pub struct TrackEvents {
+	state: Watch<State>,
+
+	// The tracks that have been returned as Added and not yet Removed.
+	known: HashSet<String>,
+
+	// Events that have been detected but not yet returned.
+	pending: VecDeque<TrackEvent>,
+
+	_dropped: Arc<Dropped>,
+}
+
+impl TrackEvents {
+	fn new(state: Watch<State>, _dropped: Arc<Dropped>) -> Self {
+		Self {
+			state,
+			known: HashSet::new(),
+			pending: VecDeque::new(),
+			_dropped,
+		}
+	}
+
+	/// Block until the next track is added or removed, returning None when the broadcast is closed.
+	pub async fn next(&mut self) -> Result<Option<TrackEvent>, CacheError> {
+		loop {
+			if let Some(event) = self.pending.pop_front() {
+				return Ok(Some(event));
+			}
+
+			let mut notify = {
+				let state = self.state.lock();
+
+				let mut removed: Vec<String> = self
+					.known
+					.iter()
+					.filter(|name| !state.tracks.get(*name).is_some_and(|track| track.is_live()))
+					.cloned()
+					.collect();
+
+				let mut added: Vec<String> = state
+					.tracks
+					.iter()
+					.filter(|(name, track)| !self.known.contains(*name) && track.is_live())
+					.map(|(name, _)| name.clone())
+					.collect();
+
+				// Sort so events are returned in a deterministic order.
+				removed.sort();
+				added.sort();
+
+				for name in removed {
+					self.known.remove(&name);
+					self.pending.push_back(TrackEvent::Removed(name));
+				}
+
+				for name in added {
+					self.known.insert(name.clone());
+					self.pending.push_back(TrackEvent::Added(name));
+				}
+
+				if !self.pending.is_empty() {
+					continue;
+				}
+
+				match &state.closed {
+					Err(CacheError::Closed) => return Ok(None),
+					Err(err) => return Err(err.clone()),
+					Ok(()) => {}
+				}
+
+				// Wait for the broadcast to change, or for any open track to be answered or closed.
+				let mut notify: Vec<Pin<Box<dyn Future<Output = ()> + Send>>> = state
+					.tracks
+					.values()
+					.map(|track| Box::pin(track.changed()) as Pin<Box<dyn Future<Output = ()> + Send>>)
+					.collect();
+
+				notify.push(Box::pin(state.changed()));
+				notify
+			};
+
+			future::poll_fn(
+				|cx| match notify.iter_mut().any(|notify| notify.as_mut().poll(cx).is_ready()) {
+					true => task::Poll::Ready(()),
+					false => task::Poll::Pending,
+				},
+			)
+			.await;
+		}
+	}
+}
+
+impl fmt::Debug for TrackEvents {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TrackEvents")
+			.field("known", &self.known)
+			.field("pending", &self.pending)
+			.finish()
+	}
+}
+
 // A handle that closes the broadcast when dropped:
 // - when all Subscribers are dropped or
 // - when Publisher and Unknown are dropped.
@@ -260,3 +592,94 @@ impl Drop for Dropped {
 		self.state.lock_mut().close(CacheError::Closed).ok();
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Fail instead of hanging if an event is missing.
+	async fn next(events: &mut TrackEvents) -> Option<TrackEvent> {
+		tokio::time::timeout(time::Duration::from_secs(1), events.next())
+			.await
+			.expect("timed out")
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn track_events() {
+		let (mut publisher, subscriber) = new("test");
+
+		let audio = publisher.create_track("audio").unwrap();
+		let mut events = subscriber.track_events();
+
+		// Existing tracks are returned first.
+		assert_eq!(next(&mut events).await, Some(TrackEvent::Added("audio".to_string())));
+
+		let _video = publisher.create_track("video").unwrap();
+		assert_eq!(next(&mut events).await, Some(TrackEvent::Added("video".to_string())));
+
+		// A closed track is removed.
+		audio.close(CacheError::Closed).unwrap();
+		assert_eq!(next(&mut events).await, Some(TrackEvent::Removed("audio".to_string())));
+
+		assert_eq!(subscriber.tracks(), vec!["video".to_string()]);
+
+		drop(publisher);
+		assert_eq!(next(&mut events).await, None);
+	}
+
+	#[tokio::test]
+	async fn track_events_requested() {
+		let (mut publisher, subscriber) = new("test");
+		let mut events = subscriber.track_events();
+
+		// A requested track isn't added until the publisher answers it.
+		let _missing = subscriber.get_track("missing").unwrap();
+		let _catalog = subscriber.get_track("catalog").unwrap();
+		assert!(subscriber.tracks().is_empty());
+
+		let missing = publisher.next_track().await.unwrap();
+		assert_eq!(missing.name, "missing");
+		missing.close(CacheError::NotFound).unwrap();
+
+		let mut catalog = publisher.next_track().await.unwrap();
+		assert_eq!(catalog.name, "catalog");
+		catalog.set_metadata(Metadata::default());
+
+		// Only the answered track is reported.
+		assert_eq!(next(&mut events).await, Some(TrackEvent::Added("catalog".to_string())));
+		assert_eq!(subscriber.tracks(), vec!["catalog".to_string()]);
+	}
+
+	// Answers every request with a track that never gets any metadata.
+	#[derive(Default)]
+	struct Stalled {
+		tracks: std::sync::Mutex<Vec<track::Publisher>>,
+	}
+
+	#[async_trait::async_trait]
+	impl Handler for Stalled {
+		async fn request(&self, name: &str, order: GroupOrder) -> Result<track::Subscriber, CacheError> {
+			let (publisher, subscriber) = track::requested(name, order);
+			self.tracks.lock().unwrap().push(publisher);
+			Ok(subscriber)
+		}
+	}
+
+	#[tokio::test]
+	async fn serve_timeout() {
+		let (mut publisher, subscriber) = new("test");
+		tokio::spawn(async move {
+			publisher
+				.serve(Stalled::default(), time::Duration::from_millis(10))
+				.await
+		});
+
+		let track = subscriber.get_track("stalled").unwrap();
+		let res = tokio::time::timeout(time::Duration::from_secs(1), track.metadata())
+			.await
+			.expect("timed out");
+
+		assert!(matches!(res, Err(CacheError::Timeout)));
+	}
+}
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::MoqError;
+use crate::{code, MoqError};
 
 #[derive(Clone, Debug, Error)]
 pub enum CacheError {
@@ -24,17 +24,27 @@ pub enum CacheError {
 	/// A resource already exists with that ID.
 	#[error("duplicate")]
 	Duplicate,
+
+	/// The publisher stopped before the segment or fragment was fully received.
+	#[error("truncated")]
+	Truncated,
+
+	/// The request was not answered in time.
+	#[error("timeout")]
+	Timeout,
 }
 
 impl MoqError for CacheError {
 	/// An integer code that is sent over the wire.
 	fn code(&self) -> u32 {
 		match self {
-			Self::Closed => 0,
+			Self::Closed => code::CLOSED,
 			Self::Reset(code) => *code,
-			Self::Stop => 206,
-			Self::NotFound => 404,
-			Self::Duplicate => 409,
+			Self::Stop => code::STOP,
+			Self::NotFound => code::NOT_FOUND,
+			Self::Duplicate => code::CONFLICT,
+			Self::Timeout => code::TIMEOUT,
+			Self::Truncated => code::TRUNCATED,
 		}
 	}
 
@@ -46,6 +56,8 @@ impl MoqError for CacheError {
 			Self::Stop => "stop".to_owned(),
 			Self::NotFound => "not found".to_owned(),
 			Self::Duplicate => "duplicate".to_owned(),
+			Self::Truncated => "truncated".to_owned(),
+			Self::Timeout => "timeout".to_owned(),
 		}
 	}
 }
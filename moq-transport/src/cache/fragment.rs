@@ -8,17 +8,21 @@
 //! You can clone the [Subscriber] and each will read a copy of of all future chunks. (fanout)
 //!
 //! The fragment is closed with [CacheError::Closed] when all publishers or subscribers are dropped.
+//! If the fragment has a known size but is closed before receiving that many bytes, it's instead closed with [CacheError::Truncated].
 use core::fmt;
 use std::{ops::Deref, sync::Arc};
 
-use crate::VarInt;
+use crate::{message::ObjectExtensions, VarInt};
 use bytes::Bytes;
 
 use super::{CacheError, Watch};
 
 /// Create a new segment with the given info.
 pub fn new(info: Info) -> (Publisher, Subscriber) {
-	let state = Watch::new(State::default());
+	let state = Watch::new(State {
+		expected: info.size,
+		..Default::default()
+	});
 	let info = Arc::new(info);
 
 	let publisher = Publisher::new(state.clone(), info.clone());
@@ -37,12 +41,21 @@ pub struct Info {
 	// The size of the fragment, optionally None if this is the last fragment in a segment.
 	// TODO enforce this size.
 	pub size: Option<usize>,
+
+	// Metadata about the fragment, forwarded unchanged by relays.
+	pub extensions: ObjectExtensions,
 }
 
 struct State {
 	// The data that has been received thus far.
 	chunks: Vec<Bytes>,
 
+	// The number of bytes received thus far.
+	received: usize,
+
+	// The number of bytes expected, if known.
+	expected: Option<usize>,
+
 	// Set when the publisher is dropped.
 	closed: Result<(), CacheError>,
 }
@@ -50,7 +63,13 @@ struct State {
 impl State {
 	pub fn close(&mut self, err: CacheError) -> Result<(), CacheError> {
 		self.closed.clone()?;
-		self.closed = Err(err);
+
+		// A clean close before receiving the expected size means the fragment was cut short.
+		self.closed = match err {
+			CacheError::Closed if self.expected.is_some_and(|size| self.received < size) => Err(CacheError::Truncated),
+			err => Err(err),
+		};
+
 		Ok(())
 	}
 }
@@ -59,6 +78,8 @@ impl Default for State {
 	fn default() -> Self {
 		Self {
 			chunks: Vec::new(),
+			received: 0,
+			expected: None,
 			closed: Ok(()),
 		}
 	}
@@ -67,7 +88,11 @@ impl Default for State {
 impl fmt::Debug for State {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		// We don't want to print out the contents, so summarize.
-		f.debug_struct("State").field("closed", &self.closed).finish()
+		f.debug_struct("State")
+			.field("received", &self.received)
+			.field("expected", &self.expected)
+			.field("closed", &self.closed)
+			.finish()
 	}
 }
 
@@ -93,6 +118,7 @@ impl Publisher {
 	pub fn chunk(&mut self, chunk: Bytes) -> Result<(), CacheError> {
 		let mut state = self.state.lock_mut();
 		state.closed.clone()?;
+		state.received += chunk.len();
 		state.chunks.push(chunk);
 		Ok(())
 	}
@@ -170,6 +196,16 @@ impl Subscriber {
 			notify.await; // Try again when the state changes
 		}
 	}
+
+	/// The number of bytes received thus far, which may be less than [Info::size] if the fragment was truncated.
+	pub fn received(&self) -> usize {
+		self.state.lock().received
+	}
+
+	/// Check if the fragment is closed, either cleanly with [CacheError::Closed] or because it was [CacheError::Truncated].
+	pub fn closed_err(&self) -> Option<CacheError> {
+		self.state.lock().closed.as_ref().err().cloned()
+	}
 }
 
 impl Deref for Subscriber {
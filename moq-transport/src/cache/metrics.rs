@@ -0,0 +1,48 @@
+//! Counters for the cache, so applications can export them as metrics.
+//!
+//! Pass a [Metrics] handle to [Publisher::set_metrics](super::broadcast::Publisher::set_metrics) to count the segments of each track in a broadcast.
+//! The same handle can be shared by any number of broadcasts, and the counters only ever increase.
+use std::sync::{
+	atomic::{AtomicU64, Ordering},
+	Arc,
+};
+
+/// A handle to the cache counters, see [Metrics::snapshot].
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+	inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+	segments_cached: AtomicU64,
+	segments_evicted: AtomicU64,
+}
+
+/// A snapshot of the cache counters, see [Metrics::snapshot].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Counters {
+	/// The number of segments inserted into a track.
+	pub segments_cached: u64,
+
+	/// The number of segments removed from a track after they expired or were replaced.
+	pub segments_evicted: u64,
+}
+
+impl Metrics {
+	/// Returns the current value of each counter.
+	pub fn snapshot(&self) -> Counters {
+		Counters {
+			segments_cached: self.inner.segments_cached.load(Ordering::Relaxed),
+			segments_evicted: self.inner.segments_evicted.load(Ordering::Relaxed),
+		}
+	}
+
+	pub(crate) fn segment_cached(&self) {
+		self.inner.segments_cached.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub(crate) fn segment_evicted(&self) {
+		self.inner.segments_evicted.fetch_add(1, Ordering::Relaxed);
+	}
+}
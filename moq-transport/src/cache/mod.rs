@@ -12,6 +12,7 @@
 pub mod broadcast;
 mod error;
 pub mod fragment;
+pub mod metrics;
 pub mod segment;
 pub mod track;
 
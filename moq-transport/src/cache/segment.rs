@@ -7,10 +7,12 @@
 //! The subscriber can be cloned, in which case each subscriber receives a copy of each fragment. (fanout)
 //!
 //! The segment is closed with [CacheError::Closed] when all publishers or subscribers are dropped.
+//! A publisher that knows the segment is incomplete, ex. the upstream stream was reset, should close it with [CacheError::Truncated] instead.
+//! Use [Subscriber::progress] to compare the number of fragments and bytes received against the number expected.
 use core::fmt;
 use std::{ops::Deref, sync::Arc, time};
 
-use crate::VarInt;
+use crate::{message::ObjectExtensions, VarInt};
 
 use super::{fragment, CacheError, Watch};
 
@@ -37,12 +39,38 @@ pub struct Info {
 
 	// Cache the segment for at most this long.
 	pub expires: Option<time::Duration>,
+
+	// Metadata that applies to the entire segment, sent with the first fragment.
+	// Any extensions set on the first fragment take precedence.
+	pub extensions: ObjectExtensions,
+}
+
+/// How much of a segment has been received, returned by [Subscriber::progress].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+	/// The number of fragments received thus far.
+	pub fragments: usize,
+
+	/// The number of fragments expected, known once the final fragment was created or the segment was closed.
+	pub expected_fragments: Option<usize>,
+
+	/// The number of bytes received thus far, across all fragments.
+	pub bytes: usize,
+
+	/// The number of bytes expected, known once the size of every fragment is known.
+	pub expected_bytes: Option<usize>,
+
+	/// True if the segment and all fragments were closed cleanly without being truncated.
+	pub complete: bool,
 }
 
 struct State {
 	// The data that has been received thus far.
 	fragments: Vec<fragment::Subscriber>,
 
+	// The number of fragments, set when the final fragment is created.
+	expected: Option<usize>,
+
 	// Set when the publisher is dropped.
 	closed: Result<(), CacheError>,
 }
@@ -59,6 +87,7 @@ impl Default for State {
 	fn default() -> Self {
 		Self {
 			fragments: Vec::new(),
+			expected: None,
 			closed: Ok(()),
 		}
 	}
@@ -68,6 +97,7 @@ impl fmt::Debug for State {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.debug_struct("State")
 			.field("fragments", &self.fragments)
+			.field("expected", &self.expected)
 			.field("closed", &self.closed)
 			.finish()
 	}
@@ -96,23 +126,53 @@ impl Publisher {
 		&mut self,
 		sequence: VarInt,
 		size: Option<usize>,
+		extensions: ObjectExtensions,
 	) -> Result<fragment::Publisher, CacheError> {
-		let (publisher, subscriber) = fragment::new(fragment::Info { sequence, size });
+		let (publisher, subscriber) = fragment::new(fragment::Info {
+			sequence,
+			size,
+			extensions,
+		});
 
 		let mut state = self.state.lock_mut();
 		state.closed.clone()?;
 		state.fragments.push(subscriber);
+
+		// A fragment without a size must be the last one.
+		if size.is_none() {
+			state.expected = Some(state.fragments.len());
+		}
+
 		Ok(publisher)
 	}
 
 	/// Write a fragment
 	pub fn fragment(&mut self, sequence: VarInt, size: usize) -> Result<fragment::Publisher, CacheError> {
-		self.push_fragment(sequence, Some(size))
+		self.push_fragment(sequence, Some(size), Default::default())
+	}
+
+	/// Write a fragment with the given metadata.
+	pub fn fragment_with(
+		&mut self,
+		sequence: VarInt,
+		size: usize,
+		extensions: ObjectExtensions,
+	) -> Result<fragment::Publisher, CacheError> {
+		self.push_fragment(sequence, Some(size), extensions)
 	}
 
 	/// Write the last fragment, which means size can be unknown.
 	pub fn final_fragment(mut self, sequence: VarInt) -> Result<fragment::Publisher, CacheError> {
-		self.push_fragment(sequence, None)
+		self.push_fragment(sequence, None, Default::default())
+	}
+
+	/// Write the last fragment with the given metadata, which means size can be unknown.
+	pub fn final_fragment_with(
+		mut self,
+		sequence: VarInt,
+		extensions: ObjectExtensions,
+	) -> Result<fragment::Publisher, CacheError> {
+		self.push_fragment(sequence, None, extensions)
 	}
 
 	/// Close the segment with an error.
@@ -188,6 +248,61 @@ impl Subscriber {
 			notify.await; // Try again when the state changes
 		}
 	}
+
+	/// Return how much of the segment has been received, and whether it's complete.
+	pub fn progress(&self) -> Progress {
+		let state = self.state.lock();
+		let closed = state.closed.as_ref().err();
+
+		let mut progress = Progress {
+			fragments: state.fragments.len(),
+			expected_fragments: match closed {
+				Some(CacheError::Closed) => Some(state.fragments.len()),
+				_ => state.expected,
+			},
+			bytes: 0,
+			expected_bytes: Some(0),
+			complete: matches!(closed, Some(CacheError::Closed)),
+		};
+
+		for fragment in &state.fragments {
+			let received = fragment.received();
+			let closed = fragment.closed_err();
+
+			// The size of the final fragment is only known once it's closed cleanly.
+			let expected = match closed {
+				Some(CacheError::Closed) => Some(fragment.size.unwrap_or(received)),
+				_ => fragment.size,
+			};
+
+			progress.bytes += received;
+			progress.expected_bytes = progress.expected_bytes.zip(expected).map(|(total, size)| total + size);
+			progress.complete &= matches!(closed, Some(CacheError::Closed));
+		}
+
+		progress
+	}
+
+	/// Check if the segment is closed, either cleanly with [CacheError::Closed] or because it was [CacheError::Truncated].
+	pub fn closed_err(&self) -> Option<CacheError> {
+		self.state.lock().closed.as_ref().err().cloned()
+	}
+
+	// Wait until the segment is closed, see Self::closed_err.
+	pub(crate) async fn closed(&self) -> CacheError {
+		loop {
+			let notify = {
+				let state = self.state.lock();
+				if let Err(err) = &state.closed {
+					return err.clone();
+				}
+
+				state.changed()
+			};
+
+			notify.await;
+		}
+	}
 }
 
 impl Deref for Subscriber {
@@ -224,3 +339,80 @@ impl Drop for Dropped {
 		self.state.lock_mut().close(CacheError::Closed).ok();
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use bytes::Bytes;
+
+	use super::*;
+
+	fn segment() -> (Publisher, Subscriber) {
+		new(Info {
+			sequence: VarInt::ZERO,
+			priority: 0,
+			expires: None,
+			extensions: Default::default(),
+		})
+	}
+
+	#[test]
+	fn progress_complete() {
+		let (mut publisher, subscriber) = segment();
+
+		let mut fragment = publisher.fragment(VarInt::from_u32(0), 5).unwrap();
+		fragment.chunk(Bytes::from_static(b"hello")).unwrap();
+		drop(fragment);
+
+		let progress = subscriber.progress();
+		assert_eq!(progress.fragments, 1);
+		assert_eq!(progress.expected_fragments, None);
+		assert_eq!(progress.bytes, 5);
+		assert!(!progress.complete);
+
+		// The size of the final fragment is unknown until it's closed.
+		let mut fragment = publisher.final_fragment(VarInt::from_u32(1)).unwrap();
+		fragment.chunk(Bytes::from_static(b"world!")).unwrap();
+
+		let progress = subscriber.progress();
+		assert_eq!(progress.expected_fragments, Some(2));
+		assert_eq!(progress.expected_bytes, None);
+		assert!(!progress.complete);
+
+		drop(fragment);
+
+		assert_eq!(
+			subscriber.progress(),
+			Progress {
+				fragments: 2,
+				expected_fragments: Some(2),
+				bytes: 11,
+				expected_bytes: Some(11),
+				complete: true,
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn truncated() {
+		let (mut publisher, mut subscriber) = segment();
+
+		// The fragment ends before the expected size, so it's truncated.
+		let mut fragment = publisher.fragment(VarInt::from_u32(0), 10).unwrap();
+		fragment.chunk(Bytes::from_static(b"hello")).unwrap();
+		drop(fragment);
+
+		publisher.close(CacheError::Truncated).unwrap();
+
+		let progress = subscriber.progress();
+		assert_eq!(progress.bytes, 5);
+		assert_eq!(progress.expected_bytes, Some(10));
+		assert_eq!(progress.expected_fragments, None);
+		assert!(!progress.complete);
+
+		let fragment = subscriber.fragment().await.unwrap().unwrap();
+		assert!(matches!(fragment.closed_err(), Some(CacheError::Truncated)));
+
+		assert!(matches!(subscriber.fragment().await, Err(CacheError::Truncated)));
+		assert!(matches!(subscriber.closed_err(), Some(CacheError::Truncated)));
+	}
+}
@@ -4,25 +4,63 @@
 //! The sequest number is used to determine the order of segments, while the priority is used to determine which segment to transmit first.
 //! This may seem counter-intuitive, but is designed for live streaming where the newest segments may be higher priority.
 //! A cloned [Publisher] can be used to create segments in parallel, but will error if a duplicate sequence number is used.
+//! Alternatively, [Publisher::add_source] lets a track be fed by redundant sources, keeping the first complete copy of each segment.
 //!
 //! A [Subscriber] may not receive all segments in order or at all.
 //! These segments are meant to be transmitted over congested networks and the key to MoQ Tranport is to not block on them.
 //! Segments will be cached for a potentially limited duration added to the unreliable nature.
 //! A cloned [Subscriber] will receive a copy of all new segment going forward (fanout).
+//! When multiple segments are available, they're returned based on the [GroupOrder] of the [Subscriber].
+//!
+//! Each track has [Metadata], such as the codec, which is forwarded to subscribers in SUBSCRIBE_OK.
+//! It may not be known until the upstream SUBSCRIBE_OK arrives, so [Subscriber::metadata] waits until it's available.
 //!
 //! The track is closed with [CacheError::Closed] when all publishers or subscribers are dropped.
 
-use std::{collections::BinaryHeap, fmt, ops::Deref, sync::Arc, time};
-
-use indexmap::IndexMap;
-
-use super::{segment, CacheError, Watch};
-use crate::VarInt;
+use std::{
+	collections::{BinaryHeap, HashMap, VecDeque},
+	fmt,
+	future::Future,
+	ops::Deref,
+	sync::{atomic, Arc},
+	time,
+};
+
+use super::{metrics::Metrics, segment, CacheError, Watch};
+use crate::{
+	message::{GroupOrder, Metadata},
+	VarInt,
+};
 
 /// Create a track with the given name.
 pub fn new(name: &str) -> (Publisher, Subscriber) {
-	let state = Watch::new(State::default());
-	let info = Arc::new(Info { name: name.to_string() });
+	new_ordered(name, GroupOrder::default())
+}
+
+/// Create a track with the given name, requesting that segments are delivered in the given order.
+pub fn new_ordered(name: &str, order: GroupOrder) -> (Publisher, Subscriber) {
+	create(name, order, Some(Metadata::default()))
+}
+
+/// Create a track with the given name and metadata, which is available to subscribers immediately.
+pub fn new_with_metadata(name: &str, metadata: Metadata) -> (Publisher, Subscriber) {
+	create(name, GroupOrder::default(), Some(metadata))
+}
+
+// Create a track where the metadata is not known until the publisher answers the request.
+pub(crate) fn requested(name: &str, order: GroupOrder) -> (Publisher, Subscriber) {
+	create(name, order, None)
+}
+
+fn create(name: &str, order: GroupOrder, metadata: Option<Metadata>) -> (Publisher, Subscriber) {
+	let state = Watch::new(State {
+		metadata,
+		..Default::default()
+	});
+	let info = Arc::new(Info {
+		name: name.to_string(),
+		order,
+	});
 
 	let publisher = Publisher::new(state.clone(), info.clone());
 	let subscriber = Subscriber::new(state, info);
@@ -34,13 +72,18 @@ pub fn new(name: &str) -> (Publisher, Subscriber) {
 #[derive(Debug)]
 pub struct Info {
 	pub name: String,
+
+	/// The order requested by whoever created the track, used by default for each [Subscriber].
+	pub order: GroupOrder,
 }
 
 struct State {
 	// Store segments in received order so subscribers can detect changes.
-	// The key is the segment sequence, which could have gaps.
-	// A None value means the segment has expired.
-	lookup: IndexMap<VarInt, Option<segment::Subscriber>>,
+	// The sequence could have gaps, and a None value means the segment has expired or was replaced.
+	lookup: VecDeque<(VarInt, Option<segment::Subscriber>)>,
+
+	// The index of each segment in the lookup, counting pruned entries.
+	sequences: HashMap<VarInt, usize>,
 
 	// Store when segments will expire in a priority queue.
 	expires: BinaryHeap<SegmentExpiration>,
@@ -48,8 +91,26 @@ struct State {
 	// The number of None entries removed from the start of the lookup.
 	pruned: usize,
 
+	// The largest sequence ever inserted, even if it has since expired.
+	latest: Option<VarInt>,
+
 	// Set when the publisher is closed/dropped, or all subscribers are dropped.
 	closed: Result<(), CacheError>,
+
+	// If true, duplicate segments are merged instead of returning an error.
+	merge: bool,
+
+	// A duplicate copy of each segment that's still in progress, used if the first copy is truncated.
+	standby: HashMap<VarInt, segment::Subscriber>,
+
+	// The number of sources still feeding the track, see Publisher::add_source.
+	sources: usize,
+
+	// Set by the publisher, or None if it's not known yet.
+	metadata: Option<Metadata>,
+
+	// Counts cached and evicted segments, if set by the broadcast.
+	metrics: Option<Metrics>,
 }
 
 impl State {
@@ -62,28 +123,75 @@ impl State {
 	pub fn insert(&mut self, segment: segment::Subscriber) -> Result<(), CacheError> {
 		self.closed.clone()?;
 
-		let entry = match self.lookup.entry(segment.sequence) {
-			indexmap::map::Entry::Occupied(_entry) => return Err(CacheError::Duplicate),
-			indexmap::map::Entry::Vacant(entry) => entry,
-		};
+		match self.sequences.get(&segment.sequence).copied() {
+			None => self.push(segment),
+			Some(_) if !self.merge => return Err(CacheError::Duplicate),
+			Some(index) => match self.get(index) {
+				// Replace a truncated copy immediately.
+				Some(existing) if truncated(existing) => self.replace(index, segment),
 
-		if let Some(expires) = segment.expires {
-			self.expires.push(SegmentExpiration {
-				sequence: segment.sequence,
-				expires: time::Instant::now() + expires,
-			});
+				// Keep the duplicate in case the first copy is truncated later.
+				Some(existing) if !existing.progress().complete => {
+					self.standby.insert(segment.sequence, segment);
+				}
+
+				// The first copy is complete or expired, so the duplicate isn't needed.
+				_ => {}
+			},
 		}
 
-		entry.insert(Some(segment));
+		// The publisher didn't provide any metadata before the first segment, so it never will.
+		self.metadata.get_or_insert_with(Default::default);
 
 		// Expire any existing segments on insert.
 		// This means if you don't insert then you won't expire... but it's probably fine since the cache won't grow.
 		// TODO Use a timer to expire segments at the correct time instead
 		self.expire();
+		self.promote();
 
 		Ok(())
 	}
 
+	// Return the latest copy of the segment at the given index, unless it expired.
+	fn get(&self, index: usize) -> Option<&segment::Subscriber> {
+		let index = index.checked_sub(self.pruned)?;
+		self.lookup.get(index)?.1.as_ref()
+	}
+
+	// Append a new segment to the lookup.
+	fn push(&mut self, segment: segment::Subscriber) {
+		let index = self.pruned + self.lookup.len();
+		self.sequences.insert(segment.sequence, index);
+		self.latest = self.latest.max(Some(segment.sequence));
+
+		if let Some(expires) = segment.expires {
+			self.expires.push(SegmentExpiration {
+				index,
+				expires: time::Instant::now() + expires,
+			});
+		}
+
+		self.lookup.push_back((segment.sequence, Some(segment)));
+		if let Some(metrics) = &self.metrics {
+			metrics.segment_cached();
+		}
+	}
+
+	// Replace the copy of a segment in place, keeping its expiration.
+	// Subscribers that already read the old copy won't receive the segment again, as they're past its index.
+	fn replace(&mut self, index: usize, segment: segment::Subscriber) {
+		let existing = &mut self.lookup[index - self.pruned].1;
+		if existing.replace(segment).is_some() {
+			if let Some(metrics) = &self.metrics {
+				metrics.segment_evicted();
+			}
+		}
+
+		if let Some(metrics) = &self.metrics {
+			metrics.segment_cached();
+		}
+	}
+
 	// Try expiring any segments
 	pub fn expire(&mut self) {
 		let now = time::Instant::now();
@@ -93,29 +201,71 @@ impl State {
 			}
 
 			// Update the entry to None while preserving the index.
-			match self.lookup.entry(segment.sequence) {
-				indexmap::map::Entry::Occupied(mut entry) => entry.insert(None),
-				indexmap::map::Entry::Vacant(_) => panic!("expired segment not found"),
-			};
+			if let Some(index) = segment.index.checked_sub(self.pruned) {
+				if self.lookup[index].1.take().is_some() {
+					if let Some(metrics) = &self.metrics {
+						metrics.segment_evicted();
+					}
+				}
+			}
 
 			self.expires.pop();
 		}
 
 		// Remove None entries from the start of the lookup.
-		while let Some((_, None)) = self.lookup.get_index(0) {
-			self.lookup.shift_remove_index(0);
+		while let Some((sequence, None)) = self.lookup.front() {
+			if self.sequences.get(sequence) == Some(&self.pruned) {
+				self.sequences.remove(sequence);
+			}
+
+			self.lookup.pop_front();
 			self.pruned += 1;
 		}
 	}
+
+	// Replace any truncated segments with a copy from another source, dropping copies that are no longer needed.
+	fn promote(&mut self) {
+		for (sequence, segment) in std::mem::take(&mut self.standby) {
+			let index = match self.sequences.get(&sequence) {
+				Some(index) => *index,
+				None => continue,
+			};
+
+			let existing = match self.get(index) {
+				Some(existing) => existing,
+				None => continue,
+			};
+
+			if truncated(existing) {
+				if !truncated(&segment) {
+					self.replace(index, segment);
+				}
+			} else if !existing.progress().complete {
+				self.standby.insert(sequence, segment);
+			}
+		}
+	}
+}
+
+// Returns true if the segment was closed with an error, rather than still in progress or complete.
+fn truncated(segment: &segment::Subscriber) -> bool {
+	matches!(segment.closed_err(), Some(err) if !matches!(err, CacheError::Closed))
 }
 
 impl Default for State {
 	fn default() -> Self {
 		Self {
 			lookup: Default::default(),
+			sequences: Default::default(),
 			expires: Default::default(),
 			pruned: 0,
+			latest: None,
 			closed: Ok(()),
+			merge: false,
+			standby: Default::default(),
+			sources: 0,
+			metadata: None,
+			metrics: None,
 		}
 	}
 }
@@ -126,38 +276,148 @@ impl fmt::Debug for State {
 			.field("lookup", &self.lookup)
 			.field("pruned", &self.pruned)
 			.field("closed", &self.closed)
+			.field("merge", &self.merge)
+			.field("sources", &self.sources)
+			.field("metadata", &self.metadata)
 			.finish()
 	}
 }
 
 /// Creates new segments for a track.
+///
+/// This can be cloned to insert segments from multiple sources.
+#[derive(Clone)]
 pub struct Publisher {
 	state: Watch<State>,
 	info: Arc<Info>,
+
+	// Set if this handle was returned by add_source.
+	source: Option<Arc<Source>>,
+
 	_dropped: Arc<Dropped>,
 }
 
 impl Publisher {
 	fn new(state: Watch<State>, info: Arc<Info>) -> Self {
 		let _dropped = Arc::new(Dropped::new(state.clone()));
-		Self { state, info, _dropped }
+		Self {
+			state,
+			info,
+			source: None,
+			_dropped,
+		}
 	}
 
 	/// Insert a new segment.
 	pub fn insert_segment(&mut self, segment: segment::Subscriber) -> Result<(), CacheError> {
-		self.state.lock_mut().insert(segment)
+		let sequence = segment.sequence;
+
+		let mut state = self.state.lock_mut();
+		let watching = state.standby.contains_key(&sequence);
+		state.insert(segment)?;
+
+		if watching || !state.standby.contains_key(&sequence) {
+			return Ok(());
+		}
+
+		// A duplicate is on standby, so replace the first copy as soon as it's truncated.
+		// Otherwise the final segment, or one before a pause, would stay truncated until another segment is inserted.
+		let existing = match state.sequences.get(&sequence).and_then(|index| state.get(*index)) {
+			Some(existing) => existing.clone(),
+			None => return Ok(()),
+		};
+
+		let track = self.state.clone();
+		tokio::spawn(async move {
+			existing.closed().await;
+			track.lock_mut().promote();
+		});
+
+		Ok(())
+	}
+
+	/// Merge segments with a duplicate sequence number instead of returning [CacheError::Duplicate].
+	///
+	/// The first copy of each segment is kept unless it's truncated, in which case it's replaced by a duplicate from another source.
+	/// Any gaps are filled by whichever source delivers first.
+	pub fn set_merge(&mut self, merge: bool) {
+		self.state.lock_mut().merge = merge;
+	}
+
+	/// Returns the largest sequence number inserted so far, including any segments that have expired.
+	pub fn latest(&self) -> Option<VarInt> {
+		self.state.lock().latest
+	}
+
+	/// Return a handle for another source feeding the same track, such as a second upstream session carrying the same broadcast.
+	///
+	/// This enables [Self::set_merge], and the track is only closed once every source has been closed or dropped.
+	/// The error from the last source is used to close the track.
+	pub fn add_source(&self) -> Self {
+		let mut state = self.state.lock_mut();
+		state.merge = true;
+		state.sources += 1;
+
+		Self {
+			source: Some(Arc::new(Source::new(self.state.clone()))),
+			..self.clone()
+		}
+	}
+
+	// Count the segments in this track, see Publisher::set_metrics in the broadcast.
+	pub(crate) fn set_metrics(&mut self, metrics: Option<Metrics>) {
+		self.state.lock_mut().metrics = metrics;
+	}
+
+	/// Set the metadata for the track, which is sent to any new subscribers.
+	pub fn set_metadata(&mut self, metadata: Metadata) {
+		self.state.lock_mut().metadata = Some(metadata);
 	}
 
 	/// Create an insert a segment with the given info.
-	pub fn create_segment(&mut self, info: segment::Info) -> Result<segment::Publisher, CacheError> {
+	///
+	/// If the info doesn't set an expiration, the default from the track [Metadata] is used.
+	pub fn create_segment(&mut self, mut info: segment::Info) -> Result<segment::Publisher, CacheError> {
+		if info.expires.is_none() {
+			info.expires = self
+				.state
+				.lock()
+				.metadata
+				.as_ref()
+				.and_then(|metadata| metadata.expires);
+		}
+
 		let (publisher, subscriber) = segment::new(info);
 		self.insert_segment(subscriber)?;
 		Ok(publisher)
 	}
 
+	/// Create and insert a segment using the default priority and expiration from the track [Metadata].
+	pub fn create_default_segment(&mut self, sequence: VarInt) -> Result<segment::Publisher, CacheError> {
+		let priority = self
+			.state
+			.lock()
+			.metadata
+			.as_ref()
+			.and_then(|metadata| metadata.priority)
+			.unwrap_or_default();
+
+		self.create_segment(segment::Info {
+			sequence,
+			priority,
+			expires: None,
+			extensions: Default::default(),
+		})
+	}
+
 	/// Close the segment with an error.
+	///
+	/// If this handle was returned by [Self::add_source], the track stays open until the last source is closed.
 	pub fn close(self, err: CacheError) -> Result<(), CacheError> {
-		self.state.lock_mut().close(err)
+		match &self.source {
+			Some(source) => source.close(err),
+			None => self.state.lock_mut().close(err),
+		}
 	}
 }
 
@@ -187,9 +447,12 @@ pub struct Subscriber {
 	// The index of the next segment to return.
 	index: usize,
 
-	// If there are multiple segments to return, we put them in here to return them in priority order.
+	// If there are multiple segments to return, we put them in here to return them in the desired order.
 	pending: BinaryHeap<SegmentPriority>,
 
+	// The order used to return pending segments.
+	order: GroupOrder,
+
 	// Dropped when all subscribers are dropped.
 	_dropped: Arc<Dropped>,
 }
@@ -197,15 +460,56 @@ pub struct Subscriber {
 impl Subscriber {
 	fn new(state: Watch<State>, info: Arc<Info>) -> Self {
 		let _dropped = Arc::new(Dropped::new(state.clone()));
+		let order = info.order;
+
 		Self {
 			state,
 			info,
 			index: 0,
 			pending: Default::default(),
+			order,
 			_dropped,
 		}
 	}
 
+	/// Change the order in which segments are returned, defaulting to the order in [Info].
+	pub fn set_order(&mut self, order: GroupOrder) {
+		self.order = order;
+		self.pending = self
+			.pending
+			.drain()
+			.map(|pending| SegmentPriority(pending.0, order))
+			.collect();
+	}
+
+	/// Block until the metadata is known, which may require waiting for the publisher to answer the request.
+	pub async fn metadata(&self) -> Result<Metadata, CacheError> {
+		loop {
+			let notify = {
+				let state = self.state.lock();
+				if let Some(metadata) = &state.metadata {
+					return Ok(metadata.clone());
+				}
+
+				state.closed.clone()?;
+				state.changed()
+			};
+
+			notify.await
+		}
+	}
+
+	// Returns true if the publisher answered the request and the track isn't closed.
+	pub(crate) fn is_live(&self) -> bool {
+		let state = self.state.lock();
+		state.metadata.is_some() && state.closed.is_ok()
+	}
+
+	// Wait until the track changes, ex. it's answered or closed, or a segment is inserted.
+	pub(crate) fn changed(&self) -> impl Future<Output = ()> + Send + 'static {
+		self.state.lock().changed()
+	}
+
 	/// Block until the next segment arrives
 	pub async fn segment(&mut self) -> Result<Option<segment::Subscriber>, CacheError> {
 		loop {
@@ -217,12 +521,12 @@ impl Subscriber {
 
 				// Push all new segments into a priority queue.
 				while index < state.lookup.len() {
-					let (_, segment) = state.lookup.get_index(index).unwrap();
+					let (_, segment) = &state.lookup[index];
 
 					// Skip None values (expired segments).
 					// TODO These might actually be expired, so we should check the expiration time.
 					if let Some(segment) = segment {
-						self.pending.push(SegmentPriority(segment.clone()));
+						self.pending.push(SegmentPriority(segment.clone(), self.order));
 					}
 
 					index += 1;
@@ -230,7 +534,7 @@ impl Subscriber {
 
 				self.index = state.pruned + index;
 
-				// Return the higher priority segment.
+				// Return the next segment based on the order.
 				if let Some(segment) = self.pending.pop() {
 					return Ok(Some(segment.0));
 				}
@@ -262,6 +566,7 @@ impl fmt::Debug for Subscriber {
 			.field("state", &self.state)
 			.field("info", &self.info)
 			.field("index", &self.index)
+			.field("order", &self.order)
 			.finish()
 	}
 }
@@ -283,9 +588,44 @@ impl Drop for Dropped {
 	}
 }
 
+// Counts a source feeding the track, closing the track when the last one is closed or dropped.
+struct Source {
+	state: Watch<State>,
+	closed: atomic::AtomicBool,
+}
+
+impl Source {
+	fn new(state: Watch<State>) -> Self {
+		Self {
+			state,
+			closed: atomic::AtomicBool::new(false),
+		}
+	}
+
+	fn close(&self, err: CacheError) -> Result<(), CacheError> {
+		if self.closed.swap(true, atomic::Ordering::SeqCst) {
+			return Ok(());
+		}
+
+		let mut state = self.state.lock_mut();
+		state.sources -= 1;
+
+		match state.sources {
+			0 => state.close(err),
+			_ => Ok(()),
+		}
+	}
+}
+
+impl Drop for Source {
+	fn drop(&mut self) {
+		self.close(CacheError::Closed).ok();
+	}
+}
+
 // Used to order segments by expiration time.
 struct SegmentExpiration {
-	sequence: VarInt,
+	index: usize,
 	expires: time::Instant,
 }
 
@@ -310,15 +650,20 @@ impl PartialEq for SegmentExpiration {
 
 impl Eq for SegmentExpiration {}
 
-// Used to order segments by priority
+// Used to order segments by priority or sequence, depending on the GroupOrder.
 #[derive(Clone)]
-struct SegmentPriority(pub segment::Subscriber);
+struct SegmentPriority(pub segment::Subscriber, pub GroupOrder);
 
 impl Ord for SegmentPriority {
 	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-		// Reverse order so the highest priority is at the top of the heap.
-		// TODO I let CodePilot generate this code so yolo
-		other.0.priority.cmp(&self.0.priority)
+		// BinaryHeap is a max-heap, so the segment that should be returned first must compare as the greatest.
+		match self.1 {
+			// Reverse order so the smallest priority is at the top of the heap.
+			GroupOrder::Publisher => other.0.priority.cmp(&self.0.priority),
+			// Reverse order so the oldest segment is at the top of the heap.
+			GroupOrder::Ascending => other.0.sequence.cmp(&self.0.sequence),
+			GroupOrder::Descending => self.0.sequence.cmp(&other.0.sequence),
+		}
 	}
 }
 
@@ -330,8 +675,157 @@ impl PartialOrd for SegmentPriority {
 
 impl PartialEq for SegmentPriority {
 	fn eq(&self, other: &Self) -> bool {
-		self.0.priority == other.0.priority
+		self.cmp(other) == std::cmp::Ordering::Equal
 	}
 }
 
 impl Eq for SegmentPriority {}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Use the priority to tell apart copies of the same segment.
+	fn segment(sequence: u32, priority: u32) -> (segment::Publisher, segment::Subscriber) {
+		segment::new(segment::Info {
+			sequence: VarInt::from_u32(sequence),
+			priority,
+			expires: None,
+			extensions: Default::default(),
+		})
+	}
+
+	// Return the sequence and priority of each remaining segment, once the track is closed.
+	async fn drain(subscriber: &mut Subscriber) -> Vec<(u64, u32)> {
+		let mut segments = Vec::new();
+		while let Some(segment) = subscriber.segment().await.unwrap() {
+			segments.push((segment.sequence.into_inner(), segment.priority));
+		}
+
+		segments.sort();
+		segments
+	}
+
+	#[test]
+	fn duplicate() {
+		let (mut publisher, _subscriber) = new("test");
+
+		let (_first, first) = segment(0, 0);
+		let (_second, second) = segment(0, 1);
+
+		publisher.insert_segment(first).unwrap();
+		assert!(matches!(publisher.insert_segment(second), Err(CacheError::Duplicate)));
+	}
+
+	#[test]
+	fn latest() {
+		let (mut publisher, _subscriber) = new("test");
+		assert_eq!(publisher.latest(), None);
+
+		let (_first, first) = segment(3, 0);
+		let (_second, second) = segment(1, 0);
+
+		publisher.insert_segment(first).unwrap();
+		publisher.insert_segment(second).unwrap();
+		assert_eq!(publisher.latest(), Some(VarInt::from_u32(3)));
+	}
+
+	#[tokio::test]
+	async fn merge_replaces_truncated() {
+		let (mut publisher, mut subscriber) = new("test");
+		publisher.set_merge(true);
+
+		// Another subscriber that hasn't read anything yet.
+		let mut late = subscriber.clone();
+
+		let (first, copy) = segment(0, 1);
+		publisher.insert_segment(copy).unwrap();
+
+		let read = subscriber.segment().await.unwrap().unwrap();
+		assert_eq!(read.priority, 1);
+
+		first.close(CacheError::Truncated).unwrap();
+
+		// A complete copy from another source replaces the truncated one.
+		let (second, copy) = segment(0, 2);
+		second.close(CacheError::Closed).unwrap();
+		publisher.insert_segment(copy).unwrap();
+
+		publisher.close(CacheError::Closed).unwrap();
+
+		// The segment isn't delivered again to a subscriber that already read the truncated copy.
+		assert_eq!(drain(&mut subscriber).await, vec![]);
+		assert_eq!(drain(&mut late).await, vec![(0, 2)]);
+	}
+
+	#[tokio::test]
+	async fn merge_keeps_complete() {
+		let (mut publisher, mut subscriber) = new("test");
+		publisher.set_merge(true);
+
+		let (first, copy) = segment(0, 1);
+		publisher.insert_segment(copy).unwrap();
+		first.close(CacheError::Closed).unwrap();
+
+		// The first copy is complete, so the duplicate is ignored.
+		let (_second, copy) = segment(0, 2);
+		publisher.insert_segment(copy).unwrap();
+
+		publisher.close(CacheError::Closed).unwrap();
+
+		assert_eq!(drain(&mut subscriber).await, vec![(0, 1)]);
+	}
+
+	#[tokio::test]
+	async fn merge_standby() {
+		let (mut publisher, mut subscriber) = new("test");
+		publisher.set_merge(true);
+
+		// Both copies are in progress, so the second is kept on standby.
+		let (first, copy) = segment(0, 1);
+		publisher.insert_segment(copy).unwrap();
+
+		let (_second, copy) = segment(0, 2);
+		publisher.insert_segment(copy).unwrap();
+
+		// The first copy is truncated, so the standby replaces it without waiting for another segment.
+		let changed = subscriber.changed();
+		first.close(CacheError::Truncated).unwrap();
+		tokio::time::timeout(time::Duration::from_secs(1), changed)
+			.await
+			.expect("not promoted");
+
+		publisher.close(CacheError::Closed).unwrap();
+
+		// The truncated copy was replaced before it was read.
+		assert_eq!(drain(&mut subscriber).await, vec![(0, 2)]);
+	}
+
+	#[tokio::test]
+	async fn sources() {
+		let (publisher, mut subscriber) = new("test");
+
+		let mut first = publisher.add_source();
+		let second = publisher.add_source();
+		drop(publisher);
+
+		// Both sources deliver the same segment, and the duplicate is merged.
+		let (segment1, copy) = segment(0, 1);
+		first.insert_segment(copy).unwrap();
+		segment1.close(CacheError::Closed).unwrap();
+
+		let mut other = second.clone();
+		let (_segment2, copy) = segment(0, 2);
+		other.insert_segment(copy).unwrap();
+		drop(other);
+
+		// The track stays open until every source is closed.
+		first.close(CacheError::Truncated).unwrap();
+		assert!(subscriber.is_live());
+
+		second.close(CacheError::Closed).unwrap();
+		assert!(!subscriber.is_live());
+
+		assert_eq!(drain(&mut subscriber).await, vec![(0, 1)]);
+	}
+}
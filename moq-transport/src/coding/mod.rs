@@ -7,5 +7,4 @@ mod varint;
 pub use decode::*;
 pub use encode::*;
 pub use params::*;
-pub use string::*;
 pub use varint::*;
@@ -5,3 +5,65 @@ pub trait MoqError {
 	/// An optional reason sometimes sent over the wire.
 	fn reason(&self) -> String;
 }
+
+/// The error codes sent over the wire via [MoqError::code], loosely based on HTTP status codes.
+///
+/// Any other codes are application specific, ex. [CacheError::Reset](crate::cache::CacheError::Reset).
+pub mod code {
+	/// A clean termination.
+	pub const CLOSED: u32 = 0;
+
+	/// The subscriber is no longer interested.
+	pub const STOP: u32 = 206;
+
+	/// A field in a message had an invalid value, ex. the priority or size.
+	pub const BAD_REQUEST: u32 = 400;
+
+	/// The requested resource was not found.
+	pub const NOT_FOUND: u32 = 404;
+
+	/// A message was sent that isn't allowed for the negotiated role.
+	pub const ROLE_VIOLATION: u32 = 405;
+
+	/// The version or role was incompatible during the handshake.
+	pub const NOT_ACCEPTABLE: u32 = 406;
+
+	/// The request was not answered in time.
+	pub const TIMEOUT: u32 = 408;
+
+	/// A resource already exists with that ID, or the stream mapping was violated.
+	pub const CONFLICT: u32 = 409;
+
+	/// The publisher stopped before the segment or fragment was fully received.
+	pub const TRUNCATED: u32 = 410;
+
+	/// A value was too large to encode or decode.
+	pub const LIMIT_EXCEEDED: u32 = 413;
+
+	/// A message with an unknown type was received.
+	pub const UNKNOWN_MESSAGE: u32 = 415;
+
+	/// A message could not be decoded.
+	pub const MALFORMED: u32 = 422;
+
+	/// A required extension was not offered.
+	pub const REQUIRED_EXTENSION: u32 = 426;
+
+	/// An internal error, ex. a message could not be encoded.
+	pub const INTERNAL: u32 = 500;
+
+	/// Failed to write to a stream.
+	pub const WRITE: u32 = 501;
+
+	/// Failed to read from a stream.
+	pub const READ: u32 = 502;
+
+	/// The WebTransport session failed.
+	pub const SESSION: u32 = 503;
+
+	/// A stream was closed before a message was fully received.
+	pub const STREAM_CLOSED: u32 = 504;
+
+	/// An I/O error on a stream.
+	pub const IO: u32 = 505;
+}
@@ -10,9 +10,11 @@ mod coding;
 mod error;
 
 pub mod cache;
+#[cfg(feature = "logging")]
+pub mod logging;
 pub mod message;
 pub mod session;
 pub mod setup;
 
 pub use coding::VarInt;
-pub use error::MoqError;
+pub use error::{code, MoqError};
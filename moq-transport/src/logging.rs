@@ -0,0 +1,58 @@
+//! A shared [tracing] subscriber for binaries, enabled by the `logging` feature.
+//!
+//! Both [tracing] events and [log] records are captured, so each line includes the active spans.
+//! The filter is read from the `RUST_LOG` environment variable, supporting per-module directives like `info,moq_transport=debug`.
+//! When `RUST_LOG` is not set, noisy dependencies like Quinn are limited to warnings.
+use std::{fmt, str::FromStr};
+
+use tracing_subscriber::EnvFilter;
+
+// Used when RUST_LOG is not set.
+const DEFAULT_FILTER: &str = "info,quinn=warn,quinn_proto=warn,rustls=warn";
+
+/// The output format for each line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+	/// Human readable text.
+	#[default]
+	Text,
+
+	/// A JSON object per line, including the fields of each active span.
+	Json,
+}
+
+impl FromStr for Format {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"text" => Ok(Self::Text),
+			"json" => Ok(Self::Json),
+			_ => Err(format!("unknown log format: {}", s)),
+		}
+	}
+}
+
+impl fmt::Display for Format {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Text => write!(f, "text"),
+			Self::Json => write!(f, "json"),
+		}
+	}
+}
+
+/// Install the global subscriber, panicking if one was already installed.
+pub fn init(format: Format) {
+	let filter = match std::env::var(EnvFilter::DEFAULT_ENV) {
+		Ok(_) => EnvFilter::from_default_env(),
+		Err(_) => EnvFilter::new(DEFAULT_FILTER),
+	};
+
+	let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+	match format {
+		Format::Text => builder.init(),
+		Format::Json => builder.json().with_current_span(true).with_span_list(true).init(),
+	}
+}
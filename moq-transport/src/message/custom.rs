@@ -0,0 +1,125 @@
+use bytes::Bytes;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{DecodeError, EncodeError, VarInt};
+
+use super::Message;
+
+/// A control message with a type not defined by this crate, used to extend the protocol.
+///
+/// These can only be sent or received when the `control_framing` extension was negotiated.
+/// Received messages are skipped unless the type was registered with the session.
+#[derive(Clone, Debug)]
+pub struct Custom {
+	/// The message type.
+	pub id: VarInt,
+
+	/// The encoded message, without the type or size.
+	pub payload: Bytes,
+}
+
+impl Custom {
+	// The reader is limited to the size of the message.
+	pub async fn decode<R: AsyncRead>(id: VarInt, r: &mut R) -> Result<Self, DecodeError> {
+		let mut payload = Vec::new();
+		r.read_to_end(&mut payload).await?;
+
+		Ok(Self {
+			id,
+			payload: payload.into(),
+		})
+	}
+
+	pub async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		w.write_all(&self.payload).await?;
+		Ok(())
+	}
+}
+
+impl From<Custom> for Message {
+	fn from(m: Custom) -> Self {
+		Message::Custom(m)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io;
+
+	use super::*;
+	use crate::coding::Encode;
+	use crate::message::GoAway;
+	use crate::setup::Extensions;
+
+	fn framing() -> Extensions {
+		Extensions {
+			control_framing: true,
+			..Default::default()
+		}
+	}
+
+	#[tokio::test]
+	async fn encode_decode() {
+		let msg = Custom {
+			id: VarInt::from_u32(0x1234),
+			payload: Bytes::from_static(b"hello"),
+		};
+
+		assert!(!Message::is_known(msg.id));
+
+		let mut buf = Vec::new();
+		Message::from(msg.clone()).encode(&mut buf, &framing()).await.unwrap();
+
+		let decoded = match Message::decode(&mut io::Cursor::new(buf), &framing()).await.unwrap() {
+			Message::Custom(msg) => msg,
+			msg => panic!("unexpected message: {:?}", msg),
+		};
+
+		assert_eq!(decoded.id, msg.id);
+		assert_eq!(decoded.payload, msg.payload);
+	}
+
+	#[tokio::test]
+	async fn requires_framing() {
+		let msg = Custom {
+			id: VarInt::from_u32(0x1234),
+			payload: Bytes::from_static(b"hello"),
+		};
+
+		// Nothing is written, so the stream isn't corrupted.
+		let mut buf = Vec::new();
+		let res = Message::from(msg).encode(&mut buf, &Extensions::default()).await;
+
+		assert!(matches!(res, Err(EncodeError::InvalidValue)));
+		assert!(buf.is_empty());
+	}
+
+	#[tokio::test]
+	async fn skip_trailing() {
+		let msg = GoAway {
+			url: "https://example.com".to_string(),
+		};
+
+		// A newer version could add fields to a known message, which are skipped.
+		let mut body = Vec::new();
+		msg.encode(&mut body, &framing()).await.unwrap();
+		body.extend_from_slice(b"future");
+
+		let mut buf = Vec::new();
+		Message::from(msg.clone()).id().encode(&mut buf).await.unwrap();
+		VarInt::try_from(body.len()).unwrap().encode(&mut buf).await.unwrap();
+		buf.extend_from_slice(&body);
+
+		// Followed by another message, which is decoded normally.
+		Message::from(msg.clone()).encode(&mut buf, &framing()).await.unwrap();
+
+		let mut r = io::Cursor::new(buf);
+		for _ in 0..2 {
+			match Message::decode(&mut r, &framing()).await.unwrap() {
+				Message::GoAway(decoded) => assert_eq!(decoded.url, msg.url),
+				msg => panic!("unexpected message: {:?}", msg),
+			}
+		}
+	}
+}
@@ -0,0 +1,228 @@
+use std::time;
+
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
+
+/// Optional information about a track or broadcast, which relays forward unchanged.
+///
+/// This is sent in SUBSCRIBE_OK when the `subscribe_metadata` extension was negotiated.
+/// It's encoded as a list of parameters so unknown fields can be skipped.
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+	/// The default priority of each segment, where **smaller** values are sent first.
+	/// See [create_default_segment](crate::cache::track::Publisher::create_default_segment).
+	pub priority: Option<u32>,
+
+	/// The default duration that each segment is cached, used when the segment doesn't set one.
+	pub expires: Option<time::Duration>,
+
+	/// The kind of media contained in the track.
+	pub kind: Option<MediaKind>,
+
+	/// The codec string, ex. `avc1.64001f` or `mp4a.40.2`.
+	pub codec: Option<String>,
+
+	/// The wall clock time when the track or broadcast was created.
+	pub created: Option<time::SystemTime>,
+
+	/// Unknown fields.
+	pub unknown: Params,
+}
+
+impl Metadata {
+	const PRIORITY: VarInt = VarInt::from_u32(0x0);
+	const EXPIRES: VarInt = VarInt::from_u32(0x1); // in milliseconds
+	const KIND: VarInt = VarInt::from_u32(0x2);
+	const CODEC: VarInt = VarInt::from_u32(0x3);
+	const CREATED: VarInt = VarInt::from_u32(0x4); // in microseconds since the UNIX epoch
+}
+
+#[async_trait::async_trait]
+impl Decode for Metadata {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let mut unknown = Params::decode(r).await?;
+
+		let priority = match unknown.get::<VarInt>(Self::PRIORITY).await? {
+			Some(priority) => Some(priority.try_into()?),
+			None => None,
+		};
+
+		let expires = unknown
+			.get::<VarInt>(Self::EXPIRES)
+			.await?
+			.map(|millis| time::Duration::from_millis(millis.into_inner()));
+
+		let kind = unknown.get::<MediaKind>(Self::KIND).await?;
+		let codec = unknown.get::<String>(Self::CODEC).await?;
+
+		let created = unknown
+			.get::<VarInt>(Self::CREATED)
+			.await?
+			.map(|micros| time::UNIX_EPOCH + time::Duration::from_micros(micros.into_inner()));
+
+		Ok(Self {
+			priority,
+			expires,
+			kind,
+			codec,
+			created,
+			unknown,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for Metadata {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		let mut params = self.unknown.clone();
+
+		if let Some(priority) = self.priority {
+			params.set(Self::PRIORITY, VarInt::from_u32(priority)).await?;
+		}
+
+		if let Some(expires) = self.expires {
+			params
+				.set(Self::EXPIRES, VarInt::try_from(expires.as_millis())?)
+				.await?;
+		}
+
+		if let Some(kind) = self.kind {
+			params.set(Self::KIND, kind).await?;
+		}
+
+		if let Some(codec) = &self.codec {
+			params.set(Self::CODEC, codec.clone()).await?;
+		}
+
+		if let Some(created) = self.created {
+			let micros = created
+				.duration_since(time::UNIX_EPOCH)
+				.map_err(|_| EncodeError::InvalidValue)?
+				.as_micros();
+
+			params.set(Self::CREATED, VarInt::try_from(micros)?).await?;
+		}
+
+		params.encode(w).await
+	}
+}
+
+/// The kind of media contained in a track.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+	Audio,
+	Video,
+	Data,
+}
+
+#[async_trait::async_trait]
+impl Decode for MediaKind {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		match VarInt::decode(r).await?.into_inner() {
+			0 => Ok(Self::Audio),
+			1 => Ok(Self::Video),
+			2 => Ok(Self::Data),
+			_ => Err(DecodeError::InvalidParameter),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for MediaKind {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		let v = match self {
+			Self::Audio => VarInt::from_u32(0),
+			Self::Video => VarInt::from_u32(1),
+			Self::Data => VarInt::from_u32(2),
+		};
+
+		v.encode(w).await
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io;
+
+	use super::*;
+	use crate::message::{Message, SubscribeOk};
+	use crate::setup::Extensions;
+
+	fn metadata() -> Metadata {
+		Metadata {
+			priority: Some(2),
+			expires: Some(time::Duration::from_secs(30)),
+			kind: Some(MediaKind::Video),
+			codec: Some("avc1.64001f".to_string()),
+			created: Some(time::UNIX_EPOCH + time::Duration::from_micros(1_700_000_000_000_000)),
+			unknown: Default::default(),
+		}
+	}
+
+	#[tokio::test]
+	async fn encode_decode() {
+		let mut metadata = metadata();
+		metadata
+			.unknown
+			.set(VarInt::from_u32(0x100), "extra".to_string())
+			.await
+			.unwrap();
+
+		let mut buf = Vec::new();
+		metadata.encode(&mut buf).await.unwrap();
+
+		let mut decoded = Metadata::decode(&mut io::Cursor::new(buf)).await.unwrap();
+		assert_eq!(decoded.priority, metadata.priority);
+		assert_eq!(decoded.expires, metadata.expires);
+		assert_eq!(decoded.kind, metadata.kind);
+		assert_eq!(decoded.codec, metadata.codec);
+		assert_eq!(decoded.created, metadata.created);
+
+		// Unknown fields are preserved so relays can forward them.
+		let extra = decoded.unknown.get::<String>(VarInt::from_u32(0x100)).await.unwrap();
+		assert_eq!(extra.as_deref(), Some("extra"));
+	}
+
+	#[tokio::test]
+	async fn subscribe_ok() {
+		let msg = SubscribeOk {
+			id: VarInt::from_u32(3),
+			expires: VarInt::ZERO,
+			track: metadata(),
+			broadcast: Metadata {
+				codec: Some("catalog".to_string()),
+				..Default::default()
+			},
+		};
+
+		for subscribe_metadata in [false, true] {
+			let ext = Extensions {
+				subscribe_metadata,
+				..Default::default()
+			};
+
+			let mut buf = Vec::new();
+			Message::from(msg.clone()).encode(&mut buf, &ext).await.unwrap();
+
+			let decoded = match Message::decode(&mut io::Cursor::new(buf), &ext).await.unwrap() {
+				Message::SubscribeOk(msg) => msg,
+				msg => panic!("unexpected message: {:?}", msg),
+			};
+
+			assert_eq!(decoded.id, msg.id);
+
+			// The metadata is dropped unless the extension was negotiated.
+			match subscribe_metadata {
+				true => {
+					assert_eq!(decoded.track.codec, msg.track.codec);
+					assert_eq!(decoded.track.priority, msg.track.priority);
+					assert_eq!(decoded.broadcast.codec, msg.broadcast.codec);
+				}
+				false => {
+					assert_eq!(decoded.track.codec, None);
+					assert_eq!(decoded.broadcast.codec, None);
+				}
+			}
+		}
+	}
+}
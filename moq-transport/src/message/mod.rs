@@ -10,14 +10,19 @@
 //! - [SubscribeOk]
 //! - [SubscribeError]
 //! - [SubscribeReset]
+//! - [SubscribeMatch]
 //! - [Object]
 //!
 //! Messages sent by the subscriber:
 //! - [Subscribe]
+//! - [SubscribeUpdate]
 //! - [Unsubscribe]
 //! - [AnnounceOk]
 //! - [AnnounceError]
 //!
+//! When the `control_framing` extension is negotiated, each message is prefixed with its length.
+//! Unknown message types are decoded as [Custom] so they can be skipped or handled by the application.
+//!
 //! Example flow:
 //! ```test
 //!  -> ANNOUNCE        namespace="foo"
@@ -33,26 +38,34 @@
 mod announce;
 mod announce_ok;
 mod announce_reset;
+mod custom;
 mod go_away;
+mod metadata;
 mod object;
 mod subscribe;
 mod subscribe_error;
 mod subscribe_fin;
+mod subscribe_match;
 mod subscribe_ok;
 mod subscribe_reset;
+mod subscribe_update;
 mod unannounce;
 mod unsubscribe;
 
 pub use announce::*;
 pub use announce_ok::*;
 pub use announce_reset::*;
+pub use custom::*;
 pub use go_away::*;
+pub use metadata::*;
 pub use object::*;
 pub use subscribe::*;
 pub use subscribe_error::*;
 pub use subscribe_fin::*;
+pub use subscribe_match::*;
 pub use subscribe_ok::*;
 pub use subscribe_reset::*;
+pub use subscribe_update::*;
 pub use unannounce::*;
 pub use unsubscribe::*;
 
@@ -60,6 +73,8 @@ use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
 
 use std::fmt;
 
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
 use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
 
@@ -70,28 +85,76 @@ macro_rules! message_types {
 		/// All supported message types.
 		#[derive(Clone)]
 		pub enum Message {
-			$($name($name)),*
+			$($name($name),)*
+
+			/// A message type not defined by this crate, only possible with the `control_framing` extension.
+			Custom(Custom),
 		}
 
 		impl Message {
 			pub async fn decode<R: AsyncRead>(r: &mut R, ext: &Extensions) -> Result<Self, DecodeError> {
 				let t = VarInt::decode(r).await?;
 
+				if !ext.control_framing {
+					return Self::decode_body(t, r, ext).await?.ok_or(DecodeError::InvalidMessage(t));
+				}
+
+				let size = VarInt::decode(r).await?;
+				let mut payload = r.take(size.into_inner());
+
+				let msg = match Self::decode_body(t, &mut payload, ext).await? {
+					Some(msg) => msg,
+					None => Self::Custom(Custom::decode(t, &mut payload).await?),
+				};
+
+				// Skip any remaining bytes, which could be fields added by a newer version.
+				tokio::io::copy(&mut payload, &mut tokio::io::sink()).await?;
+				if payload.limit() > 0 {
+					return Err(DecodeError::UnexpectedEnd);
+				}
+
+				Ok(msg)
+			}
+
+			// Decode the message body, returning None if the type is unknown.
+			async fn decode_body<R: AsyncRead>(t: VarInt, r: &mut R, ext: &Extensions) -> Result<Option<Self>, DecodeError> {
 				match t.into_inner() {
 					$($val => {
 						let msg = $name::decode(r, ext).await?;
-						Ok(Self::$name(msg))
+						Ok(Some(Self::$name(msg)))
 					})*
-					_ => Err(DecodeError::InvalidMessage(t)),
+					_ => Ok(None),
 				}
 			}
 
 			pub async fn encode<W: AsyncWrite>(&self, w: &mut W, ext: &Extensions) -> Result<(), EncodeError> {
+				if !ext.control_framing {
+					// There's no way for the peer to skip a custom message without framing.
+					// Fail before writing anything so the stream isn't corrupted.
+					if let Self::Custom(_) = self {
+						return Err(EncodeError::InvalidValue);
+					}
+
+					self.id().encode(w).await?;
+					return self.encode_body(w, ext).await;
+				}
+
+				// Encode the message first so we know the size.
+				let mut payload = Vec::new();
+				self.encode_body(&mut payload, ext).await?;
+
+				self.id().encode(w).await?;
+				VarInt::try_from(payload.len())?.encode(w).await?;
+				w.write_all(&payload).await?;
+
+				Ok(())
+			}
+
+			async fn encode_body<W: AsyncWrite>(&self, w: &mut W, ext: &Extensions) -> Result<(), EncodeError> {
 				match self {
-					$(Self::$name(ref m) => {
-						VarInt::from_u32($val).encode(w).await?;
-						m.encode(w, ext).await
-					},)*
+					$(Self::$name(ref m) => m.encode(w, ext).await,)*
+
+					Self::Custom(ref m) => m.encode(w).await,
 				}
 			}
 
@@ -100,6 +163,7 @@ macro_rules! message_types {
 					$(Self::$name(_) => {
 						VarInt::from_u32($val)
 					},)*
+					Self::Custom(ref m) => m.id,
 				}
 			}
 
@@ -108,8 +172,14 @@ macro_rules! message_types {
 					$(Self::$name(_) => {
 						stringify!($name)
 					},)*
+					Self::Custom(_) => "Custom",
 				}
 			}
+
+			/// Returns true if the message type is defined by this crate.
+			pub fn is_known(id: VarInt) -> bool {
+				matches!(id.into_inner(), $($val)|*)
+			}
 		}
 
 		$(impl From<$name> for Message {
@@ -123,6 +193,7 @@ macro_rules! message_types {
 			fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 				match self {
 					$(Self::$name(ref m) => m.fmt(f),)*
+					Self::Custom(ref m) => m.fmt(f),
 				}
 			}
 		}
@@ -140,12 +211,14 @@ message_types! {
 	// SUBSCRIBE family, sent by subscriber
 	Subscribe = 0x3,
 	Unsubscribe = 0xa,
+	SubscribeUpdate = 0xd,
 
 	// SUBSCRIBE family, sent by publisher
 	SubscribeOk = 0x4,
 	SubscribeError = 0x5,
 	SubscribeFin = 0xb,
 	SubscribeReset = 0xc,
+	SubscribeMatch = 0xe,
 
 	// ANNOUNCE family, sent by publisher
 	Announce = 0x6,
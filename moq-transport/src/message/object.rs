@@ -3,7 +3,7 @@ use std::{io, time};
 use tokio::io::AsyncReadExt;
 
 use crate::coding::{AsyncRead, AsyncWrite};
-use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
 use crate::setup;
 
 /// Sent by the publisher as the header of each data stream.
@@ -26,6 +26,9 @@ pub struct Object {
 	// Zero means never expire.
 	pub expires: Option<time::Duration>,
 
+	/// Optional metadata, only sent when the `object_extensions` extension was negotiated.
+	pub extensions: ObjectExtensions,
+
 	/// An optional size, allowing multiple OBJECTs on the same stream.
 	pub size: Option<VarInt>,
 }
@@ -58,6 +61,11 @@ impl Object {
 			false => None,
 		};
 
+		let object_extensions = match extensions.object_extensions {
+			true => ObjectExtensions::decode(r).await?,
+			false => ObjectExtensions::default(),
+		};
+
 		// The presence of the size field depends on the type.
 		let size = match size_present {
 			true => Some(VarInt::decode(r).await?),
@@ -70,6 +78,7 @@ impl Object {
 			sequence,
 			priority,
 			expires,
+			extensions: object_extensions,
 			size,
 		})
 	}
@@ -99,6 +108,11 @@ impl Object {
 			VarInt::try_from(expires)?.encode(w).await?;
 		}
 
+		// NOTE: The extensions are silently dropped if the peer doesn't support them.
+		if extensions.object_extensions {
+			self.extensions.encode(w).await?;
+		}
+
 		if let Some(size) = self.size {
 			size.encode(w).await?;
 		}
@@ -106,3 +120,205 @@ impl Object {
 		Ok(())
 	}
 }
+
+/// Optional metadata attached to an OBJECT, which relays forward unchanged.
+///
+/// This is encoded as a list of parameters so unknown extensions can be skipped.
+#[derive(Clone, Debug, Default)]
+pub struct ObjectExtensions {
+	/// The wall clock time when the object was captured, used to measure latency.
+	pub timestamp: Option<time::SystemTime>,
+
+	/// The duration of the media contained in the object.
+	pub duration: Option<time::Duration>,
+
+	/// The timestamp of the media contained in the object, ex. the decode time of the first sample.
+	pub media_time: Option<time::Duration>,
+
+	/// Unknown extensions.
+	pub unknown: Params,
+}
+
+impl ObjectExtensions {
+	// Each value is encoded as a VarInt in microseconds.
+	const TIMESTAMP: VarInt = VarInt::from_u32(0x0);
+	const DURATION: VarInt = VarInt::from_u32(0x1);
+	const MEDIA_TIME: VarInt = VarInt::from_u32(0x2);
+
+	/// Fill in any missing extensions using the values in `other`.
+	pub fn or(&self, other: &Self) -> Self {
+		let mut unknown = other.unknown.clone();
+		unknown.0.extend(self.unknown.0.clone());
+
+		Self {
+			timestamp: self.timestamp.or(other.timestamp),
+			duration: self.duration.or(other.duration),
+			media_time: self.media_time.or(other.media_time),
+			unknown,
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Decode for ObjectExtensions {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let mut unknown = Params::decode(r).await?;
+
+		let timestamp = unknown
+			.get::<VarInt>(Self::TIMESTAMP)
+			.await?
+			.map(|micros| time::UNIX_EPOCH + time::Duration::from_micros(micros.into_inner()));
+
+		let duration = unknown
+			.get::<VarInt>(Self::DURATION)
+			.await?
+			.map(|micros| time::Duration::from_micros(micros.into_inner()));
+
+		let media_time = unknown
+			.get::<VarInt>(Self::MEDIA_TIME)
+			.await?
+			.map(|micros| time::Duration::from_micros(micros.into_inner()));
+
+		Ok(Self {
+			timestamp,
+			duration,
+			media_time,
+			unknown,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for ObjectExtensions {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		let mut params = self.unknown.clone();
+
+		if let Some(timestamp) = self.timestamp {
+			let micros = timestamp
+				.duration_since(time::UNIX_EPOCH)
+				.map_err(|_| EncodeError::InvalidValue)?
+				.as_micros();
+
+			params.set(Self::TIMESTAMP, VarInt::try_from(micros)?).await?;
+		}
+
+		if let Some(duration) = self.duration {
+			params
+				.set(Self::DURATION, VarInt::try_from(duration.as_micros())?)
+				.await?;
+		}
+
+		if let Some(media_time) = self.media_time {
+			params
+				.set(Self::MEDIA_TIME, VarInt::try_from(media_time.as_micros())?)
+				.await?;
+		}
+
+		params.encode(w).await
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io;
+
+	use super::*;
+
+	fn object() -> Object {
+		Object {
+			track: VarInt::from_u32(1),
+			group: VarInt::from_u32(2),
+			sequence: VarInt::from_u32(3),
+			priority: 4,
+			expires: Some(time::Duration::from_secs(30)),
+			extensions: ObjectExtensions {
+				timestamp: Some(time::UNIX_EPOCH + time::Duration::from_micros(1_700_000_000_000_000)),
+				duration: Some(time::Duration::from_millis(33)),
+				media_time: Some(time::Duration::from_secs(90)),
+				unknown: Default::default(),
+			},
+			size: Some(VarInt::from_u32(1024)),
+		}
+	}
+
+	async fn round_trip(object: &Object, ext: &setup::Extensions) -> Object {
+		let mut buf = Vec::new();
+		object.encode(&mut buf, ext).await.unwrap();
+		Object::decode(&mut io::Cursor::new(buf), ext).await.unwrap()
+	}
+
+	#[tokio::test]
+	async fn extensions() {
+		let mut object = object();
+		object
+			.extensions
+			.unknown
+			.set(VarInt::from_u32(0x100), VarInt::from_u32(5))
+			.await
+			.unwrap();
+
+		let ext = setup::Extensions {
+			object_expires: true,
+			object_extensions: true,
+			..Default::default()
+		};
+
+		let mut decoded = round_trip(&object, &ext).await;
+		assert_eq!(decoded.track, object.track);
+		assert_eq!(decoded.group, object.group);
+		assert_eq!(decoded.sequence, object.sequence);
+		assert_eq!(decoded.priority, object.priority);
+		assert_eq!(decoded.expires, object.expires);
+		assert_eq!(decoded.size, object.size);
+
+		assert_eq!(decoded.extensions.timestamp, object.extensions.timestamp);
+		assert_eq!(decoded.extensions.duration, object.extensions.duration);
+		assert_eq!(decoded.extensions.media_time, object.extensions.media_time);
+
+		// Unknown extensions are preserved so relays can forward them.
+		let unknown = decoded
+			.extensions
+			.unknown
+			.get::<VarInt>(VarInt::from_u32(0x100))
+			.await
+			.unwrap();
+		assert_eq!(unknown, Some(VarInt::from_u32(5)));
+	}
+
+	#[tokio::test]
+	async fn extensions_not_negotiated() {
+		let object = object();
+
+		let ext = setup::Extensions {
+			object_expires: true,
+			..Default::default()
+		};
+
+		// The extensions are dropped, but the rest of the header is still decoded.
+		let decoded = round_trip(&object, &ext).await;
+		assert_eq!(decoded.sequence, object.sequence);
+		assert_eq!(decoded.size, object.size);
+		assert_eq!(decoded.extensions.timestamp, None);
+		assert_eq!(decoded.extensions.duration, None);
+	}
+
+	#[test]
+	fn extensions_or() {
+		let first = ObjectExtensions {
+			timestamp: Some(time::UNIX_EPOCH),
+			..Default::default()
+		};
+
+		let segment = ObjectExtensions {
+			timestamp: Some(time::UNIX_EPOCH + time::Duration::from_secs(1)),
+			duration: Some(time::Duration::from_millis(33)),
+			..Default::default()
+		};
+
+		// Any extensions set on the object take precedence over the segment.
+		let merged = first.or(&segment);
+		assert_eq!(merged.timestamp, first.timestamp);
+		assert_eq!(merged.duration, segment.duration);
+		assert_eq!(merged.media_time, None);
+	}
+}
@@ -17,19 +17,35 @@ pub struct Subscribe {
 	/// Must be None if `extensions.subscribe_split` is false.
 	pub namespace: Option<String>,
 
-	/// The track name.
+	/// The track name, or a prefix of the track name if `prefix` is true.
 	pub name: String,
 
+	/// Subscribe to every track that starts with `name`, encoded as a parameter.
+	///
+	/// Each matching track is sent as a [SubscribeMatch](super::SubscribeMatch), requiring the `subscribe_prefix` extension.
+	pub prefix: bool,
+
 	/// The start/end group/object.
 	pub start_group: SubscribeLocation,
 	pub start_object: SubscribeLocation,
 	pub end_group: SubscribeLocation,
 	pub end_object: SubscribeLocation,
 
-	/// Optional parameters
+	/// The order in which groups should be delivered, encoded as a parameter.
+	pub order: GroupOrder,
+
+	/// Unknown parameters.
 	pub params: Params,
 }
 
+// A custom SUBSCRIBE parameter containing the group order.
+// TODO write up a PR
+const GROUP_ORDER_PARAM: VarInt = VarInt::from_u32(0x20);
+
+// A custom SUBSCRIBE parameter, present with a value of 1 when the name is a prefix.
+// TODO write up a PR
+const PREFIX_PARAM: VarInt = VarInt::from_u32(0x21);
+
 impl Subscribe {
 	pub async fn decode<R: AsyncRead>(r: &mut R, ext: &Extensions) -> Result<Self, DecodeError> {
 		let id = VarInt::decode(r).await?;
@@ -58,16 +74,24 @@ impl Subscribe {
 
 		// NOTE: There's some more location restrictions in the draft, but they're enforced at a higher level.
 
-		let params = Params::decode(r).await?;
+		let mut params = Params::decode(r).await?;
+
+		let order = params.get::<GroupOrder>(GROUP_ORDER_PARAM).await?.unwrap_or_default();
+		let prefix = params
+			.get::<VarInt>(PREFIX_PARAM)
+			.await?
+			.is_some_and(|v| v != VarInt::ZERO);
 
 		Ok(Self {
 			id,
 			namespace,
 			name,
+			prefix,
 			start_group,
 			start_object,
 			end_group,
 			end_object,
+			order,
 			params,
 		})
 	}
@@ -90,7 +114,17 @@ impl Subscribe {
 		self.end_group.encode(w).await?;
 		self.end_object.encode(w).await?;
 
-		self.params.encode(w).await?;
+		// Don't bother encoding the default so older peers see the same message.
+		let mut params = self.params.clone();
+		if self.order != GroupOrder::Publisher {
+			params.set(GROUP_ORDER_PARAM, self.order).await?;
+		}
+
+		if self.prefix {
+			params.set(PREFIX_PARAM, VarInt::from_u32(1)).await?;
+		}
+
+		params.encode(w).await?;
 
 		Ok(())
 	}
@@ -140,3 +174,42 @@ impl SubscribeLocation {
 		Ok(())
 	}
 }
+
+/// The order in which the groups of a subscription should be delivered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GroupOrder {
+	/// Use the priority chosen by the publisher, where **smaller** values are sent first.
+	#[default]
+	Publisher,
+
+	/// Oldest group first, useful for recording or VOD.
+	Ascending,
+
+	/// Newest group first, useful for live playback.
+	Descending,
+}
+
+#[async_trait::async_trait]
+impl Decode for GroupOrder {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		match VarInt::decode(r).await?.into_inner() {
+			0 => Ok(Self::Publisher),
+			1 => Ok(Self::Ascending),
+			2 => Ok(Self::Descending),
+			_ => Err(DecodeError::InvalidParameter),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for GroupOrder {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		let v = match self {
+			Self::Publisher => 0u32,
+			Self::Ascending => 1,
+			Self::Descending => 2,
+		};
+
+		VarInt::from_u32(v).encode(w).await
+	}
+}
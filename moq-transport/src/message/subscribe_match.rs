@@ -0,0 +1,74 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+use crate::setup::Extensions;
+
+/// Sent by the publisher when a track matches a prefix Subscribe, before any objects for the track.
+///
+/// The track is then treated like any other subscription, starting with a SUBSCRIBE_OK for the new ID.
+/// This requires the `subscribe_prefix` extension.
+#[derive(Clone, Debug)]
+pub struct SubscribeMatch {
+	/// The ID of the prefix subscription.
+	pub id: VarInt,
+
+	/// The ID chosen by the publisher for the matching track, which is at least [SubscribeMatch::MIN_TRACK].
+	pub track: VarInt,
+
+	/// The full name of the matching track.
+	pub name: String,
+}
+
+impl SubscribeMatch {
+	/// IDs chosen by the publisher start here, so the subscriber must choose smaller IDs to avoid a collision.
+	pub const MIN_TRACK: VarInt = VarInt::from_u32(u32::MAX);
+
+	pub async fn decode<R: AsyncRead>(r: &mut R, _ext: &Extensions) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let track = VarInt::decode(r).await?;
+		let name = String::decode(r).await?;
+
+		Ok(Self { id, track, name })
+	}
+
+	pub async fn encode<W: AsyncWrite>(&self, w: &mut W, _ext: &Extensions) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		self.track.encode(w).await?;
+		self.name.encode(w).await?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io;
+
+	use super::*;
+	use crate::message::Message;
+
+	#[tokio::test]
+	async fn encode_decode() {
+		let msg = SubscribeMatch {
+			id: VarInt::from_u32(1),
+			track: SubscribeMatch::MIN_TRACK,
+			name: "video/1080p".to_string(),
+		};
+
+		let ext = Extensions {
+			subscribe_prefix: true,
+			..Default::default()
+		};
+
+		let mut buf = Vec::new();
+		Message::from(msg.clone()).encode(&mut buf, &ext).await.unwrap();
+
+		let decoded = match Message::decode(&mut io::Cursor::new(buf), &ext).await.unwrap() {
+			Message::SubscribeMatch(msg) => msg,
+			msg => panic!("unexpected message: {:?}", msg),
+		};
+
+		assert_eq!(decoded.id, msg.id);
+		assert_eq!(decoded.track, msg.track);
+		assert_eq!(decoded.name, msg.name);
+	}
+}
@@ -1,8 +1,10 @@
-use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
 
 use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
 
+use super::Metadata;
+
 /// Sent by the publisher to accept a Subscribe.
 #[derive(Clone, Debug)]
 pub struct SubscribeOk {
@@ -12,20 +14,55 @@ pub struct SubscribeOk {
 
 	/// The subscription will expire in this many milliseconds.
 	pub expires: VarInt,
+
+	/// Information about the track, only sent when the `subscribe_metadata` extension was negotiated.
+	pub track: Metadata,
+
+	/// Information about the broadcast, only sent when the `subscribe_metadata` extension was negotiated.
+	pub broadcast: Metadata,
 }
 
 impl SubscribeOk {
-	pub async fn decode<R: AsyncRead>(r: &mut R, _ext: &Extensions) -> Result<Self, DecodeError> {
+	// Both are encoded as a nested list of parameters.
+	const TRACK_PARAM: VarInt = VarInt::from_u32(0x0);
+	const BROADCAST_PARAM: VarInt = VarInt::from_u32(0x1);
+
+	pub async fn decode<R: AsyncRead>(r: &mut R, ext: &Extensions) -> Result<Self, DecodeError> {
 		let id = VarInt::decode(r).await?;
 		let expires = VarInt::decode(r).await?;
-		Ok(Self { id, expires })
+
+		let (track, broadcast) = match ext.subscribe_metadata {
+			true => {
+				let mut params = Params::decode(r).await?;
+				let track = params.get(Self::TRACK_PARAM).await?.unwrap_or_default();
+				let broadcast = params.get(Self::BROADCAST_PARAM).await?.unwrap_or_default();
+				(track, broadcast)
+			}
+			false => Default::default(),
+		};
+
+		Ok(Self {
+			id,
+			expires,
+			track,
+			broadcast,
+		})
 	}
 }
 
 impl SubscribeOk {
-	pub async fn encode<W: AsyncWrite>(&self, w: &mut W, _ext: &Extensions) -> Result<(), EncodeError> {
+	pub async fn encode<W: AsyncWrite>(&self, w: &mut W, ext: &Extensions) -> Result<(), EncodeError> {
 		self.id.encode(w).await?;
 		self.expires.encode(w).await?;
+
+		// NOTE: The metadata is silently dropped if the peer doesn't support it.
+		if ext.subscribe_metadata {
+			let mut params = Params::default();
+			params.set(Self::TRACK_PARAM, self.track.clone()).await?;
+			params.set(Self::BROADCAST_PARAM, self.broadcast.clone()).await?;
+			params.encode(w).await?;
+		}
+
 		Ok(())
 	}
 }
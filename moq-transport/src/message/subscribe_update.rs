@@ -0,0 +1,123 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
+
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::setup::Extensions;
+
+use super::SubscribeLocation;
+
+/// Sent by the subscriber to modify an active Subscribe, such as changing the priority or the end group.
+#[derive(Clone, Debug)]
+pub struct SubscribeUpdate {
+	// NOTE: No full track name because of this proposal: https://github.com/moq-wg/moq-transport/issues/209
+	/// The ID for this subscription.
+	pub id: VarInt,
+
+	/// The new end group/object.
+	pub end_group: SubscribeLocation,
+	pub end_object: SubscribeLocation,
+
+	/// The new subscriber priority, where **smaller** values are sent first.
+	pub priority: u8,
+
+	/// Optional parameters
+	pub params: Params,
+}
+
+impl SubscribeUpdate {
+	pub async fn decode<R: AsyncRead>(r: &mut R, _ext: &Extensions) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+
+		let end_group = SubscribeLocation::decode(r).await?;
+		let end_object = SubscribeLocation::decode(r).await?;
+
+		// You can't have an end object without an end group.
+		if end_group == SubscribeLocation::None && end_object != SubscribeLocation::None {
+			return Err(DecodeError::InvalidSubscribeLocation);
+		}
+
+		let priority = VarInt::decode(r).await?.try_into()?;
+		let params = Params::decode(r).await?;
+
+		Ok(Self {
+			id,
+			end_group,
+			end_object,
+			priority,
+			params,
+		})
+	}
+
+	pub async fn encode<W: AsyncWrite>(&self, w: &mut W, _ext: &Extensions) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+
+		self.end_group.encode(w).await?;
+		self.end_object.encode(w).await?;
+
+		VarInt::from(self.priority).encode(w).await?;
+		self.params.encode(w).await?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io;
+
+	use super::*;
+	use crate::message::Message;
+
+	async fn round_trip(msg: SubscribeUpdate, ext: &Extensions) -> SubscribeUpdate {
+		let mut buf = Vec::new();
+		Message::from(msg).encode(&mut buf, ext).await.unwrap();
+
+		match Message::decode(&mut io::Cursor::new(buf), ext).await.unwrap() {
+			Message::SubscribeUpdate(msg) => msg,
+			msg => panic!("unexpected message: {:?}", msg),
+		}
+	}
+
+	#[tokio::test]
+	async fn encode_decode() {
+		let msg = SubscribeUpdate {
+			id: VarInt::from_u32(7),
+			end_group: SubscribeLocation::Absolute(VarInt::from_u32(100)),
+			end_object: SubscribeLocation::None,
+			priority: 3,
+			params: Default::default(),
+		};
+
+		for control_framing in [false, true] {
+			let ext = Extensions {
+				subscribe_update: true,
+				control_framing,
+				..Default::default()
+			};
+
+			let decoded = round_trip(msg.clone(), &ext).await;
+			assert_eq!(decoded.id, msg.id);
+			assert_eq!(decoded.end_group, msg.end_group);
+			assert_eq!(decoded.end_object, msg.end_object);
+			assert_eq!(decoded.priority, msg.priority);
+		}
+	}
+
+	#[tokio::test]
+	async fn end_object_requires_end_group() {
+		let msg = SubscribeUpdate {
+			id: VarInt::from_u32(7),
+			end_group: SubscribeLocation::None,
+			end_object: SubscribeLocation::Absolute(VarInt::from_u32(1)),
+			priority: 0,
+			params: Default::default(),
+		};
+
+		let ext = Extensions::default();
+
+		let mut buf = Vec::new();
+		msg.encode(&mut buf, &ext).await.unwrap();
+
+		let res = SubscribeUpdate::decode(&mut io::Cursor::new(buf), &ext).await;
+		assert!(matches!(res, Err(DecodeError::InvalidSubscribeLocation)));
+	}
+}
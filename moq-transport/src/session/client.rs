@@ -8,15 +8,54 @@ pub struct Client {}
 impl Client {
 	/// Connect using an established WebTransport session, performing the MoQ handshake as a publisher.
 	pub async fn publisher(session: Session, source: broadcast::Subscriber) -> Result<Publisher, SessionError> {
-		let control = Self::send_setup(&session, setup::Role::Publisher).await?;
-		let publisher = Publisher::new(session, control, source);
+		let control = Self::send_setup(&session, setup::Role::Publisher, false, None).await?;
+		let publisher = Publisher::new(session, control, Some(source), None);
+		Ok(publisher)
+	}
+
+	/// Connect as a publisher like [Client::publisher], sending a token in the SETUP.
+	///
+	/// A relay only lets a publisher with the same token resume or take over the broadcast.
+	pub async fn publisher_with_token(
+		session: Session,
+		source: broadcast::Subscriber,
+		token: &str,
+	) -> Result<Publisher, SessionError> {
+		let control = Self::send_setup(&session, setup::Role::Publisher, false, Some(token)).await?;
+		let publisher = Publisher::new(session, control, Some(source), None);
+		Ok(publisher)
+	}
+
+	/// Connect as a publisher like [Client::publisher], on behalf of a relay pushing a broadcast it already serves.
+	///
+	/// This offers the `push` extension, so the server knows another relay is the broadcast's origin.
+	/// The server only trusts the extension if the token identifies us as another relay.
+	pub async fn pusher(
+		session: Session,
+		source: broadcast::Subscriber,
+		token: Option<&str>,
+	) -> Result<Publisher, SessionError> {
+		let control = Self::send_setup(&session, setup::Role::Publisher, true, token).await?;
+		let publisher = Publisher::new(session, control, Some(source), None);
 		Ok(publisher)
 	}
 
 	/// Connect using an established WebTransport session, performing the MoQ handshake as a subscriber.
 	pub async fn subscriber(session: Session, source: broadcast::Publisher) -> Result<Subscriber, SessionError> {
-		let control = Self::send_setup(&session, setup::Role::Subscriber).await?;
-		let subscriber = Subscriber::new(session, control, source);
+		let control = Self::send_setup(&session, setup::Role::Subscriber, false, None).await?;
+		let subscriber = Subscriber::new(session, control, Some(source));
+		Ok(subscriber)
+	}
+
+	/// Connect using an established WebTransport session, performing the MoQ handshake as a subscriber without a broadcast.
+	///
+	/// Use [Subscriber::fetch] to subscribe to many broadcasts by namespace, which requires the `subscribe_split` extension.
+	/// Returns [SessionError::RequiredExtension] if the server doesn't support it, so callers can fall back to [Client::subscriber].
+	pub async fn fetcher(session: Session) -> Result<Subscriber, SessionError> {
+		let control = Self::send_setup(&session, setup::Role::Subscriber, false, None).await?;
+		control.ext.require_subscribe_split()?;
+
+		let subscriber = Subscriber::new(session, control, None);
 		Ok(subscriber)
 	}
 
@@ -27,7 +66,12 @@ impl Client {
 		}
 	*/
 
-	async fn send_setup(session: &Session, role: setup::Role) -> Result<Control, SessionError> {
+	async fn send_setup(
+		session: &Session,
+		role: setup::Role,
+		push: bool,
+		token: Option<&str>,
+	) -> Result<Control, SessionError> {
 		let mut control = session.open_bi().await?;
 
 		let versions: setup::Versions = [setup::Version::DRAFT_01, setup::Version::KIXEL_01].into();
@@ -35,13 +79,20 @@ impl Client {
 		let client = setup::Client {
 			role,
 			versions: versions.clone(),
+			token: token.map(str::to_string),
 			params: Default::default(),
 
-			// Offer all extensions
+			// Offer all extensions, except push which describes the session rather than what we support.
 			extensions: setup::Extensions {
 				object_expires: true,
 				subscriber_id: true,
 				subscribe_split: true,
+				subscribe_update: true,
+				object_extensions: true,
+				control_framing: true,
+				subscribe_metadata: true,
+				subscribe_prefix: true,
+				push,
 			},
 		};
 
@@ -63,8 +114,18 @@ impl Client {
 				}
 			}
 			setup::Version::KIXEL_01 => {
-				// KIXEL_01 didn't support extensions; all were enabled.
-				server.extensions = client.extensions.clone()
+				// KIXEL_01 didn't support extensions; all of the original ones were enabled.
+				server.extensions = setup::Extensions {
+					object_expires: true,
+					subscriber_id: true,
+					subscribe_split: true,
+					subscribe_update: false,
+					object_extensions: false,
+					control_framing: false,
+					subscribe_metadata: false,
+					subscribe_prefix: false,
+					push: false,
+				}
 			}
 			_ => return Err(SessionError::Version(versions, [server.version].into())),
 		}
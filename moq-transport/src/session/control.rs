@@ -1,18 +1,25 @@
 // A helper class to guard sending control messages behind a Mutex.
 
-use std::{fmt, sync::Arc};
+use std::{collections::HashSet, fmt, sync, sync::Arc};
 
 use tokio::sync::Mutex;
 use webtransport_quinn::{RecvStream, SendStream};
 
 use super::SessionError;
-use crate::{message::Message, setup::Extensions};
+use crate::{
+	message::{self, Message},
+	setup::Extensions,
+	VarInt,
+};
 
 #[derive(Debug, Clone)]
 pub(crate) struct Control {
 	send: Arc<Mutex<SendStream>>,
 	recv: Arc<Mutex<RecvStream>>,
 	pub ext: Extensions,
+
+	// Custom message types that are returned instead of skipped.
+	custom: Arc<sync::Mutex<HashSet<VarInt>>>,
 }
 
 impl Control {
@@ -21,25 +28,50 @@ impl Control {
 			send: Arc::new(Mutex::new(send)),
 			recv: Arc::new(Mutex::new(recv)),
 			ext,
+			custom: Default::default(),
+		}
+	}
+
+	// Return custom messages with this type from recv instead of skipping them.
+	pub fn register(&self, id: VarInt) -> Result<(), SessionError> {
+		self.ext.require_control_framing()?;
+
+		if Message::is_known(id) || !self.custom.lock().unwrap().insert(id) {
+			return Err(SessionError::CustomKnown(id));
+		}
+
+		Ok(())
+	}
+
+	// Send a custom message, which must not use a type defined by this crate, otherwise the peer would decode it as that message.
+	pub async fn send_custom(&self, msg: message::Custom) -> Result<(), SessionError> {
+		self.ext.require_control_framing()?;
+
+		if Message::is_known(msg.id) {
+			return Err(SessionError::CustomKnown(msg.id));
 		}
+
+		self.send(msg).await
 	}
 
 	pub async fn send<T: Into<Message> + fmt::Debug>(&self, msg: T) -> Result<(), SessionError> {
 		let mut stream = self.send.lock().await;
-		log::info!("sending message: {:?}", msg);
-		msg.into()
-			.encode(&mut *stream, &self.ext)
-			.await
-			.map_err(|e| SessionError::Unknown(e.to_string()))?;
+		log::debug!("sending message: {:?}", msg);
+		msg.into().encode(&mut *stream, &self.ext).await?;
 		Ok(())
 	}
 
 	// It's likely a mistake to call this from two different tasks, but it's easier to just support it.
 	pub async fn recv(&self) -> Result<Message, SessionError> {
 		let mut stream = self.recv.lock().await;
-		let msg = Message::decode(&mut *stream, &self.ext)
-			.await
-			.map_err(|e| SessionError::Unknown(e.to_string()))?;
-		Ok(msg)
+
+		loop {
+			match Message::decode(&mut *stream, &self.ext).await? {
+				Message::Custom(msg) if !self.custom.lock().unwrap().contains(&msg.id) => {
+					log::warn!("skipping unknown message: id={} size={}", msg.id, msg.payload.len())
+				}
+				msg => return Ok(msg),
+			}
+		}
 	}
 }
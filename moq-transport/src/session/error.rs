@@ -1,4 +1,6 @@
-use crate::{cache, coding, setup, MoqError, VarInt};
+use std::io;
+
+use crate::{cache, code, coding, setup, MoqError, VarInt};
 
 #[derive(thiserror::Error, Debug)]
 pub enum SessionError {
@@ -8,11 +10,29 @@ pub enum SessionError {
 	#[error("cache error: {0}")]
 	Cache(#[from] cache::CacheError),
 
+	/// A message could not be encoded.
 	#[error("encode error: {0}")]
-	Encode(#[from] coding::EncodeError),
+	Encode(#[source] coding::EncodeError),
+
+	/// A message could not be decoded.
+	#[error("malformed message: {0}")]
+	Malformed(#[source] coding::DecodeError),
+
+	/// A message with an unknown type was received.
+	#[error("unknown message type: {0}")]
+	UnknownMessage(#[source] coding::DecodeError),
+
+	/// The stream was closed cleanly, so there are no more messages.
+	#[error("stream closed: {0}")]
+	Closed(#[source] coding::DecodeError),
+
+	/// The stream was closed before a message was fully received.
+	#[error("unexpected end of stream: {0}")]
+	UnexpectedEnd(#[source] coding::DecodeError),
 
-	#[error("decode error: {0}")]
-	Decode(#[from] coding::DecodeError),
+	/// An I/O error occured while reading or writing a message.
+	#[error("io error: {0}")]
+	Io(#[source] io::Error),
 
 	#[error("unsupported versions: client={0:?} server={1:?}")]
 	Version(setup::Versions, setup::Versions),
@@ -48,13 +68,54 @@ pub enum SessionError {
 	#[error("required extension not offered: {0:?}")]
 	RequiredExtension(VarInt),
 
-	/// Some VarInt was too large and we were too lazy to handle it
-	#[error("varint bounds exceeded")]
-	BoundsExceeded(#[from] coding::BoundsExceeded),
+	/// A custom message type was already registered or is defined by this crate.
+	#[error("custom message type already known: {0}")]
+	CustomKnown(VarInt),
+
+	/// Some VarInt was too large to encode or decode.
+	#[error("limit exceeded: {0}")]
+	LimitExceeded(#[from] coding::BoundsExceeded),
+}
+
+impl SessionError {
+	/// Returns the code if the peer reset the stream, as opposed to a local or protocol failure.
+	pub fn reset_code(&self) -> Option<u32> {
+		match self {
+			Self::Read(webtransport_quinn::ReadError::Reset(code)) => Some(*code),
+
+			// NOTE: The code is lost when the reset is read via AsyncRead, ex. while decoding an OBJECT header.
+			Self::Io(err) if err.kind() == io::ErrorKind::ConnectionReset => Some(code::READ),
+			_ => None,
+		}
+	}
+}
+
+// Classify decode errors so they're sent with a meaningful code.
+impl From<coding::DecodeError> for SessionError {
+	fn from(err: coding::DecodeError) -> Self {
+		match err {
+			coding::DecodeError::InvalidMessage(_) => Self::UnknownMessage(err),
+			coding::DecodeError::BoundsExceeded(err) => Self::LimitExceeded(err),
+			coding::DecodeError::Final => Self::Closed(err),
+			coding::DecodeError::UnexpectedEnd => Self::UnexpectedEnd(err),
+			coding::DecodeError::IoError(ref inner) if inner.kind() == io::ErrorKind::UnexpectedEof => {
+				Self::UnexpectedEnd(err)
+			}
+			coding::DecodeError::IoError(err) => Self::Io(err),
+			err => Self::Malformed(err),
+		}
+	}
+}
 
-	/// An unclassified error because I'm lazy. TODO classify these errors
-	#[error("unknown error: {0}")]
-	Unknown(String),
+// Classify encode errors so they're sent with a meaningful code.
+impl From<coding::EncodeError> for SessionError {
+	fn from(err: coding::EncodeError) -> Self {
+		match err {
+			coding::EncodeError::BoundsExceeded(err) => Self::LimitExceeded(err),
+			coding::EncodeError::IoError(err) => Self::Io(err),
+			err => Self::Encode(err),
+		}
+	}
 }
 
 impl MoqError for SessionError {
@@ -62,20 +123,24 @@ impl MoqError for SessionError {
 	fn code(&self) -> u32 {
 		match self {
 			Self::Cache(err) => err.code(),
-			Self::RoleIncompatible(..) => 406,
-			Self::RoleViolation(..) => 405,
-			Self::StreamMapping => 409,
-			Self::Unknown(_) => 500,
-			Self::Write(_) => 501,
-			Self::Read(_) => 502,
-			Self::Session(_) => 503,
-			Self::Version(..) => 406,
-			Self::Encode(_) => 500,
-			Self::Decode(_) => 500,
-			Self::InvalidPriority(_) => 400,
-			Self::InvalidSize(_) => 400,
-			Self::RequiredExtension(_) => 426,
-			Self::BoundsExceeded(_) => 500,
+			Self::RoleIncompatible(..) => code::NOT_ACCEPTABLE,
+			Self::RoleViolation(..) => code::ROLE_VIOLATION,
+			Self::StreamMapping => code::CONFLICT,
+			Self::Write(_) => code::WRITE,
+			Self::Read(_) => code::READ,
+			Self::Session(_) => code::SESSION,
+			Self::Version(..) => code::NOT_ACCEPTABLE,
+			Self::Encode(_) => code::INTERNAL,
+			Self::Malformed(_) => code::MALFORMED,
+			Self::UnknownMessage(_) => code::UNKNOWN_MESSAGE,
+			Self::Closed(_) => code::CLOSED,
+			Self::UnexpectedEnd(_) => code::STREAM_CLOSED,
+			Self::Io(_) => code::IO,
+			Self::InvalidPriority(_) => code::BAD_REQUEST,
+			Self::InvalidSize(_) => code::BAD_REQUEST,
+			Self::RequiredExtension(_) => code::REQUIRED_EXTENSION,
+			Self::CustomKnown(_) => code::CONFLICT,
+			Self::LimitExceeded(_) => code::LIMIT_EXCEEDED,
 		}
 	}
 
@@ -93,15 +158,19 @@ impl MoqError for SessionError {
 			Self::Read(err) => format!("read error: {}", err),
 			Self::Write(err) => format!("write error: {}", err),
 			Self::Session(err) => format!("session error: {}", err),
-			Self::Unknown(err) => format!("unknown error: {}", err),
 			Self::Version(client, server) => format!("unsupported versions: client={:?} server={:?}", client, server),
 			Self::Encode(err) => format!("encode error: {}", err),
-			Self::Decode(err) => format!("decode error: {}", err),
+			Self::Malformed(err) => format!("malformed message: {}", err),
+			Self::UnknownMessage(err) => format!("unknown message type: {}", err),
+			Self::Closed(_) => "stream closed".to_owned(),
+			Self::UnexpectedEnd(err) => format!("unexpected end of stream: {}", err),
+			Self::Io(err) => format!("io error: {}", err),
 			Self::StreamMapping => "streaming mapping conflict".to_owned(),
 			Self::InvalidPriority(priority) => format!("invalid priority: {}", priority),
 			Self::InvalidSize(size) => format!("invalid size: {}", size),
 			Self::RequiredExtension(id) => format!("required extension was missing: {:?}", id),
-			Self::BoundsExceeded(_) => "varint bounds exceeded".to_string(),
+			Self::CustomKnown(id) => format!("custom message type already known: {}", id),
+			Self::LimitExceeded(err) => format!("limit exceeded: {}", err),
 		}
 	}
 }
@@ -0,0 +1,187 @@
+use std::{sync::Mutex, time};
+
+use super::Stats;
+
+/// An estimate of the network conditions for a session, see [Subscriber::estimate](super::Subscriber::estimate).
+///
+/// This can be used to choose between renditions, ex. switching to a lower bitrate track when the throughput drops.
+#[derive(Clone, Debug)]
+pub struct Estimate {
+	/// The smoothed delivery rate in bytes per second, or None until enough data has been received.
+	///
+	/// Only the time spent actively receiving data is counted, so this isn't limited to the bitrate of the media.
+	pub throughput: Option<u64>,
+
+	/// The smoothed round trip time, see [Stats::rtt].
+	pub rtt: time::Duration,
+}
+
+impl Estimate {
+	pub(crate) fn new(throughput: Option<u64>, stats: &Stats) -> Self {
+		Self {
+			throughput,
+			rtt: stats.rtt,
+		}
+	}
+}
+
+// Computes an exponentially weighted moving average of the delivery rate.
+//
+// Live media arrives in bursts, as each frame is sent as soon as it's produced.
+// A gap longer than the RTT means the publisher had nothing to send, so only the time within each burst is counted.
+#[derive(Debug, Default)]
+pub(crate) struct Estimator {
+	state: Mutex<EstimatorState>,
+}
+
+impl Estimator {
+	// The weight given to each new sample, the same as the QUIC smoothed RTT.
+	const WEIGHT: f64 = 1.0 / 8.0;
+
+	// Bursts shorter than this are ignored, since packets are often received in batches.
+	const MIN_DURATION: time::Duration = time::Duration::from_millis(10);
+
+	// Take a sample at least this often, so a continuous transfer is still measured.
+	const MAX_DURATION: time::Duration = time::Duration::from_millis(250);
+
+	// Record that some bytes were received, given the current RTT of the connection.
+	pub fn received(&self, bytes: usize, rtt: time::Duration) {
+		self.received_at(bytes, rtt, time::Instant::now())
+	}
+
+	fn received_at(&self, bytes: usize, rtt: time::Duration, now: time::Instant) {
+		let mut state = self.state.lock().unwrap();
+
+		match (state.start, state.last) {
+			(Some(start), Some(last)) if now - last <= rtt => {
+				state.bytes += bytes;
+				state.last = Some(now);
+
+				if now - start >= Self::MAX_DURATION {
+					state.sample();
+					state.start = Some(now);
+				}
+			}
+			_ => {
+				// The publisher was idle, so start a new burst.
+				// These bytes are not counted since we don't know when they were sent.
+				state.sample();
+				state.start = Some(now);
+				state.last = Some(now);
+			}
+		}
+	}
+
+	pub fn throughput(&self) -> Option<u64> {
+		self.state
+			.lock()
+			.unwrap()
+			.throughput
+			.map(|throughput| throughput as u64)
+	}
+}
+
+#[derive(Debug, Default)]
+struct EstimatorState {
+	// The smoothed delivery rate in bytes per second.
+	throughput: Option<f64>,
+
+	// When the current burst started and when data was last received.
+	start: Option<time::Instant>,
+	last: Option<time::Instant>,
+
+	// The number of bytes received during the current burst.
+	bytes: usize,
+}
+
+impl EstimatorState {
+	// Add a sample for the current burst, if it was long enough to measure, and reset the byte count.
+	fn sample(&mut self) {
+		let bytes = std::mem::take(&mut self.bytes);
+
+		let elapsed = match self.start.zip(self.last) {
+			Some((start, last)) => last - start,
+			None => return,
+		};
+
+		if bytes == 0 || elapsed < Estimator::MIN_DURATION {
+			return;
+		}
+
+		let sample = bytes as f64 / elapsed.as_secs_f64();
+
+		self.throughput = Some(match self.throughput {
+			Some(current) => current + Estimator::WEIGHT * (sample - current),
+			None => sample,
+		});
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	const RTT: time::Duration = time::Duration::from_millis(50);
+
+	// Receive a burst of packets every 25ms, returning the time of the last packet.
+	fn burst(estimator: &Estimator, start: time::Instant, packets: u32, bytes: usize) -> time::Instant {
+		let mut now = start;
+		for i in 0..packets {
+			now = start + time::Duration::from_millis(25) * i;
+			estimator.received_at(bytes, RTT, now);
+		}
+		now
+	}
+
+	#[test]
+	fn burst_rate() {
+		let estimator = Estimator::default();
+		let start = time::Instant::now();
+
+		// 5 packets after the first one over 125ms, counting only the time within the burst.
+		let last = burst(&estimator, start, 6, 2500);
+		assert_eq!(estimator.throughput(), None);
+
+		// The idle gap ends the burst.
+		let next = last + time::Duration::from_secs(1);
+		estimator.received_at(2500, RTT, next);
+		assert_eq!(estimator.throughput(), Some(100_000));
+	}
+
+	#[test]
+	fn smoothing() {
+		let estimator = Estimator::default();
+		let mut now = time::Instant::now();
+
+		now = burst(&estimator, now, 6, 2500) + time::Duration::from_secs(1);
+
+		// Twice the rate, which only moves the estimate by 1/8th of the difference.
+		now = burst(&estimator, now, 6, 5000) + time::Duration::from_secs(1);
+		estimator.received_at(0, RTT, now);
+
+		assert_eq!(estimator.throughput(), Some(112_500));
+	}
+
+	#[test]
+	fn short_burst() {
+		let estimator = Estimator::default();
+		let now = time::Instant::now();
+
+		// A burst shorter than MIN_DURATION can't be measured.
+		estimator.received_at(10_000, RTT, now);
+		estimator.received_at(10_000, RTT, now + time::Duration::from_millis(5));
+		estimator.received_at(10_000, RTT, now + time::Duration::from_secs(1));
+
+		assert_eq!(estimator.throughput(), None);
+	}
+
+	#[test]
+	fn continuous() {
+		let estimator = Estimator::default();
+		let start = time::Instant::now();
+
+		// A transfer without gaps is sampled every MAX_DURATION.
+		burst(&estimator, start, 11, 2500);
+		assert_eq!(estimator.throughput(), Some(100_000));
+	}
+}
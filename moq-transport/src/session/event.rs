@@ -0,0 +1,100 @@
+use tokio::sync::broadcast;
+
+use std::time;
+
+use crate::{message, MoqError, VarInt};
+
+use super::Stats;
+
+/// Something noteworthy that happened during a [Publisher](super::Publisher) or [Subscriber](super::Subscriber) session.
+///
+/// These are purely informational; the session handles each of them automatically.
+#[derive(Clone, Debug)]
+pub enum Event {
+	/// A subscription was accepted, either by us as a publisher or by the peer as a subscriber.
+	Subscribed { id: VarInt, name: String },
+
+	/// A subscription was rejected before any objects were sent.
+	SubscribeRejected {
+		id: VarInt,
+		name: String,
+		code: u32,
+		reason: String,
+	},
+
+	/// A subscription ended, with code 0 if the track ended cleanly.
+	SubscribeEnded {
+		id: VarInt,
+		name: String,
+		code: u32,
+		reason: String,
+	},
+
+	/// A data stream was reset before the group was fully transferred.
+	StreamReset { id: VarInt, group: VarInt, code: u32 },
+
+	/// The peer announced a namespace.
+	Announced { namespace: String },
+
+	/// The peer unannounced a namespace.
+	Unannounced { namespace: String },
+
+	/// The peer would like us to reconnect to a different URL.
+	GoAway { url: String },
+
+	/// The peer sent a custom message with a type registered via `register_custom`.
+	Custom(message::Custom),
+
+	/// A snapshot of the session statistics, sent every [Events::STATS_INTERVAL] while there are any [Events] handles.
+	Stats(Stats),
+
+	/// A control message could not be handled, but the session continues.
+	Error {
+		message: &'static str,
+		code: u32,
+		reason: String,
+	},
+}
+
+impl Event {
+	pub(crate) fn error<E: MoqError>(message: &'static str, err: &E) -> Self {
+		Self::Error {
+			message,
+			code: err.code(),
+			reason: err.reason(),
+		}
+	}
+}
+
+/// A stream of [Event]s for a session.
+///
+/// Each handle receives a copy of every event sent after it was created.
+/// Events are dropped if the handle falls too far behind rather than blocking the session.
+pub struct Events {
+	recv: broadcast::Receiver<Event>,
+}
+
+impl Events {
+	// The number of events buffered for each handle before the oldest are dropped.
+	pub(crate) const CAPACITY: usize = 1024;
+
+	/// How often an [Event::Stats] snapshot is sent.
+	pub const STATS_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+	pub(crate) fn new(recv: broadcast::Receiver<Event>) -> Self {
+		Self { recv }
+	}
+
+	/// Block until the next event, returning None when the session has been dropped.
+	pub async fn next(&mut self) -> Option<Event> {
+		loop {
+			match self.recv.recv().await {
+				Ok(event) => return Some(event),
+				Err(broadcast::error::RecvError::Lagged(count)) => {
+					log::warn!("dropped session events: count={}", count)
+				}
+				Err(broadcast::error::RecvError::Closed) => return None,
+			}
+		}
+	}
+}
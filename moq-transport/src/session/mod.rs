@@ -15,13 +15,21 @@
 mod client;
 mod control;
 mod error;
+mod estimate;
+mod event;
 mod publisher;
+mod resolver;
 mod server;
+mod stats;
 mod subscriber;
 
 pub use client::*;
 pub(crate) use control::*;
 pub use error::*;
+pub use estimate::*;
+pub use event::*;
 pub use publisher::*;
+pub use resolver::*;
 pub use server::*;
+pub use stats::*;
 pub use subscriber::*;
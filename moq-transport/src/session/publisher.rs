@@ -1,10 +1,16 @@
 use std::{
-	collections::{hash_map, HashMap},
-	sync::{Arc, Mutex},
+	collections::HashMap,
+	future,
+	sync::{atomic, Arc, Mutex},
 };
 
-use tokio::task::AbortHandle;
-use webtransport_quinn::Session;
+use tokio::{
+	sync::{broadcast as events, watch},
+	task::AbortHandle,
+	time,
+};
+use tracing::Instrument;
+use webtransport_quinn::{SendStream, Session};
 
 use crate::{
 	cache::{broadcast, segment, track, CacheError},
@@ -13,29 +19,102 @@ use crate::{
 	MoqError, VarInt,
 };
 
-use super::{Control, SessionError};
+use super::{Control, Event, Events, Resolved, Resolver, SessionCounters, SessionError, Stats, SubscribeCounters};
 
 /// Serves broadcasts over the network, automatically handling subscriptions and caching.
 // TODO Clone specific fields when a task actually needs it.
 #[derive(Clone, Debug)]
 pub struct Publisher {
-	// A map of active subscriptions, containing an abort handle to cancel them.
-	subscribes: Arc<Mutex<HashMap<VarInt, AbortHandle>>>,
+	// A map of active subscriptions, containing a handle to update or cancel them.
+	subscribes: Arc<Mutex<HashMap<VarInt, Subscribe>>>,
 	webtransport: Session,
 	control: Control,
-	source: broadcast::Subscriber,
+
+	// The broadcast served for SUBSCRIBEs with an empty namespace, if any.
+	source: Option<broadcast::Subscriber>,
+
+	// Looks up the broadcast for SUBSCRIBEs with a namespace, if any.
+	resolver: Option<Arc<dyn Resolver>>,
+
+	// Notable events, sent to any handles returned by events().
+	events: events::Sender<Event>,
+
+	// Counters for the entire session, see stats().
+	stats: Arc<SessionCounters>,
+
+	// The ID for the next track matching a prefix subscription.
+	next_match: Arc<atomic::AtomicU64>,
 }
 
 impl Publisher {
-	pub(crate) fn new(webtransport: Session, control: Control, source: broadcast::Subscriber) -> Self {
+	pub(crate) fn new(
+		webtransport: Session,
+		control: Control,
+		source: Option<broadcast::Subscriber>,
+		resolver: Option<Arc<dyn Resolver>>,
+	) -> Self {
 		Self {
 			webtransport,
 			control,
 			subscribes: Default::default(),
 			source,
+			resolver,
+			events: events::channel(Events::CAPACITY).0,
+			stats: Default::default(),
+			next_match: Arc::new(message::SubscribeMatch::MIN_TRACK.into_inner().into()),
+		}
+	}
+
+	/// Returns a snapshot of the statistics for this session and each active subscription.
+	pub fn stats(&self) -> Stats {
+		let subscriptions = self
+			.subscribes
+			.lock()
+			.unwrap()
+			.values()
+			.map(|subscribe| subscribe.stats.snapshot())
+			.collect();
+
+		self.stats.snapshot(&self.webtransport, subscriptions)
+	}
+
+	/// Returns a stream of [Event]s, such as subscriptions being accepted or ended.
+	pub fn events(&self) -> Events {
+		Events::new(self.events.subscribe())
+	}
+
+	// Send an event, ignoring the error when nobody is listening.
+	fn emit(&self, event: Event) {
+		self.events.send(event).ok();
+	}
+
+	// Send a stats snapshot, skipping the work when nobody is listening.
+	fn emit_stats(&self) {
+		if self.events.receiver_count() > 0 {
+			self.emit(Event::Stats(self.stats()));
 		}
 	}
 
+	/// Receive custom control messages with this type as [Event::Custom] instead of skipping them.
+	///
+	/// This requires the `control_framing` extension and the type must not be defined by this crate.
+	pub fn register_custom(&self, id: VarInt) -> Result<(), SessionError> {
+		self.control.register(id)
+	}
+
+	/// Send a custom control message, which requires the `control_framing` extension.
+	///
+	/// Returns [SessionError::CustomKnown] if the type is defined by this crate.
+	pub async fn send_custom(&self, msg: message::Custom) -> Result<(), SessionError> {
+		self.control.send_custom(msg).await
+	}
+
+	/// Ask the peer to reconnect, to the given URL or the same one if empty, ex. because we're shutting down.
+	pub async fn go_away(&self, url: &str) -> Result<(), SessionError> {
+		let msg = message::GoAway { url: url.to_string() };
+		self.control.send(msg).await
+	}
+
 	// TODO Serve a broadcast without sending an ANNOUNCE.
 	// fn serve(&mut self, broadcast: broadcast::Subscriber) -> Result<(), SessionError> {
 
@@ -43,19 +122,32 @@ impl Publisher {
 	// pub async fn subscribed(&mut self) -> Result<track::Producer, SessionError> {
 
 	pub async fn run(mut self) -> Result<(), SessionError> {
-		let res = self.run_inner().await;
+		let span = tracing::info_span!(
+			"session",
+			id = self.webtransport.stable_id(),
+			role = "publisher",
+			broadcast = self
+				.source
+				.as_ref()
+				.map(|source| source.id.as_str())
+				.unwrap_or_default(),
+		);
+
+		let res = self.run_inner().instrument(span).await;
 
 		// Terminate all active subscribes on error.
 		self.subscribes
 			.lock()
 			.unwrap()
 			.drain()
-			.for_each(|(_, abort)| abort.abort());
+			.for_each(|(_, subscribe)| subscribe.abort.abort());
 
 		res
 	}
 
 	pub async fn run_inner(&mut self) -> Result<(), SessionError> {
+		let mut stats = time::interval(Events::STATS_INTERVAL);
+
 		loop {
 			tokio::select! {
 				stream = self.webtransport.accept_uni() => {
@@ -66,26 +158,46 @@ impl Publisher {
 				msg = self.control.recv() => {
 					let msg = msg?;
 
-					log::info!("message received: {:?}", msg);
+					log::debug!("message received: {:?}", msg);
 					if let Err(err) = self.recv_message(&msg).await {
 						log::warn!("message error: {:?} {:?}", err, msg);
+						self.emit(Event::error(msg.name(), &err));
 					}
 				},
 				// No more broadcasts are available.
-				err = self.source.closed() => {
+				// A resolver can always serve more broadcasts, so we only stop when the session is closed.
+				err = Self::closed(self.source.clone()) => {
 					self.webtransport.close(err.code(), err.reason().as_bytes());
 					return Ok(());
 				},
+				_ = stats.tick() => self.emit_stats(),
 			}
 		}
 	}
 
+	async fn closed(source: Option<broadcast::Subscriber>) -> CacheError {
+		match source {
+			Some(source) => source.closed().await,
+			None => future::pending().await,
+		}
+	}
+
 	async fn recv_message(&mut self, msg: &Message) -> Result<(), SessionError> {
 		match msg {
 			Message::AnnounceOk(msg) => self.recv_announce_ok(msg).await,
 			Message::AnnounceError(msg) => self.recv_announce_error(msg).await,
 			Message::Subscribe(msg) => self.recv_subscribe(msg).await,
+			Message::SubscribeUpdate(msg) => self.recv_subscribe_update(msg).await,
 			Message::Unsubscribe(msg) => self.recv_unsubscribe(msg).await,
+			Message::GoAway(msg) => {
+				// It's up to the application to reconnect, since only it knows how.
+				self.emit(Event::GoAway { url: msg.url.clone() });
+				Ok(())
+			}
+			Message::Custom(msg) => {
+				self.emit(Event::Custom(msg.clone()));
+				Ok(())
+			}
 			_ => Err(SessionError::RoleViolation(msg.id())),
 		}
 	}
@@ -101,27 +213,26 @@ impl Publisher {
 	}
 
 	async fn recv_subscribe(&mut self, msg: &message::Subscribe) -> Result<(), SessionError> {
-		// Assume that the subscribe ID is unique for now.
-		let abort = match self.start_subscribe(msg.clone()) {
-			Ok(abort) => abort,
-			Err(err) => return self.reset_subscribe(msg.id, err).await,
-		};
-
-		// Insert the abort handle into the lookup table.
-		match self.subscribes.lock().unwrap().entry(msg.id) {
-			hash_map::Entry::Occupied(_) => return Err(CacheError::Duplicate.into()), // TODO fatal, because we already started the task
-			hash_map::Entry::Vacant(entry) => entry.insert(abort),
-		};
+		if self.subscribes.lock().unwrap().contains_key(&msg.id) {
+			return Err(CacheError::Duplicate.into());
+		}
 
-		self.control
-			.send(message::SubscribeOk {
+		if let Err(err) = self.start_subscribe(msg.clone(), SubscribeOptions::DEFAULT_PRIORITY, None) {
+			self.emit(Event::SubscribeRejected {
 				id: msg.id,
-				expires: VarInt::ZERO,
-			})
-			.await
+				name: msg.name.clone(),
+				code: err.code(),
+				reason: err.reason(),
+			});
+
+			return self.reset_subscribe(msg.id, err).await;
+		}
+
+		// SUBSCRIBE_OK is sent by the subscription task, once the track metadata is known if it was negotiated.
+		Ok(())
 	}
 
-	async fn reset_subscribe<E: MoqError>(&mut self, id: VarInt, err: E) -> Result<(), SessionError> {
+	async fn reset_subscribe<E: MoqError>(&self, id: VarInt, err: E) -> Result<(), SessionError> {
 		let msg = message::SubscribeReset {
 			id,
 			code: err.code(),
@@ -136,66 +247,334 @@ impl Publisher {
 		self.control.send(msg).await
 	}
 
-	fn start_subscribe(&mut self, msg: message::Subscribe) -> Result<AbortHandle, SessionError> {
-		// We currently don't use the namespace field in SUBSCRIBE
-		// Make sure the namespace is empty if it's provided.
-		if msg.namespace.as_ref().map_or(false, |namespace| !namespace.is_empty()) {
-			return Err(CacheError::NotFound.into());
+	async fn recv_subscribe_update(&mut self, msg: &message::SubscribeUpdate) -> Result<(), SessionError> {
+		self.control.ext.require_subscribe_update()?;
+
+		let subscribes = self.subscribes.lock().unwrap();
+		let subscribe = subscribes.get(&msg.id).ok_or(CacheError::NotFound)?;
+
+		// The running subscription task will pick up the change.
+		subscribe.options.send_modify(|options| {
+			options.priority = msg.priority;
+			options.end_group = msg.end_group.clone();
+		});
+
+		Ok(())
+	}
+
+	// Look up the broadcast for the namespace, using the source if it's empty.
+	fn resolve(&self, namespace: &str) -> Result<Resolved, SessionError> {
+		if namespace.is_empty() {
+			let source = self.source.clone().ok_or(CacheError::NotFound)?;
+			return Ok(source.into());
+		}
+
+		let resolver = self.resolver.as_ref().ok_or(CacheError::NotFound)?;
+		Ok(resolver.resolve(namespace)?)
+	}
+
+	// Spawn a task to serve the subscription and insert it into the lookup table.
+	fn start_subscribe(
+		&self,
+		msg: message::Subscribe,
+		priority: u8,
+		parent: Option<VarInt>,
+	) -> Result<(), SessionError> {
+		let source = self.resolve(msg.namespace.as_deref().unwrap_or_default())?;
+
+		if msg.prefix {
+			self.control.ext.require_subscribe_prefix()?;
 		}
 
-		let mut track = self.source.get_track(&msg.name)?;
+		// A prefix subscription doesn't have a single track; each match is served separately.
+		let track = match msg.prefix {
+			true => None,
+			false => Some(source.broadcast.get_track_ordered(&msg.name, msg.order)?),
+		};
+
+		let stats = Arc::new(SubscribeCounters::new(msg.id, &msg.name));
+
+		let (options, mut updates) = watch::channel(SubscribeOptions {
+			priority,
+			order: msg.order,
+			end_group: msg.end_group.clone(),
+		});
 
 		// TODO only clone the fields we need
-		let mut this = self.clone();
+		let this = self.clone();
+		let counters = stats.clone();
+
+		let span = tracing::info_span!("subscribe", id = %msg.id, track = %msg.name);
+		let id = msg.id;
+		let name = msg.name.clone();
+
+		// Hold the lock while spawning so the task can't remove the entry before it's inserted.
+		let mut subscribes = self.subscribes.lock().unwrap();
 
-		let handle = tokio::spawn(async move {
-			log::info!("serving track: name={}", track.name);
+		let handle = tokio::spawn(
+			async move {
+				log::info!("serving track: name={} prefix={}", msg.name, msg.prefix);
 
-			let res = this.run_subscribe(msg.id, &mut track).await;
-			if let Err(err) = &res {
-				log::warn!("failed to serve track: name={} err={:#?}", track.name, err);
+				// NOTE: This holds the resolved broadcast until the subscription ends.
+				let res = match track {
+					Some(mut track) => {
+						this.run_subscribe(msg.id, &source.broadcast, &mut track, &mut updates, &counters)
+							.await
+					}
+					None => this.run_prefix(&msg, &source.broadcast, &updates).await,
+				};
+
+				if let Err(err) = &res {
+					log::warn!("failed to serve track: name={} err={:#?}", msg.name, err);
+				}
+
+				// Make sure we send a reset at the end.
+				let err = res.err().unwrap_or(CacheError::Closed.into());
+
+				this.emit(Event::SubscribeEnded {
+					id: msg.id,
+					name: msg.name.clone(),
+					code: err.code(),
+					reason: err.reason(),
+				});
+
+				this.reset_subscribe(msg.id, err).await.ok();
+
+				// We're all done, so clean up the abort handle.
+				this.subscribes.lock().unwrap().remove(&msg.id);
 			}
+			.instrument(span),
+		);
+
+		subscribes.insert(
+			id,
+			Subscribe {
+				name,
+				abort: handle.abort_handle(),
+				options,
+				stats,
+				parent,
+			},
+		);
+
+		Ok(())
+	}
 
-			// Make sure we send a reset at the end.
-			let err = res.err().unwrap_or(CacheError::Closed.into());
-			this.reset_subscribe(msg.id, err).await.ok();
+	// Serve each track that matches the prefix as it's added to the broadcast, until the broadcast is closed.
+	async fn run_prefix(
+		&self,
+		msg: &message::Subscribe,
+		source: &broadcast::Subscriber,
+		updates: &watch::Receiver<SubscribeOptions>,
+	) -> Result<(), SessionError> {
+		// There's no track metadata for a prefix, but we can still send the broadcast metadata.
+		self.control
+			.send(message::SubscribeOk {
+				id: msg.id,
+				expires: VarInt::ZERO,
+				track: Default::default(),
+				broadcast: source.metadata(),
+			})
+			.await?;
 
-			// We're all done, so clean up the abort handle.
-			this.subscribes.lock().unwrap().remove(&msg.id);
+		self.emit(Event::Subscribed {
+			id: msg.id,
+			name: msg.name.clone(),
 		});
 
-		Ok(handle.abort_handle())
-	}
+		// NOTE: This returns each existing track first.
+		// Requested tracks are only added once the publisher answers, so names that don't exist are never matched.
+		let mut tracks = source.track_events();
 
-	async fn run_subscribe(&self, id: VarInt, track: &mut track::Subscriber) -> Result<(), SessionError> {
-		// TODO add an Ok method to track::Publisher so we can send SUBSCRIBE_OK
+		while let Some(event) = tracks.next().await? {
+			let name = match event {
+				broadcast::TrackEvent::Added(name) if name.starts_with(&msg.name) => name,
+				_ => continue,
+			};
 
-		while let Some(mut segment) = track.segment().await? {
-			// TODO only clone the fields we need
-			let this = self.clone();
+			let id = VarInt::try_from(self.next_match.fetch_add(1, atomic::Ordering::SeqCst))?;
+
+			// Tell the subscriber the name before the SUBSCRIBE_OK or any objects for this ID.
+			self.control
+				.send(message::SubscribeMatch {
+					id: msg.id,
+					track: id,
+					name: name.clone(),
+				})
+				.await?;
+
+			// Serve the match like a regular subscription, so it can be updated or cancelled individually.
+			let matched = message::Subscribe {
+				id,
+				name,
+				prefix: false,
+				..msg.clone()
+			};
 
-			tokio::spawn(async move {
-				if let Err(err) = this.run_segment(id, &mut segment).await {
-					log::warn!("failed to serve segment: {:?}", err)
-				}
-			});
+			// Inherit the priority of the prefix subscription.
+			let priority = updates.borrow().priority;
+
+			if let Err(err) = self.start_subscribe(matched, priority, Some(msg.id)) {
+				self.reset_subscribe(id, err).await?;
+			}
 		}
 
 		Ok(())
 	}
 
-	async fn run_segment(&self, id: VarInt, segment: &mut segment::Subscriber) -> Result<(), SessionError> {
+	async fn run_subscribe(
+		&self,
+		id: VarInt,
+		source: &broadcast::Subscriber,
+		track: &mut track::Subscriber,
+		updates: &mut watch::Receiver<SubscribeOptions>,
+		stats: &Arc<SubscribeCounters>,
+	) -> Result<(), SessionError> {
+		// Only wait for the metadata if we're going to send it, which could require an upstream SUBSCRIBE_OK.
+		// Otherwise the SUBSCRIBE_OK is sent immediately, and the metadata is ignored by the encoder anyway.
+		let metadata = match self.control.ext.subscribe_metadata {
+			true => track.metadata().await?,
+			false => Default::default(),
+		};
+
+		self.control
+			.send(message::SubscribeOk {
+				id,
+				expires: VarInt::ZERO,
+				track: metadata,
+				broadcast: source.metadata(),
+			})
+			.await?;
+
+		self.emit(Event::Subscribed {
+			id,
+			name: track.name.clone(),
+		});
+
+		// The largest group we've served so far, used to resolve relative locations.
+		let mut latest = None;
+
+		// The last group we'll serve, or None if the subscription is unbounded.
+		let mut end = updates.borrow_and_update().end(latest)?;
+
+		loop {
+			tokio::select! {
+				segment = track.segment() => {
+					let mut segment = match segment? {
+						Some(segment) => segment,
+						None => return Ok(()),
+					};
+
+					let sequence = segment.sequence;
+
+					// A relative end group is resolved against the first group, as there was no latest group before it.
+					if latest.is_none() && end.is_none() {
+						end = updates.borrow().end(Some(sequence))?;
+					}
+
+					// Stop once we've gone past the end group.
+					if end.is_some_and(|end| sequence > end) {
+						return Ok(());
+					}
+
+					latest = latest.max(Some(sequence));
+
+					// TODO only clone the fields we need
+					let this = self.clone();
+					let mut updates = updates.clone();
+					let stats = stats.clone();
+					let span = tracing::debug_span!("segment", group = %sequence);
+
+					tokio::spawn(async move {
+						if let Err(err) = this.run_segment(id, &mut segment, &mut updates, &stats).await {
+							log::warn!("failed to serve segment: {:?}", err)
+						}
+					}.instrument(span));
+
+					// Stop after serving the end group.
+					if end == Some(sequence) {
+						return Ok(());
+					}
+				},
+				// The sender is only dropped when the subscription is removed, and we're about to be aborted.
+				Ok(()) = updates.changed() => {
+					end = updates.borrow_and_update().end(latest)?;
+
+					// The subscription may have been shortened to a group we've already served.
+					if end.is_some_and(|end| latest.is_some_and(|latest| latest >= end)) {
+						return Ok(());
+					}
+				},
+			}
+		}
+	}
+
+	async fn run_segment(
+		&self,
+		id: VarInt,
+		segment: &mut segment::Subscriber,
+		updates: &mut watch::Receiver<SubscribeOptions>,
+		stats: &SubscribeCounters,
+	) -> Result<(), SessionError> {
 		log::trace!("serving group: {:?}", segment);
 
 		let mut stream = self.webtransport.open_uni().await?;
+		let _active = self.stats.stream();
+		stats.group(segment.sequence);
 
-		// Convert the u32 to a i32, since the Quinn set_priority is signed.
-		let priority = (segment.priority as i64 - i32::MAX as i64) as i32;
+		let priority = updates.borrow_and_update().stream_priority(segment);
 		stream.set_priority(priority).ok();
 
-		while let Some(mut fragment) = segment.fragment().await? {
+		// Reset the stream on error, ex. the segment was truncated, so the subscriber doesn't think it's complete.
+		if let Err(err) = self.write_segment(id, segment, updates, &mut stream, stats).await {
+			stream.reset(err.code()).ok();
+			self.stats.reset();
+
+			self.emit(Event::StreamReset {
+				id,
+				group: segment.sequence,
+				code: err.code(),
+			});
+
+			return Err(err);
+		}
+
+		Ok(())
+	}
+
+	async fn write_segment(
+		&self,
+		id: VarInt,
+		segment: &mut segment::Subscriber,
+		updates: &mut watch::Receiver<SubscribeOptions>,
+		stream: &mut SendStream,
+		stats: &SubscribeCounters,
+	) -> Result<(), SessionError> {
+		// The segment extensions are sent with the first fragment.
+		let mut first = true;
+
+		loop {
+			// Reprioritize the stream if the subscription was updated while we're waiting.
+			let mut fragment = tokio::select! {
+				fragment = segment.fragment() => match fragment? {
+					Some(fragment) => fragment,
+					None => break,
+				},
+				Ok(()) = updates.changed() => {
+					let priority = updates.borrow_and_update().stream_priority(segment);
+					stream.set_priority(priority).ok();
+					continue;
+				},
+			};
+
 			log::trace!("serving fragment: {:?}", fragment);
 
+			let extensions = match first {
+				true => fragment.extensions.or(&segment.extensions),
+				false => fragment.extensions.clone(),
+			};
+			first = false;
+
 			let object = message::Object {
 				track: id,
 
@@ -207,16 +586,29 @@ impl Publisher {
 				// Properties of the fragment
 				sequence: fragment.sequence,
 				size: fragment.size.map(VarInt::try_from).transpose()?,
+				extensions,
 			};
 
-			object
-				.encode(&mut stream, &self.control.ext)
-				.await
-				.map_err(|e| SessionError::Unknown(e.to_string()))?;
+			object.encode(stream, &self.control.ext).await?;
+
+			stats.object();
+
+			loop {
+				let chunk = tokio::select! {
+					chunk = fragment.chunk() => match chunk? {
+						Some(chunk) => chunk,
+						None => break,
+					},
+					Ok(()) = updates.changed() => {
+						let priority = updates.borrow_and_update().stream_priority(segment);
+						stream.set_priority(priority).ok();
+						continue;
+					},
+				};
 
-			while let Some(chunk) = fragment.chunk().await? {
 				//log::trace!("writing chunk: {:?}", chunk);
 				stream.write_all(&chunk).await?;
+				stats.bytes(chunk.len());
 			}
 		}
 
@@ -224,14 +616,168 @@ impl Publisher {
 	}
 
 	async fn recv_unsubscribe(&mut self, msg: &message::Unsubscribe) -> Result<(), SessionError> {
-		let abort = self
+		let subscribe = self
 			.subscribes
 			.lock()
 			.unwrap()
 			.remove(&msg.id)
 			.ok_or(CacheError::NotFound)?;
-		abort.abort();
 
-		self.reset_subscribe(msg.id, CacheError::Stop).await
+		// Also cancel each track that matched a prefix subscription.
+		let matches: Vec<(VarInt, Subscribe)> = {
+			let mut subscribes = self.subscribes.lock().unwrap();
+			let ids: Vec<VarInt> = subscribes
+				.iter()
+				.filter(|(_, subscribe)| subscribe.parent == Some(msg.id))
+				.map(|(id, _)| *id)
+				.collect();
+
+			ids.into_iter()
+				.filter_map(|id| subscribes.remove(&id).map(|subscribe| (id, subscribe)))
+				.collect()
+		};
+
+		for (id, subscribe) in std::iter::once((msg.id, subscribe)).chain(matches) {
+			subscribe.abort.abort();
+
+			// The task was aborted, so it won't emit an event itself.
+			let err = CacheError::Stop;
+			self.emit(Event::SubscribeEnded {
+				id,
+				name: subscribe.name,
+				code: err.code(),
+				reason: err.reason(),
+			});
+
+			self.reset_subscribe(id, err).await?;
+		}
+
+		Ok(())
+	}
+}
+
+// An active subscription, which can be updated or aborted.
+#[derive(Debug)]
+struct Subscribe {
+	name: String,
+	abort: AbortHandle,
+	options: watch::Sender<SubscribeOptions>,
+	stats: Arc<SubscribeCounters>,
+
+	// The prefix subscription that this track matched, if any.
+	parent: Option<VarInt>,
+}
+
+// The properties of a subscription that can be changed via SUBSCRIBE_UPDATE.
+#[derive(Clone, Debug)]
+struct SubscribeOptions {
+	// The subscriber priority, where **smaller** values are sent first.
+	priority: u8,
+
+	// The order of groups within the subscription.
+	order: message::GroupOrder,
+
+	// The last group to serve.
+	// NOTE: We always serve entire groups, so the end object is ignored.
+	end_group: message::SubscribeLocation,
+}
+
+impl SubscribeOptions {
+	// Used until the subscriber sends a SUBSCRIBE_UPDATE, allowing tracks to be moved in either direction.
+	const DEFAULT_PRIORITY: u8 = 128;
+
+	// Resolve the end group into an absolute sequence, relative to the latest group served.
+	// Returns None for a relative location until the latest group is known.
+	fn end(&self, latest: Option<VarInt>) -> Result<Option<VarInt>, SessionError> {
+		let end = match (&self.end_group, latest) {
+			(message::SubscribeLocation::None, _) => return Ok(None),
+			(message::SubscribeLocation::Absolute(end), _) => *end,
+			(_, None) => return Ok(None),
+			(message::SubscribeLocation::Latest(delta), Some(latest)) => {
+				VarInt::try_from(latest.into_inner().saturating_sub(delta.into_inner()))?
+			}
+			(message::SubscribeLocation::Future(delta), Some(latest)) => {
+				VarInt::try_from(latest.into_inner() + delta.into_inner())?
+			}
+		};
+
+		Ok(Some(end))
+	}
+
+	// Combine the subscriber priority and group order into a single Quinn priority.
+	// The subscriber priority takes precedence, using 24 bits based on the group order to break ties.
+	// NOTE: Quinn sends higher values first while a smaller priority is more important.
+	fn stream_priority(&self, segment: &segment::Info) -> i32 {
+		let subscriber = (u8::MAX - self.priority) as i32 - 128;
+
+		let order = match self.order {
+			// Use the publisher priority, saturating so the most important groups still share the top value.
+			message::GroupOrder::Publisher => (u32::MAX - segment.priority).min(0xff_ffff),
+
+			// Use the bottom 24 bits of the sequence, which will wrap around after a very long time.
+			message::GroupOrder::Ascending => 0xff_ffff - (segment.sequence.into_inner() & 0xff_ffff) as u32,
+			message::GroupOrder::Descending => (segment.sequence.into_inner() & 0xff_ffff) as u32,
+		};
+
+		(subscriber << 24) | order as i32
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn options(order: message::GroupOrder) -> SubscribeOptions {
+		SubscribeOptions {
+			priority: SubscribeOptions::DEFAULT_PRIORITY,
+			order,
+			end_group: message::SubscribeLocation::None,
+		}
+	}
+
+	// Mirrors moq-pub, where newer groups have a smaller (more important) priority.
+	fn segment(sequence: u32) -> segment::Info {
+		segment::Info {
+			sequence: VarInt::from_u32(sequence),
+			priority: u32::MAX - sequence,
+			expires: None,
+			extensions: Default::default(),
+		}
+	}
+
+	fn priorities(options: &SubscribeOptions) -> Vec<i32> {
+		(0..1024)
+			.map(|sequence| options.stream_priority(&segment(sequence)))
+			.collect()
+	}
+
+	#[test]
+	fn publisher_order() {
+		let priorities = priorities(&options(message::GroupOrder::Publisher));
+		assert!(
+			priorities.windows(2).all(|w| w[0] < w[1]),
+			"newer groups must be sent first"
+		);
+	}
+
+	#[test]
+	fn group_order() {
+		let descending = priorities(&options(message::GroupOrder::Descending));
+		assert!(descending.windows(2).all(|w| w[0] < w[1]));
+
+		let ascending = priorities(&options(message::GroupOrder::Ascending));
+		assert!(ascending.windows(2).all(|w| w[0] > w[1]));
+	}
+
+	#[test]
+	fn subscriber_priority() {
+		let mut high = options(message::GroupOrder::Publisher);
+		high.priority = 0;
+
+		let mut low = options(message::GroupOrder::Publisher);
+		low.priority = u8::MAX;
+
+		// The subscriber priority takes precedence over the group order.
+		assert!(high.stream_priority(&segment(0)) > low.stream_priority(&segment(1000)));
 	}
 }
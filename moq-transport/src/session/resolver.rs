@@ -0,0 +1,32 @@
+use std::{any::Any, fmt, sync::Arc};
+
+use crate::cache::{broadcast, CacheError};
+
+/// Looks up the broadcast for the namespace of each SUBSCRIBE, so a single [Publisher](super::Publisher) can serve many broadcasts.
+///
+/// This requires the `subscribe_split` extension, otherwise there's no namespace.
+pub trait Resolver: Send + Sync {
+	/// Return the broadcast with the given ID, or an error to reject the SUBSCRIBE.
+	fn resolve(&self, namespace: &str) -> Result<Resolved, CacheError>;
+}
+
+impl fmt::Debug for dyn Resolver {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Resolver").finish()
+	}
+}
+
+/// A broadcast returned by a [Resolver].
+#[derive(Clone)]
+pub struct Resolved {
+	pub broadcast: broadcast::Subscriber,
+
+	/// Held until every subscription to the broadcast has ended, ex. to keep it in a cache.
+	pub hold: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl From<broadcast::Subscriber> for Resolved {
+	fn from(broadcast: broadcast::Subscriber) -> Self {
+		Self { broadcast, hold: None }
+	}
+}
@@ -1,4 +1,6 @@
-use super::{Control, Publisher, SessionError, Subscriber};
+use std::sync::Arc;
+
+use super::{Control, Publisher, Resolver, SessionError, Subscriber};
 use crate::{cache::broadcast, setup};
 
 use webtransport_quinn::{RecvStream, SendStream, Session};
@@ -34,6 +36,12 @@ impl Server {
 				object_expires: true,
 				subscriber_id: true,
 				subscribe_split: true,
+				subscribe_update: false,
+				object_extensions: false,
+				control_framing: false,
+				subscribe_metadata: false,
+				subscribe_prefix: false,
+				push: false,
 			};
 		} else {
 			return Err(SessionError::Version(
@@ -64,7 +72,21 @@ impl Request {
 		setup.encode(&mut self.control.0).await?;
 
 		let control = Control::new(self.control.0, self.control.1, setup.extensions);
-		let publisher = Publisher::new(self.session, control, source);
+		let publisher = Publisher::new(self.session, control, Some(source), None);
+		Ok(publisher)
+	}
+
+	/// Accept the session as a publisher, using the resolver to serve the broadcast named by each SUBSCRIBE namespace.
+	///
+	/// This allows a single session to serve many broadcasts, and requires the `subscribe_split` extension.
+	pub async fn resolver(mut self, resolver: Arc<dyn Resolver>) -> Result<Publisher, SessionError> {
+		self.client.extensions.require_subscribe_split()?;
+
+		let setup = self.setup(setup::Role::Publisher)?;
+		setup.encode(&mut self.control.0).await?;
+
+		let control = Control::new(self.control.0, self.control.1, setup.extensions);
+		let publisher = Publisher::new(self.session, control, None, Some(resolver));
 		Ok(publisher)
 	}
 
@@ -74,7 +96,7 @@ impl Request {
 		setup.encode(&mut self.control.0).await?;
 
 		let control = Control::new(self.control.0, self.control.1, setup.extensions);
-		let subscriber = Subscriber::new(self.session, control, source);
+		let subscriber = Subscriber::new(self.session, control, Some(source));
 		Ok(subscriber)
 	}
 
@@ -113,4 +135,14 @@ impl Request {
 	pub fn role(&self) -> setup::Role {
 		self.client.role
 	}
+
+	/// The extensions offered by the client.
+	pub fn extensions(&self) -> &setup::Extensions {
+		&self.client.extensions
+	}
+
+	/// The token sent by the client, if any.
+	pub fn token(&self) -> Option<&str> {
+		self.client.token.as_deref()
+	}
 }
@@ -0,0 +1,250 @@
+use std::{
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time,
+};
+
+use crate::VarInt;
+
+/// A snapshot of the statistics for a session, see [Publisher::stats](super::Publisher::stats) and [Subscriber::stats](super::Subscriber::stats).
+#[derive(Clone, Debug)]
+pub struct Stats {
+	/// Statistics for each active subscription.
+	pub subscriptions: Vec<SubscribeStats>,
+
+	/// The number of data streams opened or accepted.
+	pub streams_opened: u64,
+
+	/// The number of data streams that were reset before the group was fully transferred.
+	pub streams_reset: u64,
+
+	/// The number of data streams currently being transferred.
+	pub queue_depth: u64,
+
+	/// The estimated round trip time of the connection.
+	pub rtt: time::Duration,
+
+	/// The current congestion window of the connection, in bytes.
+	pub cwnd: u64,
+
+	/// The number of packets sent and lost by the connection.
+	pub sent_packets: u64,
+	pub lost_packets: u64,
+
+	/// The number of UDP bytes sent and received by the connection.
+	pub sent_bytes: u64,
+	pub recv_bytes: u64,
+}
+
+/// A snapshot of the statistics for a single subscription.
+#[derive(Clone, Debug)]
+pub struct SubscribeStats {
+	/// The ID for this subscription.
+	pub id: VarInt,
+
+	/// The name of the track.
+	pub name: String,
+
+	/// The number of payload bytes sent or received.
+	pub bytes: u64,
+
+	/// The number of objects sent or received.
+	pub objects: u64,
+
+	/// The number of groups sent or received.
+	pub groups: u64,
+
+	/// The number of groups that were skipped, based on gaps in the group sequence.
+	/// This is approximate when groups are not transferred in ascending order.
+	pub groups_dropped: u64,
+}
+
+// Counters for the entire session, updated by each task.
+#[derive(Debug, Default)]
+pub(crate) struct SessionCounters {
+	streams_opened: AtomicU64,
+	streams_reset: AtomicU64,
+	streams_active: AtomicU64,
+}
+
+impl SessionCounters {
+	// Count a new data stream, which is considered active until the guard is dropped.
+	pub fn stream(self: &Arc<Self>) -> StreamGuard {
+		self.streams_opened.fetch_add(1, Ordering::Relaxed);
+		self.streams_active.fetch_add(1, Ordering::Relaxed);
+
+		StreamGuard { counters: self.clone() }
+	}
+
+	pub fn reset(&self) {
+		self.streams_reset.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn snapshot(&self, connection: &quinn::Connection, subscriptions: Vec<SubscribeStats>) -> Stats {
+		let quic = connection.stats();
+
+		Stats {
+			subscriptions,
+			streams_opened: self.streams_opened.load(Ordering::Relaxed),
+			streams_reset: self.streams_reset.load(Ordering::Relaxed),
+			queue_depth: self.streams_active.load(Ordering::Relaxed),
+			rtt: quic.path.rtt,
+			cwnd: quic.path.cwnd,
+			sent_packets: quic.path.sent_packets,
+			lost_packets: quic.path.lost_packets,
+			sent_bytes: quic.udp_tx.bytes,
+			recv_bytes: quic.udp_rx.bytes,
+		}
+	}
+}
+
+// Marks a data stream as no longer active when dropped.
+pub(crate) struct StreamGuard {
+	counters: Arc<SessionCounters>,
+}
+
+impl Drop for StreamGuard {
+	fn drop(&mut self) {
+		self.counters.streams_active.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+// Counters for a single subscription, updated by each stream.
+#[derive(Debug)]
+pub(crate) struct SubscribeCounters {
+	id: VarInt,
+	name: String,
+
+	bytes: AtomicU64,
+	objects: AtomicU64,
+	groups: AtomicU64,
+	groups_dropped: AtomicU64,
+
+	// One more than the largest group sequence, or zero if there hasn't been a group yet.
+	next_group: AtomicU64,
+}
+
+impl SubscribeCounters {
+	pub fn new(id: VarInt, name: &str) -> Self {
+		Self {
+			id,
+			name: name.to_string(),
+			bytes: Default::default(),
+			objects: Default::default(),
+			groups: Default::default(),
+			groups_dropped: Default::default(),
+			next_group: Default::default(),
+		}
+	}
+
+	pub fn group(&self, sequence: VarInt) {
+		let sequence = sequence.into_inner();
+		self.groups.fetch_add(1, Ordering::Relaxed);
+
+		let next = self.next_group.fetch_max(sequence + 1, Ordering::Relaxed);
+		if next == 0 {
+			// The first group, so there's nothing to compare against.
+		} else if sequence > next {
+			// Count any skipped groups as dropped.
+			self.groups_dropped.fetch_add(sequence - next, Ordering::Relaxed);
+		} else if sequence + 1 < next {
+			// An older group arrived late, so it wasn't dropped after all.
+			self.groups_dropped
+				.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |dropped| dropped.checked_sub(1))
+				.ok();
+		}
+	}
+
+	pub fn object(&self) {
+		self.objects.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn bytes(&self, size: usize) {
+		self.bytes.fetch_add(size as u64, Ordering::Relaxed);
+	}
+
+	pub fn snapshot(&self) -> SubscribeStats {
+		SubscribeStats {
+			id: self.id,
+			name: self.name.clone(),
+			bytes: self.bytes.load(Ordering::Relaxed),
+			objects: self.objects.load(Ordering::Relaxed),
+			groups: self.groups.load(Ordering::Relaxed),
+			groups_dropped: self.groups_dropped.load(Ordering::Relaxed),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn dropped_groups() {
+		let counters = SubscribeCounters::new(VarInt::from_u32(1), "video");
+
+		// The first group is never a gap, even if it's not zero.
+		counters.group(VarInt::from_u32(3));
+		counters.group(VarInt::from_u32(4));
+		assert_eq!(counters.snapshot().groups_dropped, 0);
+
+		// Groups 5 and 6 were skipped.
+		counters.group(VarInt::from_u32(7));
+		assert_eq!(counters.snapshot().groups_dropped, 2);
+
+		// Group 5 arrived late.
+		counters.group(VarInt::from_u32(5));
+		assert_eq!(counters.snapshot().groups_dropped, 1);
+
+		// The latest group again doesn't change anything.
+		counters.group(VarInt::from_u32(7));
+		assert_eq!(counters.snapshot().groups_dropped, 1);
+
+		// Late groups before the first one don't underflow.
+		counters.group(VarInt::from_u32(0));
+		counters.group(VarInt::from_u32(1));
+		counters.group(VarInt::from_u32(2));
+		assert_eq!(counters.snapshot().groups_dropped, 0);
+	}
+
+	#[test]
+	fn snapshot() {
+		let counters = SubscribeCounters::new(VarInt::from_u32(1), "video");
+
+		counters.group(VarInt::from_u32(0));
+		counters.object();
+		counters.bytes(100);
+		counters.object();
+		counters.bytes(50);
+		counters.group(VarInt::from_u32(1));
+		counters.object();
+		counters.bytes(10);
+
+		let stats = counters.snapshot();
+		assert_eq!(stats.id, VarInt::from_u32(1));
+		assert_eq!(stats.name, "video");
+		assert_eq!(stats.bytes, 160);
+		assert_eq!(stats.objects, 3);
+		assert_eq!(stats.groups, 2);
+		assert_eq!(stats.groups_dropped, 0);
+	}
+
+	#[test]
+	fn streams() {
+		let counters = Arc::new(SessionCounters::default());
+
+		let first = counters.stream();
+		let second = counters.stream();
+		assert_eq!(counters.streams_active.load(Ordering::Relaxed), 2);
+
+		drop(first);
+		counters.reset();
+		drop(second);
+
+		assert_eq!(counters.streams_opened.load(Ordering::Relaxed), 2);
+		assert_eq!(counters.streams_reset.load(Ordering::Relaxed), 1);
+		assert_eq!(counters.streams_active.load(Ordering::Relaxed), 0);
+	}
+}
@@ -1,17 +1,21 @@
+use tracing::Instrument;
 use webtransport_quinn::{RecvStream, Session};
 
 use std::{
-	collections::HashMap,
+	collections::{hash_map, HashMap},
+	future,
 	sync::{atomic, Arc, Mutex},
 };
 
+use tokio::sync::broadcast as events;
+
 use crate::{
 	cache::{broadcast, segment, track, CacheError},
-	coding::DecodeError,
+	coding::{self, DecodeError},
 	message,
 	message::Message,
-	session::{Control, SessionError},
-	VarInt,
+	session::{Control, Estimate, Estimator, Event, Events, SessionCounters, SessionError, Stats, SubscribeCounters},
+	MoqError, VarInt,
 };
 
 /// Receives broadcasts over the network, automatically handling subscriptions and caching.
@@ -22,7 +26,10 @@ pub struct Subscriber {
 	webtransport: Session,
 
 	// The list of active subscriptions, each guarded by an mutex.
-	subscribes: Arc<Mutex<HashMap<VarInt, track::Publisher>>>,
+	subscribes: Arc<Mutex<HashMap<VarInt, Subscribe>>>,
+
+	// Active prefix subscriptions, mapping the ID to the prefix.
+	prefixes: Arc<Mutex<HashMap<VarInt, String>>>,
 
 	// The sequence number for the next subscription.
 	next: Arc<atomic::AtomicU32>,
@@ -30,64 +37,431 @@ pub struct Subscriber {
 	// A channel for sending messages.
 	control: Control,
 
-	// All unknown subscribes comes here.
-	source: broadcast::Publisher,
+	// All unknown subscribes comes here, if any.
+	source: Option<broadcast::Publisher>,
+
+	// The broadcasts being fetched by namespace, see fetch().
+	fetches: Arc<Mutex<HashMap<String, broadcast::Publisher>>>,
+
+	// Notable events, sent to any handles returned by events().
+	events: events::Sender<Event>,
+
+	// Counters for the entire session, see stats().
+	stats: Arc<SessionCounters>,
+
+	// Measures the delivery rate of incoming data, see estimate().
+	estimator: Arc<Estimator>,
+
+	// Number each group after any group already in the track, see set_rebase().
+	rebase: Arc<atomic::AtomicBool>,
 }
 
 impl Subscriber {
-	pub(crate) fn new(webtransport: Session, control: Control, source: broadcast::Publisher) -> Self {
+	pub(crate) fn new(webtransport: Session, control: Control, source: Option<broadcast::Publisher>) -> Self {
 		Self {
 			webtransport,
 			subscribes: Default::default(),
+			prefixes: Default::default(),
 			next: Default::default(),
 			control,
 			source,
+			fetches: Default::default(),
+			events: events::channel(Events::CAPACITY).0,
+			stats: Default::default(),
+			estimator: Default::default(),
+			rebase: Default::default(),
+		}
+	}
+
+	/// Returns an estimate of the current throughput and RTT, used to choose between renditions.
+	pub fn estimate(&self) -> Estimate {
+		let stats = self.stats.snapshot(&self.webtransport, Vec::new());
+		Estimate::new(self.estimator.throughput(), &stats)
+	}
+
+	/// Returns a snapshot of the statistics for this session and each active subscription.
+	pub fn stats(&self) -> Stats {
+		let subscriptions = self
+			.subscribes
+			.lock()
+			.unwrap()
+			.values()
+			.map(|subscribe| subscribe.stats.snapshot())
+			.collect();
+
+		self.stats.snapshot(&self.webtransport, subscriptions)
+	}
+
+	/// Returns a stream of [Event]s, such as subscriptions being accepted or the peer sending a GOAWAY.
+	pub fn events(&self) -> Events {
+		Events::new(self.events.subscribe())
+	}
+
+	// Send an event, ignoring the error when nobody is listening.
+	fn emit(&self, event: Event) {
+		self.events.send(event).ok();
+	}
+
+	// Send a stats snapshot, skipping the work when nobody is listening.
+	fn emit_stats(&self) {
+		if self.events.receiver_count() > 0 {
+			self.emit(Event::Stats(self.stats()));
 		}
 	}
 
+	/// Receive custom control messages with this type as [Event::Custom] instead of skipping them.
+	///
+	/// This requires the `control_framing` extension and the type must not be defined by this crate.
+	pub fn register_custom(&self, id: VarInt) -> Result<(), SessionError> {
+		self.control.register(id)
+	}
+
+	/// Send a custom control message, which requires the `control_framing` extension.
+	///
+	/// Returns [SessionError::CustomKnown] if the type is defined by this crate.
+	pub async fn send_custom(&self, msg: message::Custom) -> Result<(), SessionError> {
+		self.control.send_custom(msg).await
+	}
+
+	/// Number each received group after the latest group already in the track, instead of using the group ID as is.
+	///
+	/// This is for a publisher that starts numbering from zero each time it connects, such as a restarted encoder resuming a broadcast.
+	/// Otherwise its groups would collide with the groups left in the cache by the previous publisher.
+	/// It only applies to subscriptions made after it's called.
+	pub fn set_rebase(&self, rebase: bool) {
+		self.rebase.store(rebase, atomic::Ordering::Relaxed);
+	}
+
+	/// Ask the peer to reconnect, to the given URL or the same one if empty, ex. because we're shutting down.
+	pub async fn go_away(&self, url: &str) -> Result<(), SessionError> {
+		let msg = message::GoAway { url: url.to_string() };
+		self.control.send(msg).await
+	}
+
 	pub async fn run(self) -> Result<(), SessionError> {
+		let span = tracing::info_span!(
+			"session",
+			id = self.webtransport.stable_id(),
+			role = "subscriber",
+			broadcast = self
+				.source
+				.as_ref()
+				.map(|source| source.id.as_str())
+				.unwrap_or_default(),
+		);
+
+		// Return any active subscriptions to their broadcast when the session ends, so another session can resume them.
+		let _requeue = Requeue(self.clone());
+
 		let inbound = self.clone().run_inbound();
 		let streams = self.clone().run_streams();
 		let source = self.clone().run_source();
+		let stats = self.clone().run_stats();
 
 		// Return the first error.
-		tokio::select! {
-			res = inbound => res,
-			res = streams => res,
-			res = source => res,
+		async {
+			tokio::select! {
+				res = inbound => res,
+				res = streams => res,
+				res = source => res,
+				res = stats => res,
+			}
+		}
+		.instrument(span)
+		.await
+	}
+
+	/// Change the priority and end group of the active subscription for the given track.
+	///
+	/// The namespace is the ID of a broadcast passed to [Self::fetch], or empty for the source broadcast.
+	/// A smaller priority is more important. This requires the SUBSCRIBE_UPDATE extension.
+	pub async fn update(
+		&self,
+		namespace: &str,
+		name: &str,
+		priority: u8,
+		end_group: message::SubscribeLocation,
+	) -> Result<(), SessionError> {
+		self.control.ext.require_subscribe_update()?;
+
+		let id = self
+			.subscribes
+			.lock()
+			.unwrap()
+			.iter()
+			.find(|(_, subscribe)| subscribe.namespace == namespace && subscribe.track.name == name)
+			.map(|(id, _)| *id)
+			.ok_or(CacheError::NotFound)?;
+
+		let msg = message::SubscribeUpdate {
+			id,
+			end_group,
+			end_object: message::SubscribeLocation::None,
+			priority,
+			params: Default::default(),
+		};
+
+		self.control.send(msg).await
+	}
+
+	/// Subscribe to every track with a name starting with the prefix, or every track if the prefix is empty.
+	///
+	/// Each matching track is inserted into the broadcast under its full name as the publisher creates it.
+	/// This requires the `subscribe_prefix` extension.
+	pub async fn subscribe_prefix(&self, prefix: &str, order: message::GroupOrder) -> Result<(), SessionError> {
+		self.control.ext.require_subscribe_prefix()?;
+		self.source.as_ref().ok_or(CacheError::NotFound)?;
+
+		let id = self.next_id()?;
+		self.prefixes.lock().unwrap().insert(id, prefix.to_string());
+
+		let msg = message::Subscribe {
+			id,
+			namespace: self.control.ext.subscribe_split.then(|| "".to_string()),
+			name: prefix.to_string(),
+			prefix: true,
+
+			// TODO correctly support these
+			start_group: message::SubscribeLocation::Latest(VarInt::ZERO),
+			start_object: message::SubscribeLocation::Absolute(VarInt::ZERO),
+			end_group: message::SubscribeLocation::None,
+			end_object: message::SubscribeLocation::None,
+
+			order,
+			params: Default::default(),
+		};
+
+		self.control.send(msg).await
+	}
+
+	/// Subscribe to each track requested from the broadcast, using its ID as the SUBSCRIBE namespace.
+	///
+	/// This allows a single session to fetch many broadcasts, and requires the `subscribe_split` extension.
+	/// Returns once the broadcast is closed, cancelling any active subscriptions for it.
+	/// If this is dropped first, ex. because the session failed, the subscribed tracks are returned to the broadcast so another fetch can resume them.
+	pub async fn fetch(&self, mut broadcast: broadcast::Publisher) -> Result<(), SessionError> {
+		self.control.ext.require_subscribe_split()?;
+
+		let namespace = broadcast.id.clone();
+
+		match self.fetches.lock().unwrap().entry(namespace.clone()) {
+			hash_map::Entry::Occupied(_) => return Err(CacheError::Duplicate.into()),
+			hash_map::Entry::Vacant(entry) => entry.insert(broadcast.clone()),
+		};
+
+		// Unsubscribe when we return, or when this future is dropped.
+		let _fetch = Fetch {
+			subscriber: self.clone(),
+			namespace: namespace.clone(),
+		};
+
+		match self.subscribe_tracks(&mut broadcast, &namespace).await {
+			Err(SessionError::Cache(CacheError::Closed)) => Ok(()),
+			res => res,
 		}
 	}
 
+	// Return the broadcast for the namespace, using the source if it's empty.
+	fn broadcast(&self, namespace: &str) -> Option<broadcast::Publisher> {
+		match namespace.is_empty() {
+			true => self.source.clone(),
+			false => self.fetches.lock().unwrap().get(namespace).cloned(),
+		}
+	}
+
+	// Remove each subscription for the namespace, or every subscription if None, returning the tracks to their broadcast.
+	fn requeue(&self, namespace: Option<&str>) -> Vec<VarInt> {
+		let mut subscribes = self.subscribes.lock().unwrap();
+		let ids: Vec<VarInt> = subscribes
+			.iter()
+			.filter(|(_, subscribe)| namespace.is_none_or(|namespace| subscribe.namespace == namespace))
+			.map(|(id, _)| *id)
+			.collect();
+
+		for id in &ids {
+			let subscribe = match subscribes.remove(id) {
+				Some(subscribe) => subscribe,
+				None => continue,
+			};
+
+			// Requeue the track so the next subscription skips any groups we already have, when it continues the same numbering.
+			// A publisher that restarts its numbering uses set_rebase instead, so its groups never collide.
+			// It's dropped, and therefore closed, if the broadcast is closed.
+			let mut track = subscribe.track;
+			track.set_merge(true);
+
+			if let Some(mut broadcast) = self.broadcast(&subscribe.namespace) {
+				broadcast.requeue_track(track).ok();
+			}
+		}
+
+		ids
+	}
+
+	// Choose the ID for the next subscription, which is always below SubscribeMatch::MIN_TRACK.
+	fn next_id(&self) -> Result<VarInt, SessionError> {
+		let max = message::SubscribeMatch::MIN_TRACK.into_inner() as u32;
+
+		let id = self
+			.next
+			.fetch_update(atomic::Ordering::SeqCst, atomic::Ordering::SeqCst, |id| {
+				(id < max).then_some(id + 1)
+			})
+			.map_err(|_| coding::BoundsExceeded)?;
+
+		Ok(VarInt::from_u32(id))
+	}
+
 	async fn run_inbound(mut self) -> Result<(), SessionError> {
 		loop {
 			let msg = self.control.recv().await?;
 
-			log::info!("message received: {:?}", msg);
-			if let Err(err) = self.recv_message(&msg) {
+			log::debug!("message received: {:?}", msg);
+			if let Err(err) = self.recv_message(&msg).await {
 				log::warn!("message error: {:?} {:?}", err, msg);
+				self.emit(Event::error(msg.name(), &err));
 			}
 		}
 	}
 
-	fn recv_message(&mut self, msg: &Message) -> Result<(), SessionError> {
+	async fn recv_message(&mut self, msg: &Message) -> Result<(), SessionError> {
 		match msg {
-			Message::Announce(_) => Ok(()),       // don't care
-			Message::Unannounce(_) => Ok(()),     // also don't care
-			Message::SubscribeOk(_msg) => Ok(()), // don't care
-			Message::SubscribeReset(msg) => self.recv_subscribe_error(msg.id, CacheError::Reset(msg.code)),
-			Message::SubscribeFin(msg) => self.recv_subscribe_error(msg.id, CacheError::Closed),
-			Message::SubscribeError(msg) => self.recv_subscribe_error(msg.id, CacheError::Reset(msg.code)),
-			Message::GoAway(_msg) => unimplemented!("GOAWAY"),
+			Message::Announce(msg) => {
+				self.emit(Event::Announced {
+					namespace: msg.namespace.clone(),
+				});
+				Ok(())
+			}
+			Message::Unannounce(msg) => {
+				self.emit(Event::Unannounced {
+					namespace: msg.namespace.clone(),
+				});
+				Ok(())
+			}
+			Message::SubscribeOk(msg) => self.recv_subscribe_ok(msg),
+			Message::SubscribeMatch(msg) => self.recv_subscribe_match(msg).await,
+			Message::SubscribeReset(msg) => {
+				let name = self.recv_subscribe_error(msg.id, CacheError::Reset(msg.code))?;
+				self.emit(Event::SubscribeEnded {
+					id: msg.id,
+					name,
+					code: msg.code,
+					reason: msg.reason.clone(),
+				});
+				Ok(())
+			}
+			Message::SubscribeFin(msg) => {
+				let name = self.recv_subscribe_error(msg.id, CacheError::Closed)?;
+				self.emit(Event::SubscribeEnded {
+					id: msg.id,
+					name,
+					code: 0,
+					reason: String::new(),
+				});
+				Ok(())
+			}
+			Message::SubscribeError(msg) => {
+				let name = self.recv_subscribe_error(msg.id, CacheError::Reset(msg.code))?;
+				self.emit(Event::SubscribeRejected {
+					id: msg.id,
+					name,
+					code: msg.code,
+					reason: msg.reason.clone(),
+				});
+				Ok(())
+			}
+			Message::GoAway(msg) => {
+				// It's up to the application to reconnect, since only it knows how.
+				self.emit(Event::GoAway { url: msg.url.clone() });
+				Ok(())
+			}
+			Message::Custom(msg) => {
+				self.emit(Event::Custom(msg.clone()));
+				Ok(())
+			}
 			_ => Err(SessionError::RoleViolation(msg.id())),
 		}
 	}
 
-	fn recv_subscribe_error(&mut self, id: VarInt, err: CacheError) -> Result<(), SessionError> {
+	fn recv_subscribe_ok(&mut self, msg: &message::SubscribeOk) -> Result<(), SessionError> {
+		let prefix = self.prefixes.lock().unwrap().get(&msg.id).cloned();
+
+		let (name, namespace) = match prefix {
+			// There's no track for a prefix subscription, only the broadcast metadata.
+			Some(prefix) => (prefix, String::new()),
+			None => {
+				let mut subscribes = self.subscribes.lock().unwrap();
+				let subscribe = subscribes.get_mut(&msg.id).ok_or(CacheError::NotFound)?;
+
+				// Unblock any subscribers waiting for the metadata, even if the peer didn't send any.
+				subscribe.track.set_metadata(msg.track.clone());
+				(subscribe.track.name.clone(), subscribe.namespace.clone())
+			}
+		};
+
+		if self.control.ext.subscribe_metadata {
+			if let Some(mut broadcast) = self.broadcast(&namespace) {
+				broadcast.set_metadata(msg.broadcast.clone());
+			}
+		}
+
+		self.emit(Event::Subscribed { id: msg.id, name });
+
+		Ok(())
+	}
+
+	async fn recv_subscribe_match(&mut self, msg: &message::SubscribeMatch) -> Result<(), SessionError> {
+		self.control.ext.require_subscribe_prefix()?;
+
+		let prefix = self
+			.prefixes
+			.lock()
+			.unwrap()
+			.get(&msg.id)
+			.cloned()
+			.ok_or(CacheError::NotFound)?;
+
+		if !msg.name.starts_with(&prefix) {
+			return Err(CacheError::NotFound.into());
+		}
+
+		let mut source = self.source.clone().ok_or(CacheError::NotFound)?;
+
+		let track = match source.create_track(&msg.name) {
+			Ok(track) => track,
+
+			// The track is already cached or subscribed individually, so we don't need a second copy.
+			Err(CacheError::Duplicate) => return self.control.send(message::Unsubscribe { id: msg.track }).await,
+			Err(err) => return Err(err.into()),
+		};
+
+		let stats = Arc::new(SubscribeCounters::new(msg.track, &msg.name));
+
+		match self.subscribes.lock().unwrap().entry(msg.track) {
+			hash_map::Entry::Occupied(_) => return Err(CacheError::Duplicate.into()),
+			hash_map::Entry::Vacant(entry) => entry.insert(Subscribe {
+				track,
+				stats,
+				namespace: String::new(),
+				offset: VarInt::ZERO,
+			}),
+		};
+
+		Ok(())
+	}
+
+	// Close the subscription, returning the track name.
+	fn recv_subscribe_error(&mut self, id: VarInt, err: CacheError) -> Result<String, SessionError> {
+		if let Some(prefix) = self.prefixes.lock().unwrap().remove(&id) {
+			return Ok(prefix);
+		}
+
 		let mut subscribes = self.subscribes.lock().unwrap();
 		let subscribe = subscribes.remove(&id).ok_or(CacheError::NotFound)?;
-		subscribe.close(err)?;
+		let name = subscribe.track.name.clone();
+		subscribe.track.close(err)?;
 
-		Ok(())
+		Ok(name)
 	}
 
 	async fn run_streams(self) -> Result<(), SessionError> {
@@ -96,44 +470,92 @@ impl Subscriber {
 			let stream = self.webtransport.accept_uni().await?;
 			let this = self.clone();
 
-			tokio::spawn(async move {
-				if let Err(err) = this.run_stream(stream).await {
-					log::warn!("failed to receive stream: err={:#?}", err);
+			tokio::spawn(
+				async move {
+					if let Err(err) = this.run_stream(stream).await {
+						log::warn!("failed to receive stream: err={:#?}", err);
+					}
 				}
-			});
+				.in_current_span(),
+			);
 		}
 	}
 
 	async fn run_stream(self, mut stream: RecvStream) -> Result<(), SessionError> {
+		let _active = self.stats.stream();
+
 		// Decode the object on the data stream.
-		let mut object = message::Object::decode(&mut stream, &self.control.ext)
-			.await
-			.map_err(|e| SessionError::Unknown(e.to_string()))?;
+		let object = message::Object::decode(&mut stream, &self.control.ext).await?;
 
 		log::trace!("first object: {:?}", object);
 
 		// A new scope is needed because the async compiler is dumb
-		let mut segment = {
+		let (mut segment, stats, name) = {
 			let mut subscribes = self.subscribes.lock().unwrap();
-			let track = subscribes.get_mut(&object.track).ok_or(CacheError::NotFound)?;
+			let subscribe = subscribes.get_mut(&object.track).ok_or(CacheError::NotFound)?;
 
-			track.create_segment(segment::Info {
-				sequence: object.group,
+			let sequence = object.group.into_inner() + subscribe.offset.into_inner();
+			let segment = subscribe.track.create_segment(segment::Info {
+				sequence: VarInt::try_from(sequence)?,
 				priority: object.priority,
 				expires: object.expires,
-			})?
+				extensions: object.extensions.clone(),
+			})?;
+
+			(segment, subscribe.stats.clone(), subscribe.track.name.clone())
 		};
 
+		stats.group(object.group);
+
 		log::trace!("received segment: {:?}", segment);
 
+		let (id, group) = (object.track, object.group);
+
+		let span = tracing::debug_span!("segment", track = %name, group = %group);
+
+		if let Err(err) = self
+			.read_segment(&mut stream, object, &mut segment, &stats)
+			.instrument(span)
+			.await
+		{
+			// Only a reset or an early end means the group was cut short, so it's not mistaken for a complete group.
+			let closed = match &err {
+				err if err.reset_code().is_some() => CacheError::Truncated,
+				SessionError::UnexpectedEnd(_) => CacheError::Truncated,
+				SessionError::Cache(err) => err.clone(),
+				err => CacheError::Reset(err.code()),
+			};
+
+			segment.close(closed).ok();
+
+			if let Some(code) = err.reset_code() {
+				self.stats.reset();
+				self.emit(Event::StreamReset { id, group, code });
+			}
+
+			return Err(err);
+		}
+
+		Ok(())
+	}
+
+	async fn read_segment(
+		&self,
+		stream: &mut RecvStream,
+		mut object: message::Object,
+		segment: &mut segment::Publisher,
+		stats: &SubscribeCounters,
+	) -> Result<(), SessionError> {
 		// Create the first fragment
-		let mut fragment = segment.push_fragment(object.sequence, object.size.map(usize::from))?;
+		let mut fragment =
+			segment.push_fragment(object.sequence, object.size.map(usize::from), object.extensions.clone())?;
 		let mut remain = object.size.map(usize::from);
+		stats.object();
 
 		loop {
 			if let Some(0) = remain {
 				// Decode the next object from the stream.
-				let next = match message::Object::decode(&mut stream, &self.control.ext).await {
+				let next = match message::Object::decode(stream, &self.control.ext).await {
 					Ok(next) => next,
 
 					// No more objects
@@ -155,8 +577,10 @@ impl Subscriber {
 				object = next;
 
 				// Create a new object.
-				fragment = segment.push_fragment(object.sequence, object.size.map(usize::from))?;
+				fragment =
+					segment.push_fragment(object.sequence, object.size.map(usize::from), object.extensions.clone())?;
 				remain = object.size.map(usize::from);
+				stats.object();
 
 				log::trace!("next fragment: {:?}", fragment);
 			}
@@ -174,6 +598,8 @@ impl Subscriber {
 					remain = remain.map(|r| r - data.bytes.len());
 
 					log::trace!("next chunk: {:?}", data);
+					stats.bytes(data.bytes.len());
+					self.estimator.received(data.bytes.len(), self.webtransport.rtt());
 					fragment.chunk(data.bytes)?;
 				}
 			}
@@ -182,19 +608,55 @@ impl Subscriber {
 		Ok(())
 	}
 
-	async fn run_source(mut self) -> Result<(), SessionError> {
+	async fn run_stats(self) -> Result<(), SessionError> {
+		let mut interval = tokio::time::interval(Events::STATS_INTERVAL);
+
+		loop {
+			interval.tick().await;
+			self.emit_stats();
+		}
+	}
+
+	async fn run_source(self) -> Result<(), SessionError> {
+		match self.source.clone() {
+			Some(mut source) => self.subscribe_tracks(&mut source, "").await,
+
+			// Only fetched broadcasts are subscribed, so we run until the session is closed.
+			None => future::pending().await,
+		}
+	}
+
+	// Send a SUBSCRIBE for each track requested from the broadcast.
+	async fn subscribe_tracks(&self, source: &mut broadcast::Publisher, namespace: &str) -> Result<(), SessionError> {
 		loop {
 			// NOTE: This returns Closed when the source is closed.
-			let track = self.source.next_track().await?;
+			let track = source.next_track().await?;
 			let name = track.name.clone();
+			let order = track.order;
+
+			// Start after any group left by a previous publisher, see set_rebase().
+			let offset = match self.rebase.load(atomic::Ordering::Relaxed) {
+				true => track
+					.latest()
+					.map_or(Ok(VarInt::ZERO), |latest| VarInt::try_from(latest.into_inner() + 1))?,
+				false => VarInt::ZERO,
+			};
 
-			let id = VarInt::from_u32(self.next.fetch_add(1, atomic::Ordering::SeqCst));
-			self.subscribes.lock().unwrap().insert(id, track);
+			let id = self.next_id()?;
+			let stats = Arc::new(SubscribeCounters::new(id, &name));
+			let subscribe = Subscribe {
+				track,
+				stats,
+				namespace: namespace.to_string(),
+				offset,
+			};
+			self.subscribes.lock().unwrap().insert(id, subscribe);
 
 			let msg = message::Subscribe {
 				id,
-				namespace: self.control.ext.subscribe_split.then(|| "".to_string()),
+				namespace: self.control.ext.subscribe_split.then(|| namespace.to_string()),
 				name,
+				prefix: false,
 
 				// TODO correctly support these
 				start_group: message::SubscribeLocation::Latest(VarInt::ZERO),
@@ -202,6 +664,7 @@ impl Subscriber {
 				end_group: message::SubscribeLocation::None,
 				end_object: message::SubscribeLocation::None,
 
+				order,
 				params: Default::default(),
 			};
 
@@ -209,3 +672,46 @@ impl Subscriber {
 		}
 	}
 }
+
+// An active subscription, written to by each incoming stream.
+#[derive(Debug)]
+struct Subscribe {
+	track: track::Publisher,
+	stats: Arc<SubscribeCounters>,
+
+	// The namespace of the broadcast, which is empty for the source.
+	namespace: String,
+
+	// Added to each group ID to get the sequence in the track, see Subscriber::set_rebase.
+	offset: VarInt,
+}
+
+// Requeues every subscription when the session ends.
+struct Requeue(Subscriber);
+
+impl Drop for Requeue {
+	fn drop(&mut self) {
+		self.0.requeue(None);
+	}
+}
+
+// A broadcast being fetched, which unsubscribes from each of its tracks when dropped.
+struct Fetch {
+	subscriber: Subscriber,
+	namespace: String,
+}
+
+impl Drop for Fetch {
+	fn drop(&mut self) {
+		let ids = self.subscriber.requeue(Some(&self.namespace));
+		self.subscriber.fetches.lock().unwrap().remove(&self.namespace);
+
+		// Sending is async, so unsubscribe in the background.
+		let control = self.subscriber.control.clone();
+		tokio::spawn(async move {
+			for id in ids {
+				control.send(message::Unsubscribe { id }).await.ok();
+			}
+		});
+	}
+}
@@ -6,10 +6,11 @@ use crate::{
 
 use crate::coding::{AsyncRead, AsyncWrite};
 
+use std::fmt;
+
 /// Sent by the client to setup the session.
 // NOTE: This is not a message type, but rather the control stream header.
 // Proposal: https://github.com/moq-wg/moq-transport/issues/138
-#[derive(Debug)]
 pub struct Client {
 	/// The list of supported versions in preferred order.
 	pub versions: Versions,
@@ -20,11 +21,17 @@ pub struct Client {
 	/// A list of known/offered extensions.
 	pub extensions: Extensions,
 
+	/// A credential chosen by the client, ex. so only the same publisher can take over its broadcast.
+	pub token: Option<String>,
+
 	/// Unknown parameters.
 	pub params: Params,
 }
 
 impl Client {
+	// A custom parameter, numbered like the extensions since it's not part of the draft either.
+	const TOKEN: VarInt = VarInt::from_u32(0xf0007);
+
 	/// Decode a client setup message.
 	pub async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
 		let typ = VarInt::decode(r).await?;
@@ -47,11 +54,13 @@ impl Client {
 		}
 
 		let extensions = Extensions::load(&mut params).await?;
+		let token = params.get::<String>(Self::TOKEN).await?;
 
 		Ok(Self {
 			versions,
 			role,
 			extensions,
+			token,
 			params,
 		})
 	}
@@ -65,8 +74,69 @@ impl Client {
 		params.set(VarInt::from_u32(0), self.role).await?;
 		self.extensions.store(&mut params).await?;
 
+		if let Some(token) = &self.token {
+			params.set(Self::TOKEN, token.clone()).await?;
+		}
+
 		params.encode(w).await?;
 
 		Ok(())
 	}
 }
+
+// Don't log the token, since the SETUP is logged.
+impl fmt::Debug for Client {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Client")
+			.field("versions", &self.versions)
+			.field("role", &self.role)
+			.field("extensions", &self.extensions)
+			.field("token", &self.token.as_ref().map(|_| "<redacted>"))
+			.field("params", &self.params)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io;
+
+	use super::*;
+	use crate::setup::Version;
+
+	fn client(token: Option<&str>) -> Client {
+		Client {
+			versions: vec![Version::DRAFT_01].into(),
+			role: Role::Publisher,
+			extensions: Extensions {
+				push: true,
+				..Default::default()
+			},
+			token: token.map(str::to_string),
+			params: Default::default(),
+		}
+	}
+
+	#[tokio::test]
+	async fn token() {
+		let mut buf = Vec::new();
+		client(Some("secret")).encode(&mut buf).await.unwrap();
+
+		let decoded = Client::decode(&mut io::Cursor::new(buf)).await.unwrap();
+		assert_eq!(decoded.token.as_deref(), Some("secret"));
+		assert!(decoded.extensions.push);
+
+		// It's not left in the unknown parameters, nor logged.
+		assert!(!decoded.params.has(Client::TOKEN));
+		assert!(!format!("{:?}", decoded).contains("secret"));
+	}
+
+	#[tokio::test]
+	async fn no_token() {
+		let mut buf = Vec::new();
+		client(None).encode(&mut buf).await.unwrap();
+
+		let decoded = Client::decode(&mut io::Cursor::new(buf)).await.unwrap();
+		assert_eq!(decoded.token, None);
+	}
+}
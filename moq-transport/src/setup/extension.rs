@@ -8,7 +8,7 @@ use paste::paste;
 /// This is a custom extension scheme to allow/require draft PRs.
 ///
 /// By convention, the extension number is the PR number + 0xe0000.
-
+/// Extensions that don't have a PR yet use 0xf0000 + a local number instead, so they can't collide.
 macro_rules! extensions {
     {$($name:ident = $val:expr,)*} => {
 		#[derive(Clone, Default, Debug)]
@@ -81,4 +81,24 @@ extensions! {
 
 	// optional: SUBSCRIBE contains namespace/name tuple: https://github.com/moq-wg/moq-transport/pull/277
 	subscribe_split = 0xe0115,
+
+	// optional: SUBSCRIBE_UPDATE changes the priority or end group of an active subscription.
+	subscribe_update = 0xf0001,
+
+	// optional: OBJECT contains a list of header extensions, such as the capture timestamp.
+	object_extensions = 0xf0002,
+
+	// optional: each control message is prefixed with its length, so unknown message types can be skipped.
+	control_framing = 0xf0003,
+
+	// optional: SUBSCRIBE_OK contains metadata about the track and broadcast, such as the codec.
+	subscribe_metadata = 0xf0004,
+
+	// optional: SUBSCRIBE can match every track with a name prefix, each delivered via SUBSCRIBE_MATCH.
+	// The subscriber must choose IDs below SubscribeMatch::MIN_TRACK, as larger IDs are chosen by the publisher.
+	subscribe_prefix = 0xf0005,
+
+	// optional: the publisher is a relay pushing a broadcast it's already the origin of, so the server shouldn't advertise itself.
+	// This isn't a capability, so it's only offered by Client::pusher.
+	push = 0xf0006,
 }